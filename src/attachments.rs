@@ -0,0 +1,225 @@
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+
+//files attached to nodes. Attaching a file copies it into data/attachments named after the sha1 of its content,
+//so the same file attached twice is stored once and the workspace keeps working when the original moves or goes.
+//urls and paths that importers point nodes to stay as they are, as external attachments.
+
+const STORE_FOLDER: &str = "data/attachments";
+const THUMBNAIL_SIZE: u32 = 256; //longest side, in pixels
+const PREVIEW_LINES: usize = 6;
+const PREVIEW_LINE_LENGTH: usize = 60;
+const PREVIEW_BYTES: u64 = 16 * 1024; //read from text files for their preview
+
+//the png crate is the only image decoder around, other images get a label saying so
+const IMAGE_EXTENSIONS: [&str; 1] = ["png"];
+const OTHER_IMAGE_EXTENSIONS: [&str; 9] = ["jpg", "jpeg", "gif", "webp", "bmp", "tif", "tiff", "heic", "avif"];
+const TEXT_EXTENSIONS: [&str; 24] = [
+    "txt", "md", "markdown", "org", "csv", "tsv", "json", "toml", "yaml", "yml", "xml", "html", "css", "js", "ts",
+    "rs", "py", "c", "h", "cpp", "java", "go", "tex", "bib",
+];
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Attachment {
+    External(String), //url or path, the way older data files have every attachment
+    Stored {
+        hash: String,
+        name: String, //of the file that was attached
+        size: u64,
+    },
+}
+
+pub enum Preview {
+    Image {
+        width: u32,
+        height: u32,
+        pixels: Vec<u8>, //rgba
+    },
+    Text(Vec<String>),
+    NoThumbnail(String), //an image that can't be shown, why
+    Nothing,
+}
+
+lazy_static! {
+    //by get_key, previews are made once
+    static ref PREVIEWS: Mutex<HashMap<String, Arc<Preview>>> = Mutex::new(HashMap::new());
+}
+
+impl Attachment {
+    //file name or url
+    pub fn get_name(&self) -> &str {
+        match self {
+            Attachment::External(target) => target,
+            Attachment::Stored { name, .. } => name,
+        }
+    }
+
+    //where the file is, None for urls
+    pub fn get_path(&self) -> Option<PathBuf> {
+        match self {
+            Attachment::External(target) if target.contains("://") => None,
+            Attachment::External(target) => Some(PathBuf::from(target)),
+            Attachment::Stored { hash, name, .. } => Some(Path::new(STORE_FOLDER).join(store_name(hash, name))),
+        }
+    }
+
+    //the same for the same file, whatever node it's attached to
+    pub fn get_key(&self) -> String {
+        match self {
+            Attachment::External(target) => target.clone(),
+            Attachment::Stored { hash, .. } => hash.clone(),
+        }
+    }
+
+    fn get_extension(&self) -> String {
+        Path::new(self.get_name()).extension().map(|extension| extension.to_string_lossy().to_lowercase()).unwrap_or_default()
+    }
+
+    pub fn is_image(&self) -> bool {
+        IMAGE_EXTENSIONS.contains(&self.get_extension().as_str())
+    }
+
+    pub fn is_text(&self) -> bool {
+        TEXT_EXTENSIONS.contains(&self.get_extension().as_str())
+    }
+}
+
+//"<hash>.<extension of the original>"
+fn store_name(hash: &str, name: &str) -> String {
+    match Path::new(name).extension() {
+        Some(extension) => format!("{}.{}", hash, extension.to_string_lossy().to_lowercase()),
+        None => hash.to_string(),
+    }
+}
+
+//copies a file into the store (unless the same content is there already)
+pub fn store(path: &str) -> Result<Attachment, String> {
+    let data = std::fs::read(path).map_err(|e| format!("can't read {}: {}", path, e))?;
+    let hash = sha1_smol::Sha1::from(&data).digest().to_string();
+    let name = Path::new(path).file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or(hash.clone());
+
+    std::fs::create_dir_all(STORE_FOLDER).map_err(|e| format!("can't create {}: {}", STORE_FOLDER, e))?;
+    let stored = Path::new(STORE_FOLDER).join(store_name(&hash, &name));
+    if !stored.exists() {
+        std::fs::write(&stored, &data).map_err(|e| format!("can't write {}: {}", stored.display(), e))?;
+    }
+
+    Ok(Attachment::Stored { hash, name, size: data.len() as u64 })
+}
+
+//with whatever the system opens that kind of file (or url) with
+pub fn open(attachment: &Attachment) -> Result<(), String> {
+    let target = match attachment.get_path() {
+        Some(path) if !path.exists() => return Err(format!("{} is not there anymore", path.display())),
+        Some(path) => path.to_string_lossy().to_string(),
+        None => attachment.get_name().to_string(),
+    };
+
+    #[cfg(target_os = "windows")]
+    let mut command = {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    };
+    #[cfg(target_os = "macos")]
+    let mut command = Command::new("open");
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let mut command = Command::new("xdg-open");
+
+    command.arg(&target).spawn().map(|_| ()).map_err(|e| format!("can't open {}: {}", target, e))
+}
+
+pub fn get_preview(attachment: &Attachment) -> Arc<Preview> {
+    let key = attachment.get_key();
+    if let Some(preview) = PREVIEWS.lock().unwrap().get(&key) {
+        return preview.clone();
+    }
+
+    let preview = match attachment.get_path() {
+        Some(path) if attachment.is_image() => read_thumbnail(&path).unwrap_or_else(Preview::NoThumbnail),
+        Some(_) if OTHER_IMAGE_EXTENSIONS.contains(&attachment.get_extension().as_str()) => {
+            Preview::NoThumbnail("only png images get thumbnails".to_string())
+        }
+        Some(path) if attachment.is_text() => read_text_preview(&path).map(Preview::Text).unwrap_or(Preview::Nothing),
+        _ => Preview::Nothing,
+    };
+    let preview = Arc::new(preview);
+    PREVIEWS.lock().unwrap().insert(key, preview.clone());
+    preview
+}
+
+//the first lines, cut to a readable width
+fn read_text_preview(path: &Path) -> Option<Vec<String>> {
+    let mut data = Vec::new();
+    File::open(path).ok()?.take(PREVIEW_BYTES).read_to_end(&mut data).ok()?;
+    let text = String::from_utf8_lossy(&data);
+    let lines: Vec<String> = text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .take(PREVIEW_LINES)
+        .map(|line| {
+            let line = line.replace('\t', "    ");
+            if line.chars().count() > PREVIEW_LINE_LENGTH {
+                format!("{}...", line.chars().take(PREVIEW_LINE_LENGTH).collect::<String>())
+            } else {
+                line
+            }
+        })
+        .collect();
+    Some(lines).filter(|lines| !lines.is_empty())
+}
+
+fn read_thumbnail(path: &Path) -> Result<Preview, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(|e| e.to_string())?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(|e| e.to_string())?;
+    let data = &buffer[..info.buffer_size()];
+
+    //everything to rgba
+    let pixels: Vec<u8> = match info.color_type {
+        png::ColorType::Rgba => data.to_vec(),
+        png::ColorType::Rgb => data.chunks(3).flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 255]).collect(),
+        png::ColorType::GrayscaleAlpha => data.chunks(2).flat_map(|pixel| [pixel[0], pixel[0], pixel[0], pixel[1]]).collect(),
+        png::ColorType::Grayscale => data.iter().flat_map(|gray| [*gray, *gray, *gray, 255]).collect(),
+        png::ColorType::Indexed => return Err("unexpected indexed colors".to_string()),
+    };
+
+    Ok(shrink(info.width, info.height, &pixels))
+}
+
+//averages blocks of pixels down to at most THUMBNAIL_SIZE on the longest side
+fn shrink(width: u32, height: u32, pixels: &[u8]) -> Preview {
+    let factor = (width.max(height) as f32 / THUMBNAIL_SIZE as f32).max(1.0);
+    let (new_width, new_height) = (((width as f32 / factor) as u32).max(1), ((height as f32 / factor) as u32).max(1));
+
+    let mut shrunk = Vec::with_capacity((new_width * new_height * 4) as usize);
+    for y in 0..new_height {
+        let (top, bottom) = ((y as f32 * factor) as u32, (((y + 1) as f32 * factor) as u32).clamp(1, height));
+        for x in 0..new_width {
+            let (left, right) = ((x as f32 * factor) as u32, (((x + 1) as f32 * factor) as u32).clamp(1, width));
+            let mut sum = [0u32; 4];
+            let mut count = 0;
+            for source_y in top..bottom.max(top + 1).min(height) {
+                for source_x in left..right.max(left + 1).min(width) {
+                    let index = ((source_y * width + source_x) * 4) as usize;
+                    for channel in 0..4 {
+                        sum[channel] += pixels[index + channel] as u32;
+                    }
+                    count += 1;
+                }
+            }
+            shrunk.extend(sum.iter().map(|total| (total / count.max(1)) as u8));
+        }
+    }
+
+    Preview::Image { width: new_width, height: new_height, pixels: shrunk }
+}
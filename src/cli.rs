@@ -0,0 +1,790 @@
+use crate::formats::{anki, bibtex, csv, epub, graphml, html, ical, json_canvas, markdown, mermaid, org, pdf, find_link, find_node, register_link, write_file, Layout};
+use crate::attachments::{self, Attachment};
+use crate::hierarchy;
+use crate::membership;
+use crate::site;
+use crate::structs::link::{parse_color, ArrowHead, Link, LinkDirection, LinkStyle, LinkType};
+use crate::structs::node::Node;
+use crate::structs::task::{Recurrence, Task, TaskPriority, TaskStatus};
+use crate::tasks;
+use crate::{LINKS, NODES};
+use crate::types::ModuleRef;
+use crate::utils::{civil_to_timestamp, format_timestamp, now};
+use crate::workspace::{self, NodeFilter, NodeOrder};
+use std::collections::HashMap;
+
+const USAGE: &str = "usage: rmaps [command] [arguments] [--workspace <folder>]
+
+without a command the window is opened as usual. --workspace is the folder containing \"data\" (defaults to the current one)
+
+commands:
+    render <output.png> [--module <name>] [--scale <scale>]    draw a module's layout into a png, no window needed
+                                                                (16384 pixels on either side at most)
+    import <format> <file> [--module <name>]                    add the contents of a file to the workspace, placed in a module
+    export <format> <file> [--module <name>]                    write the nodes of a module (and their links) to a file
+    site <folder> [--title <title>]                             write the workspace as a static website: an index, a page per node
+                                                                with its links and backlinks and a page per module with its map
+    list [--author <name>] [--tag <tag,tag,...>] [--since <when>] [--created-since <when>] [--before <when>] [--sort <order>] [--limit <n>]
+                                                                print the nodes that match, one per line. <when> is a date (2024-05-01)
+                                                                or a time ago (30m, 12h, 7d, 2w). --sort created|modified|author|content
+    tags                                                        print every tag (#hashtags in the text included) and how many nodes have it
+    tag <node id> <tag> [<tag>...]                              add tags to a node, tags written as -tag are removed
+    attach <node id> <file or url> [...]                        attach files (copied into data/attachments) or urls to a node
+                                                                nodes show thumbnails of png images only, other images by name
+    open <node id>                                              open a node's attachments with the system's default applications
+    tree [<node id>]                                            print the hierarchy below a node, or all of it
+    tree from-links                                             turn unlabelled links into parent -> child pairs, for workspaces
+                                                                from before the hierarchy (a node keeps the first parent it gets)
+    move <node id> <parent id>|root [--index <n>]               put a node (and everything below it) under another one, or at the top
+    move <node id> indent|outdent|up|down                       below the sibling before it, next to its parent, or among its siblings
+    link <from id> <to id> [--type <type>] [--label <text>] [--direction directed|undirected|bidirectional] [--weight <n>]
+         [--style solid|dashed|dotted|type] [--module <name>]
+                                                                link two nodes, or change the link between them. --style type
+                                                                goes back to the style of the link's type
+    links [<node id>]                                           print every link, or the links going to or from a node
+    unlink <link id>                                            remove a link
+    link-types                                                  print the link types of the workspace
+    link-type <name> [--color <#rrggbb>] [--arrow triangle|open|diamond|circle|none] [--style solid|dashed|dotted]
+                                                                add a link type or change one, \"link-type <name> remove\" removes it
+    modules [<node id>]                                         print every module with how many nodes it shows, or the modules
+                                                                showing a node
+    send <node id> <module>                                     show a node in another module too, the same node everywhere
+    unsend <node id> <module>                                   stop showing a node in a module, it stays in the workspace
+    task <node id> [open|in-progress|done|cancelled|none] [--due <date>|none] [--priority high|medium|low|none]
+         [--repeat <rule>|none]                                 make a node a task (none makes it a note again) or change it. <rule>
+                                                                is daily, weekly, monthly, yearly, \"every 3 days\" or +2w. Done
+                                                                recurring tasks come back as a new node, due one period later
+    tasks [--status <status,...>|all]                           print the open and in progress tasks (or the ones with those statuses)
+                                                                by due date, then priority
+    config [author <name>]                                      print the workspace settings, or change one
+    help                                                        print this message
+
+formats:
+    anki        export only, an Anki deck (.apkg). --cards tagged|links|all: nodes tagged \"flashcard\" (default, the back
+                is their \"back\" property or their children) and/or every link and parent -> child pair. --deck <name> (RMaps)
+    bibtex      import only, a .bib file. Every entry becomes a paper node, --entities authors,venues makes nodes
+                for those too. Nodes citing a key ([@key], @key, \\cite{key}) get linked to the paper
+    canvas      JSON Canvas (Obsidian .canvas). export takes --groups <tag,tag,...> to draw those tags as groups.
+                link directions are the arrows at the ends of edges, link types their color
+    markdown    import only, a folder of markdown notes (an Obsidian vault)
+    mermaid     export only. --root <node id> writes the tree below that node as a mindmap,
+                otherwise the module's nodes and their links are written as a flowchart
+    pdf         import only, a document node with its bookmarks below it and its highlights and notes below
+                the bookmarks of their pages
+    csv         nodes table (.csv or .tsv) with id, content, x, y, tags, parent columns and a links table with from, to,
+                label, type, direction, weight, style.
+                --links <file> is the links table (export defaults to <file>-links.csv),
+                --columns field=column,... maps differently named columns
+    epub        import only, a book with its table of contents as chapter nodes below the book node.
+                --annotations <file> adds the highlights of a calibre .calibre_highlights export below their chapters
+    graphml     GraphML for Gephi, yEd, Cytoscape... export writes every node and link with all their data,
+                import reads labels and positions from other tools too
+    html        export only, one html file with a pan/zoom/search viewer that works offline.
+                --scope module|workspace: only the module's nodes (default) or every node
+    ical        iCalendar (.ics). export writes every node with a \"due\"/\"deadline\" or \"start\"/\"scheduled\" date as a todo
+                (or an event when it's not a todo), import reads todos and events back, updating the nodes they came from
+    org         Emacs Org-mode outline, headlines become nodes below their parent headline in the hierarchy.
+                export writes the tree below --root <node id>, or below every top level node of the module
+
+importers that don't know where to put nodes take --layout none|grid|force (default force)";
+
+//positional arguments plus "--name value" pairs
+struct Arguments {
+    positional: Vec<String>,
+    flags: HashMap<String, String>,
+}
+
+impl Arguments {
+    fn parse(args: &[String]) -> Result<Arguments, String> {
+        let mut positional = Vec::new();
+        let mut flags = HashMap::new();
+
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if let Some(name) = arg.strip_prefix("--") {
+                let value = iter.next().ok_or(format!("missing value for --{}", name))?;
+                flags.insert(name.to_string(), value.clone());
+            } else {
+                positional.push(arg.clone());
+            }
+        }
+
+        Ok(Arguments { positional, flags })
+    }
+
+    fn get_positional(&self, index: usize, what: &str) -> Result<&str, String> {
+        self.positional.get(index).map(|arg| arg.as_str()).ok_or(format!("missing {}", what))
+    }
+
+    fn get_flag(&self, name: &str) -> Option<&str> {
+        self.flags.get(name).map(|value| value.as_str())
+    }
+
+    fn get_list(&self, name: &str) -> Vec<String> {
+        match self.get_flag(name) {
+            Some(value) => value.split(',').map(|item| item.trim().to_string()).filter(|item| !item.is_empty()).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    fn get_f32(&self, name: &str, default: f32) -> Result<f32, String> {
+        match self.get_flag(name) {
+            Some(value) => value.parse().map_err(|_| format!("--{} expects a number, got \"{}\"", name, value)),
+            None => Ok(default),
+        }
+    }
+}
+
+//returns the exit code
+pub fn run(args: &[String]) -> i32 {
+    if matches!(args[0].as_str(), "help" | "--help" | "-h") {
+        println!("{}", USAGE);
+        return 0;
+    }
+
+    let result = Arguments::parse(args).and_then(|arguments| {
+        if let Some(folder) = arguments.get_flag("workspace") {
+            std::env::set_current_dir(folder).map_err(|e| format!("can't open workspace \"{}\": {}", folder, e))?;
+        }
+
+        match arguments.get_positional(0, "command")? {
+            "render" => render(&arguments),
+            "import" => import(&arguments),
+            "export" => export(&arguments),
+            "site" => generate_site(&arguments),
+            "list" => list(&arguments),
+            "tags" => tags(),
+            "tag" => tag(&arguments),
+            "attach" => attach(&arguments),
+            "open" => open(&arguments),
+            "tree" => tree(&arguments),
+            "move" => move_node(&arguments),
+            "link" => link(&arguments),
+            "links" => links(&arguments),
+            "unlink" => unlink(&arguments),
+            "link-types" => link_types(),
+            "link-type" => link_type(&arguments),
+            "modules" => modules(&arguments),
+            "send" => send(&arguments),
+            "unsend" => unsend(&arguments),
+            "task" => task(&arguments),
+            "tasks" => list_tasks(&arguments),
+            "config" => config(&arguments),
+            command => Err(format!("unknown command \"{}\"\n\n{}", command, USAGE)),
+        }
+    });
+
+    match result {
+        Ok(()) => 0,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            1
+        }
+    }
+}
+
+fn render(arguments: &Arguments) -> Result<(), String> {
+    let output = arguments.get_positional(1, "output file")?;
+    let scale = arguments.get_f32("scale", 1.0)?;
+    if scale <= 0.0 {
+        return Err("--scale must be greater than zero".to_string());
+    }
+
+    let module = load_workspace(arguments)?;
+    let name = module.read().unwrap().get_name();
+
+    let raster = module.write().unwrap().render_headless(scale)?;
+    raster.save_png(output)?;
+
+    println!("rendered \"{}\" to {} ({}x{})", name, output, raster.get_width(), raster.get_height());
+    Ok(())
+}
+
+//workspace::load, with what was wrong in the data (it's fixed, the next save keeps that) on stderr
+fn load_data() {
+    for problem in workspace::load() {
+        eprintln!("warning: {}", problem);
+    }
+}
+
+//loads the workspace and returns the module picked with --module
+fn load_workspace(arguments: &Arguments) -> Result<ModuleRef, String> {
+    //nodes have to be there before the modules load, they link to them
+    load_data();
+
+    workspace::find_module(arguments.get_flag("module"))
+        .ok_or(format!("no module named \"{}\"", arguments.get_flag("module").unwrap_or_default()))
+}
+
+fn get_layout(arguments: &Arguments) -> Result<Layout, String> {
+    Layout::parse(arguments.get_flag("layout").unwrap_or("force"))
+}
+
+fn get_root(arguments: &Arguments) -> Result<Option<i64>, String> {
+    match arguments.get_flag("root") {
+        Some(root) => root.parse().map(Some).map_err(|_| format!("--root expects a node id, got \"{}\"", root)),
+        None => Ok(None),
+    }
+}
+
+fn import(arguments: &Arguments) -> Result<(), String> {
+    let format = arguments.get_positional(1, "format")?;
+    let input = arguments.get_positional(2, "input file")?;
+    let module = load_workspace(arguments)?;
+
+    let (nodes, links) = match format {
+        "canvas" => json_canvas::import(input, &module)?,
+        "bibtex" => bibtex::import(input, &bibtex::Entities::parse(&arguments.get_list("entities"))?, &module, get_layout(arguments)?)?,
+        "markdown" => markdown::import(input, &module, get_layout(arguments)?)?,
+        "epub" => epub::import(input, arguments.get_flag("annotations"), &module, get_layout(arguments)?)?,
+        "graphml" => graphml::import(input, &module, get_layout(arguments)?)?,
+        "ical" => ical::import(input, &module, get_layout(arguments)?)?,
+        "pdf" => pdf::import(input, &module, get_layout(arguments)?)?,
+        "org" => org::import(input, &module, get_layout(arguments)?)?,
+        "csv" => csv::import(input, arguments.get_flag("links"), &csv::ColumnMapping::parse(&arguments.get_list("columns"))?, &module, get_layout(arguments)?)?,
+        format => return Err(format!("unknown format \"{}\"", format)),
+    };
+
+    workspace::save();
+    println!("imported {} nodes and {} links from {}", nodes, links, input);
+    Ok(())
+}
+
+fn export(arguments: &Arguments) -> Result<(), String> {
+    let format = arguments.get_positional(1, "format")?;
+    let output = arguments.get_positional(2, "output file")?;
+    let module = load_workspace(arguments)?;
+
+    let (nodes, links) = match format {
+        "anki" => anki::export(output, arguments.get_flag("deck").unwrap_or("RMaps"), anki::CardSource::parse(arguments.get_flag("cards").unwrap_or("tagged"))?)?,
+        "canvas" => json_canvas::export(output, &module, &arguments.get_list("groups"))?,
+        "csv" => csv::export(output, &arguments.get_flag("links").map(String::from).unwrap_or(csv::default_links_path(output)), &module)?,
+        "graphml" => graphml::export(output, &module)?,
+        "html" => html::export(output, &module, html::Scope::parse(arguments.get_flag("scope").unwrap_or("module"))?)?,
+        "ical" => ical::export(output)?,
+        "mermaid" => export_mermaid(output, arguments, &module)?,
+        "org" => org::export(output, get_root(arguments)?, &module)?,
+        format => return Err(format!("unknown format \"{}\"", format)),
+    };
+
+    println!("exported {} nodes and {} links to {}", nodes, links, output);
+    Ok(())
+}
+
+//"2024-05-01" or "7d" (that long ago: m, h, d, w)
+fn get_time(arguments: &Arguments, name: &str) -> Result<Option<i64>, String> {
+    let value = match arguments.get_flag(name) {
+        Some(value) => value.trim(),
+        None => return Ok(None),
+    };
+    let error = || format!("--{} expects a date like 2024-05-01 or a time ago like 7d, got \"{}\"", name, value);
+
+    let mut date = value.splitn(3, '-');
+    if let (Some(year), Some(month), Some(day)) = (date.next(), date.next(), date.next()) {
+        let (year, month, day) = (year.parse().map_err(|_| error())?, month.parse().map_err(|_| error())?, day.parse().map_err(|_| error())?);
+        return Ok(Some(civil_to_timestamp(year, month, day)));
+    }
+
+    let unit = match value.chars().last() {
+        Some('m') => 60,
+        Some('h') => 3600,
+        Some('d') => 86400,
+        Some('w') => 7 * 86400,
+        _ => return Err(error()),
+    };
+    let amount: i64 = value[..value.len() - 1].parse().map_err(|_| error())?;
+    Ok(Some(now() - amount * unit))
+}
+
+fn list(arguments: &Arguments) -> Result<(), String> {
+    load_data();
+
+    let filter = NodeFilter {
+        author: arguments.get_flag("author").map(String::from),
+        tags: arguments.get_list("tag"),
+        created_since: get_time(arguments, "created-since")?,
+        modified_since: get_time(arguments, "since")?,
+        modified_before: get_time(arguments, "before")?,
+    };
+    let order = arguments.get_flag("sort").map(NodeOrder::parse).transpose()?;
+    let limit = match arguments.get_flag("limit") {
+        Some(limit) => limit.parse().map_err(|_| format!("--limit expects a number, got \"{}\"", limit))?,
+        None => usize::MAX,
+    };
+
+    //id, last change, author, first line of the content
+    for node in workspace::find_nodes(&filter, order).into_iter().take(limit) {
+        let node = node.read().unwrap();
+        println!(
+            "{}\t{}\t{}\t{}",
+            node.get_id(),
+            node.get_modified().map(format_timestamp).unwrap_or("-".to_string()),
+            if node.get_author().is_empty() { "-" } else { node.get_author() },
+            node.get_content().lines().next().unwrap_or_default()
+        );
+    }
+    Ok(())
+}
+
+fn tags() -> Result<(), String> {
+    load_data();
+    for (tag, count) in workspace::get_tag_counts() {
+        println!("{}\t{}", count, tag);
+    }
+    Ok(())
+}
+
+fn tag(arguments: &Arguments) -> Result<(), String> {
+    let id = get_node_id(arguments)?;
+    arguments.get_positional(2, "tag")?;
+    load_data();
+
+    let node = find_node(id).ok_or(format!("there is no node {}", id))?;
+    {
+        let mut node = node.write().unwrap();
+        for tag in &arguments.positional[2..] {
+            match tag.strip_prefix('-') {
+                //whatever case it was written in
+                Some(tag) => {
+                    let own: Vec<String> = node.get_tags().iter()
+                        .filter(|own| workspace::normalize_tag(own) == workspace::normalize_tag(tag))
+                        .cloned()
+                        .collect();
+                    own.iter().for_each(|own| node.remove_tag(own));
+                }
+                None if node.has_tag(tag) => {}
+                None => node.add_tag(tag.trim_start_matches('#').to_string()),
+            }
+        }
+    }
+
+    workspace::save();
+    let node = node.read().unwrap();
+    println!("{}: {}", id, node.get_tags().iter().cloned().collect::<Vec<_>>().join(", "));
+    Ok(())
+}
+
+fn tree(arguments: &Arguments) -> Result<(), String> {
+    load_data();
+    let roots = match arguments.positional.get(1).map(|arg| arg.as_str()) {
+        Some("from-links") => {
+            let adopted = hierarchy::adopt_links();
+            workspace::save();
+            println!("{} links are parent -> child pairs now", adopted);
+            return Ok(());
+        }
+        Some(_) => {
+            let id = get_node_id(arguments)?;
+            find_node(id).ok_or(format!("there is no node {}", id))?;
+            vec![id]
+        }
+        None => hierarchy::get_roots(),
+    };
+
+    for root in roots {
+        let depth = hierarchy::get_ancestors(root).len();
+        for id in std::iter::once(root).chain(hierarchy::get_descendants(root)) {
+            let node = find_node(id).unwrap();
+            let node = node.read().unwrap();
+            let indent = "  ".repeat(hierarchy::get_ancestors(id).len() - depth);
+            println!("{}{}\t{}", indent, id, node.get_content().lines().next().unwrap_or_default());
+        }
+    }
+    Ok(())
+}
+
+fn move_node(arguments: &Arguments) -> Result<(), String> {
+    let id = get_node_id(arguments)?;
+    let target = arguments.get_positional(2, "parent id, root, indent, outdent, up or down")?;
+    load_data();
+
+    match target {
+        "indent" => hierarchy::indent(id),
+        "outdent" => hierarchy::outdent(id),
+        "up" => hierarchy::reorder(id, -1),
+        "down" => hierarchy::reorder(id, 1),
+        target => {
+            let parent = match target {
+                "root" => None,
+                parent => Some(parent.parse().map_err(|_| format!("expected a parent id or root, got \"{}\"", parent))?),
+            };
+            let index = match arguments.get_flag("index") {
+                Some(index) => Some(index.parse().map_err(|_| format!("--index expects a number, got \"{}\"", index))?),
+                None => None,
+            };
+            hierarchy::set_parent(id, parent, index)
+        }
+    }?;
+
+    workspace::save();
+    match hierarchy::get_parent(id) {
+        Some(parent) => println!("{} is child {} of {}", id, hierarchy::get_children(parent).iter().position(|child| *child == id).unwrap_or(0) + 1, parent),
+        None => println!("{} is a root", id),
+    }
+    Ok(())
+}
+
+fn get_node_id(arguments: &Arguments) -> Result<i64, String> {
+    let id = arguments.get_positional(1, "node id")?;
+    id.parse().map_err(|_| format!("expected a node id, got \"{}\"", id))
+}
+
+fn attach(arguments: &Arguments) -> Result<(), String> {
+    let id = get_node_id(arguments)?;
+    arguments.get_positional(2, "file to attach")?;
+    load_data();
+    let node = find_node(id).ok_or(format!("there is no node {}", id))?;
+
+    for target in &arguments.positional[2..] {
+        let attachment = if target.contains("://") {
+            Attachment::External(target.clone())
+        } else {
+            attachments::store(target)?
+        };
+        node.write().unwrap().add_attachment(attachment);
+    }
+
+    workspace::save();
+    println!("{} has {} attachments", id, node.read().unwrap().get_attachments().len());
+    Ok(())
+}
+
+fn open(arguments: &Arguments) -> Result<(), String> {
+    let id = get_node_id(arguments)?;
+    load_data();
+    let node = find_node(id).ok_or(format!("there is no node {}", id))?;
+    let node = node.read().unwrap();
+    if node.get_attachments().is_empty() {
+        return Err(format!("node {} has no attachments", id));
+    }
+    node.get_attachments().iter().try_for_each(attachments::open)
+}
+
+//id, from -> to, type, direction, weight, style, label
+fn describe_link(link: &Link) -> String {
+    format!(
+        "{}\t{} -> {}\t{}\t{}\t{}\t{}\t{}",
+        link.get_id(),
+        link.get_from_id(),
+        link.get_to_id(),
+        if link.get_relation().is_empty() { "-" } else { link.get_relation() },
+        link.get_direction().get_name(),
+        link.get_weight(),
+        link.get_style().map_or("-", |style| style.get_name()),
+        link.get_label()
+    )
+}
+
+fn link(arguments: &Arguments) -> Result<(), String> {
+    let from = get_node_id(arguments)?;
+    let to = arguments.get_positional(2, "id of the node to link to")?;
+    let to: i64 = to.parse().map_err(|_| format!("expected a node id, got \"{}\"", to))?;
+    let module = load_workspace(arguments)?;
+    for id in [from, to] {
+        find_node(id).ok_or(format!("there is no node {}", id))?;
+    }
+
+    let existing = LINKS.read().unwrap().iter()
+        .find(|link| link.read().unwrap().get_from_id() == from && link.read().unwrap().get_to_id() == to)
+        .cloned();
+    let link = match existing {
+        Some(link) => link,
+        None => register_link(Link::create_and_register(from, to, module.read().unwrap().get_name())),
+    };
+
+    {
+        let mut link = link.write().unwrap();
+        if let Some(relation) = arguments.get_flag("type") {
+            if !relation.is_empty() && workspace::get_link_type(relation).is_none() {
+                println!("\"{}\" is not one of the link types, the link is drawn like a plain one", relation);
+            }
+            link.set_relation(relation.to_string());
+        }
+        if let Some(label) = arguments.get_flag("label") {
+            link.set_label(label.to_string());
+        }
+        if let Some(direction) = arguments.get_flag("direction") {
+            link.set_direction(LinkDirection::parse(direction)?);
+        }
+        if arguments.get_flag("weight").is_some() {
+            let weight = arguments.get_f32("weight", 1.0)?;
+            if weight <= 0.0 {
+                return Err(format!("--weight has to be above 0, got {}", weight));
+            }
+            link.set_weight(weight);
+        }
+        match arguments.get_flag("style") {
+            Some("type") => link.set_style(None),
+            Some(style) => link.set_style(Some(LinkStyle::parse(style)?)),
+            None => {}
+        }
+    }
+
+    workspace::save();
+    println!("{}", describe_link(&link.read().unwrap()));
+    Ok(())
+}
+
+fn links(arguments: &Arguments) -> Result<(), String> {
+    let id = match arguments.positional.get(1) {
+        Some(_) => Some(get_node_id(arguments)?),
+        None => None,
+    };
+    load_data();
+    for link in LINKS.read().unwrap().iter() {
+        let link = link.read().unwrap();
+        if id.map_or(true, |id| link.get_from_id() == id || link.get_to_id() == id) {
+            println!("{}", describe_link(&link));
+        }
+    }
+    Ok(())
+}
+
+fn unlink(arguments: &Arguments) -> Result<(), String> {
+    let id = arguments.get_positional(1, "link id")?;
+    let id: i64 = id.parse().map_err(|_| format!("expected a link id, got \"{}\"", id))?;
+    load_data();
+    find_link(id).ok_or(format!("there is no link {}", id))?;
+    LINKS.write().unwrap().retain(|link| link.read().unwrap().get_id() != id);
+    workspace::save();
+    println!("removed link {}", id);
+    Ok(())
+}
+
+fn modules(arguments: &Arguments) -> Result<(), String> {
+    let id = match arguments.positional.get(1) {
+        Some(_) => Some(get_node_id(arguments)?),
+        None => None,
+    };
+    load_data();
+    match id {
+        Some(id) => {
+            let node = find_node(id).ok_or(format!("there is no node {}", id))?;
+            for module in node.read().unwrap().get_modules() {
+                println!("{}", module);
+            }
+        }
+        None => {
+            for name in workspace::get_module_names() {
+                let count = NODES.read().unwrap().iter().filter(|node| node.read().unwrap().is_in(&name)).count();
+                println!("{}\t{}", count, name);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn send(arguments: &Arguments) -> Result<(), String> {
+    let id = get_node_id(arguments)?;
+    let module = arguments.get_positional(2, "module")?;
+    load_data();
+    let module = membership::send(id, module)?;
+    workspace::save();
+    println!("node {} is in {} too", id, module);
+    Ok(())
+}
+
+fn unsend(arguments: &Arguments) -> Result<(), String> {
+    let id = get_node_id(arguments)?;
+    let module = arguments.get_positional(2, "module")?;
+    load_data();
+    let module = membership::remove(id, module)?;
+    workspace::save();
+    println!("node {} is not in {} anymore", id, module);
+    Ok(())
+}
+
+fn task(arguments: &Arguments) -> Result<(), String> {
+    let id = get_node_id(arguments)?;
+    let status = match arguments.positional.get(2).map(|arg| arg.as_str()) {
+        Some("none") => None,
+        Some(status) => Some(Some(TaskStatus::parse(status)?)),
+        None => Some(None),
+    };
+    load_data();
+    let node = find_node(id).ok_or(format!("there is no node {}", id))?;
+
+    //"task <id> none": a plain note again
+    let status = match status {
+        Some(status) => status,
+        None => {
+            node.write().unwrap().set_task(None);
+            workspace::save();
+            println!("node {} is not a task anymore", id);
+            return Ok(());
+        }
+    };
+
+    let mut task = node.read().unwrap().get_task().cloned().unwrap_or_else(Task::new);
+    match arguments.get_flag("due") {
+        Some("none") => task.set_due(None)?,
+        Some(due) => task.set_due(Some(due))?,
+        None => {}
+    }
+    match arguments.get_flag("priority") {
+        Some("none") => task.priority = None,
+        Some(priority) => task.priority = Some(TaskPriority::parse(priority)?),
+        None => {}
+    }
+    match arguments.get_flag("repeat") {
+        Some("none") => task.recurrence = None,
+        Some(rule) => task.recurrence = Some(Recurrence::parse(rule)?),
+        None => {}
+    }
+    node.write().unwrap().set_task(Some(task));
+
+    //status changes last, a task made done with a rule in the same go comes back right away
+    let next = match status {
+        Some(status) => tasks::set_status(id, status)?,
+        None => None,
+    };
+    if let Some(next) = next {
+        membership::place_below(next, id, None);
+    }
+    workspace::save();
+
+    println!("{}", describe_task(&node.read().unwrap()));
+    if let Some(next) = next.and_then(find_node) {
+        println!("{}", describe_task(&next.read().unwrap()));
+    }
+    Ok(())
+}
+
+fn list_tasks(arguments: &Arguments) -> Result<(), String> {
+    let statuses = match arguments.get_list("status").as_slice() {
+        [] => vec![TaskStatus::Open, TaskStatus::InProgress],
+        [all] if all == "all" => TaskStatus::ALL.to_vec(),
+        names => names.iter().map(|name| TaskStatus::parse(name)).collect::<Result<_, _>>()?,
+    };
+    load_data();
+
+    let mut nodes: Vec<_> = NODES.read().unwrap().iter()
+        .filter(|node| node.read().unwrap().get_task().map_or(false, |task| statuses.contains(&task.status)))
+        .cloned()
+        .collect();
+    //soonest first, then the most pressing. Tasks without a due date or a priority go after the others
+    nodes.sort_by_key(|node| {
+        let node = node.read().unwrap();
+        let task = node.get_task().unwrap();
+        (task.get_due_time().is_none(), task.get_due_time(), task.priority.is_none(), task.priority, node.get_id())
+    });
+    for node in nodes {
+        println!("{}", describe_task(&node.read().unwrap()));
+    }
+    Ok(())
+}
+
+//id, status, due date (with "late" when it's past), priority, recurrence, first line of the content
+fn describe_task(node: &Node) -> String {
+    let task = match node.get_task() {
+        Some(task) => task,
+        None => return format!("{}\tnot a task", node.get_id()),
+    };
+    let due = match task.get_due() {
+        Some(due) if task.is_overdue(now()) => format!("{} (late)", due),
+        Some(due) => due.clone(),
+        None => "-".to_string(),
+    };
+    format!(
+        "{}\t{}\t{}\t{}\t{}\t{}",
+        node.get_id(),
+        task.status.get_name(),
+        due,
+        task.priority.map_or("-", |priority| priority.get_name()),
+        task.recurrence.map_or("-".to_string(), |recurrence| recurrence.get_name()),
+        node.get_content().lines().next().unwrap_or_default()
+    )
+}
+
+fn link_types() -> Result<(), String> {
+    load_data();
+    for link_type in workspace::get_settings().link_types {
+        println!("{}\t{}\t{}\t{}", link_type.name, link_type.color, link_type.arrow.get_name(), link_type.style.get_name());
+    }
+    Ok(())
+}
+
+fn link_type(arguments: &Arguments) -> Result<(), String> {
+    let name = arguments.get_positional(1, "link type name")?.trim().to_string();
+    load_data();
+    let mut settings = workspace::get_settings();
+    let index = settings.link_types.iter().position(|link_type| link_type.name == name);
+
+    if arguments.positional.get(2).map(|arg| arg.as_str()) == Some("remove") {
+        let index = index.ok_or(format!("there is no link type \"{}\"", name))?;
+        settings.link_types.remove(index);
+        workspace::set_settings(settings);
+        workspace::save_settings();
+        println!("removed link type \"{}\", its links are drawn like plain ones now", name);
+        return Ok(());
+    }
+
+    if name.is_empty() || name == "-" {
+        return Err("link types need a name".to_string());
+    }
+    let mut link_type = match index {
+        Some(index) => settings.link_types[index].clone(),
+        None => LinkType::new(&name, "#4a4e69", ArrowHead::Triangle, LinkStyle::Solid),
+    };
+    if let Some(color) = arguments.get_flag("color") {
+        let rgb = parse_color(color).ok_or(format!("--color expects #rrggbb, got \"{}\"", color))?;
+        link_type.color = format!("#{:06x}", rgb);
+    }
+    if let Some(arrow) = arguments.get_flag("arrow") {
+        link_type.arrow = ArrowHead::parse(arrow)?;
+    }
+    if let Some(style) = arguments.get_flag("style") {
+        link_type.style = LinkStyle::parse(style)?;
+    }
+
+    println!("{}\t{}\t{}\t{}", link_type.name, link_type.color, link_type.arrow.get_name(), link_type.style.get_name());
+    match index {
+        Some(index) => settings.link_types[index] = link_type,
+        None => settings.link_types.push(link_type),
+    }
+    workspace::set_settings(settings);
+    workspace::save_settings();
+    Ok(())
+}
+
+fn config(arguments: &Arguments) -> Result<(), String> {
+    load_data();
+    let mut settings = workspace::get_settings();
+
+    match arguments.positional.get(1).map(|key| key.as_str()) {
+        None => {}
+        Some("author") => {
+            settings.author = arguments.get_positional(2, "author name")?.to_string();
+            workspace::set_settings(settings.clone());
+            workspace::save_settings();
+        }
+        Some(key) => return Err(format!("unknown setting \"{}\" (author)", key)),
+    }
+
+    println!("author: {}", if settings.author.is_empty() { "(not set)" } else { &settings.author });
+    Ok(())
+}
+
+fn generate_site(arguments: &Arguments) -> Result<(), String> {
+    let folder = arguments.get_positional(1, "output folder")?;
+    //every module has its map page, there's no single module to pick
+    load_data();
+    let title = arguments.get_flag("title").unwrap_or("RMaps");
+
+    let (nodes, maps) = site::generate(folder, title)?;
+    println!("wrote {} node pages and {} maps to {}", nodes, maps, folder);
+    Ok(())
+}
+
+fn export_mermaid(output: &str, arguments: &Arguments, module: &ModuleRef) -> Result<(usize, usize), String> {
+    let (diagram, nodes, edges) = match get_root(arguments)? {
+        Some(root) => mermaid::mindmap(root).ok_or(format!("no node with id {}", root))?,
+        None => {
+            let mut ids: Vec<i64> = module.read().unwrap().get_node_bounds().into_keys().collect();
+            ids.sort();
+            mermaid::flowchart(&ids)
+        }
+    };
+    write_file(output, &diagram)?;
+    Ok((nodes, edges))
+}
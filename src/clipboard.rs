@@ -0,0 +1,24 @@
+use arboard::Clipboard;
+use lazy_static::lazy_static;
+use std::sync::Mutex;
+
+lazy_static! {
+    //kept around for the whole session: on x11 the copied text goes away together with the Clipboard
+    static ref CLIPBOARD: Mutex<Option<Clipboard>> = Mutex::new(None);
+}
+
+fn with_clipboard<T>(action: impl FnOnce(&mut Clipboard) -> Result<T, arboard::Error>) -> Result<T, String> {
+    let mut clipboard = CLIPBOARD.lock().unwrap();
+    if clipboard.is_none() {
+        *clipboard = Some(Clipboard::new().map_err(|e| format!("clipboard not available: {}", e))?);
+    }
+    action(clipboard.as_mut().unwrap()).map_err(|e| e.to_string())
+}
+
+pub fn set_text(text: String) -> Result<(), String> {
+    with_clipboard(|clipboard| clipboard.set_text(text))
+}
+
+pub fn get_text() -> Result<String, String> {
+    with_clipboard(|clipboard| clipboard.get_text())
+}
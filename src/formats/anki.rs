@@ -0,0 +1,254 @@
+use crate::formats::{escape_xml, find_node};
+use crate::hierarchy;
+use crate::LINKS;
+use crate::NODES;
+use rusqlite::{params, Connection};
+use serde_json::json;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+//Anki decks (.apkg: a zip with an sqlite collection inside). Export only.
+//cards come from nodes tagged "flashcard" (front: the node, back: its "back" property or else its children)
+//or from every link and every parent -> child pair of the hierarchy (front: the parent or the node linking,
+//back: the child or the node linked to). Notes get a guid made from the node or link id,
+//so importing a newer export into Anki updates the cards instead of adding them twice, and every card is
+//tagged with the node id and the path of the node in the map.
+
+const FLASHCARD_TAG: &str = "flashcard";
+const BACK_PROPERTY: &str = "back";
+const MODEL_ID: i64 = 1_683_000_000_000; //any fixed id, Anki recognizes the note type by it on later imports
+const FIELD_SEPARATOR: char = '\u{1f}';
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CardSource {
+    Tagged, //nodes tagged "flashcard"
+    Links,  //every link and every parent -> child pair
+    All,    //both
+}
+
+impl CardSource {
+    pub fn parse(name: &str) -> Result<CardSource, String> {
+        match name {
+            "tagged" => Ok(CardSource::Tagged),
+            "links" => Ok(CardSource::Links),
+            "all" => Ok(CardSource::All),
+            name => Err(format!("unknown card source \"{}\" (tagged, links, all)", name)),
+        }
+    }
+}
+
+struct Card {
+    guid: String,
+    front: String,
+    back: String,
+    tags: Vec<String>,
+}
+
+//node content as card html
+fn card_html(text: &str) -> String {
+    escape_xml(text).replace("\r\n", "\n").replace('\n', "<br>")
+}
+
+//anki tags can't contain spaces, "::" makes them hierarchical
+fn tag_name(text: &str) -> String {
+    let name: String = text.lines().next().unwrap_or_default().trim().chars().take(40).collect();
+    name.split_whitespace().collect::<Vec<_>>().join("_").replace("::", "_")
+}
+
+//"rmaps::Root::Parent::Node", the node's ancestors in the hierarchy
+fn map_path(id: i64) -> String {
+    let path: Vec<String> = std::iter::once(id).chain(hierarchy::get_ancestors(id))
+        .filter_map(find_node)
+        .map(|node| tag_name(node.read().unwrap().get_content()))
+        .chain(std::iter::once("rmaps".to_string()))
+        .collect();
+    path.into_iter().rev().collect::<Vec<_>>().join("::")
+}
+
+fn node_tags(id: i64) -> Vec<String> {
+    let mut tags = vec![format!("rmaps_node_{}", id), map_path(id)];
+    if let Some(node) = find_node(id) {
+        tags.extend(node.read().unwrap().get_tags().iter().filter(|tag| *tag != FLASHCARD_TAG).map(|tag| tag_name(tag)));
+    }
+    tags
+}
+
+fn content(id: i64) -> String {
+    find_node(id).map(|node| node.read().unwrap().get_content().clone()).unwrap_or_default()
+}
+
+fn collect_cards(source: CardSource) -> Vec<Card> {
+    let mut cards = Vec::new();
+
+    if source != CardSource::Links {
+        let flashcards: Vec<i64> = NODES.read().unwrap().iter()
+            .map(|node| node.read().unwrap())
+            .filter(|node| node.get_tags().contains(FLASHCARD_TAG))
+            .map(|node| node.get_id())
+            .collect();
+        for id in flashcards {
+            let back = match find_node(id).and_then(|node| node.read().unwrap().get_property(BACK_PROPERTY).cloned()) {
+                Some(back) => card_html(&back),
+                None => hierarchy::get_children(id).iter().map(|child| card_html(&content(*child))).collect::<Vec<_>>().join("<hr>"),
+            };
+            cards.push(Card {
+                guid: format!("rmaps-node-{}", id),
+                front: card_html(&content(id)),
+                back,
+                tags: node_tags(id),
+            });
+        }
+    }
+
+    if source != CardSource::Tagged {
+        let links: Vec<(i64, i64, i64)> = LINKS.read().unwrap().iter()
+            .map(|link| link.read().unwrap())
+            .map(|link| (link.get_id(), link.get_from_id(), link.get_to_id()))
+            .collect();
+        for (id, from, to) in links {
+            if find_node(from).is_none() || find_node(to).is_none() {
+                continue;
+            }
+            cards.push(Card {
+                guid: format!("rmaps-link-{}", id),
+                front: card_html(&content(from)),
+                back: card_html(&content(to)),
+                tags: node_tags(to),
+            });
+        }
+
+        let children: Vec<(i64, i64)> = NODES.read().unwrap().iter()
+            .map(|node| node.read().unwrap())
+            .filter_map(|node| Some((node.get_parent()?, node.get_id())))
+            .collect();
+        for (parent, child) in children {
+            cards.push(Card {
+                guid: format!("rmaps-child-{}", child),
+                front: card_html(&content(parent)),
+                back: card_html(&content(child)),
+                tags: node_tags(child),
+            });
+        }
+    }
+
+    cards
+}
+
+//what anki compares to find duplicates: the first 8 hex digits of the sha1 of the sort field
+fn checksum(text: &str) -> i64 {
+    let digest = sha1_smol::Sha1::from(text).digest().bytes();
+    i64::from(u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]))
+}
+
+//decks are told apart by id, the same name always gives the same one
+fn deck_id(name: &str) -> i64 {
+    let digest = sha1_smol::Sha1::from(name).digest().bytes();
+    (u64::from_be_bytes([digest[0], digest[1], digest[2], digest[3], digest[4], digest[5], digest[6], digest[7]]) >> 12) as i64
+}
+
+fn create_collection(path: &str, deck: &str, cards: &[Card]) -> rusqlite::Result<()> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    let (seconds, milliseconds) = (now.as_secs() as i64, now.as_millis() as i64);
+    let deck_id = deck_id(deck);
+
+    let connection = Connection::open(path)?;
+    connection.execute_batch(
+        "CREATE TABLE col (id integer primary key, crt integer not null, mod integer not null, scm integer not null, ver integer not null,
+            dty integer not null, usn integer not null, ls integer not null, conf text not null, models text not null, decks text not null,
+            dconf text not null, tags text not null);
+        CREATE TABLE notes (id integer primary key, guid text not null, mid integer not null, mod integer not null, usn integer not null,
+            tags text not null, flds text not null, sfld integer not null, csum integer not null, flags integer not null, data text not null);
+        CREATE TABLE cards (id integer primary key, nid integer not null, did integer not null, ord integer not null, mod integer not null,
+            usn integer not null, type integer not null, queue integer not null, due integer not null, ivl integer not null,
+            factor integer not null, reps integer not null, lapses integer not null, left integer not null, odue integer not null,
+            odid integer not null, flags integer not null, data text not null);
+        CREATE TABLE revlog (id integer primary key, cid integer not null, usn integer not null, ease integer not null, ivl integer not null,
+            lastIvl integer not null, factor integer not null, time integer not null, type integer not null);
+        CREATE TABLE graves (usn integer not null, oid integer not null, type integer not null);",
+    )?;
+
+    let field = |name: &str, ord: i64| json!({"name": name, "ord": ord, "sticky": false, "rtl": false, "font": "Arial", "size": 20, "media": []});
+    let models = json!({
+        MODEL_ID.to_string(): {
+            "id": MODEL_ID, "name": "RMaps", "type": 0, "mod": seconds, "usn": -1, "sortf": 0, "did": deck_id,
+            "flds": [field("Front", 0), field("Back", 1)],
+            "tmpls": [{
+                "name": "Card 1", "ord": 0, "qfmt": "{{Front}}", "afmt": "{{FrontSide}}<hr id=answer>{{Back}}",
+                "bqfmt": "", "bafmt": "", "did": null, "bfont": "", "bsize": 0
+            }],
+            "css": ".card { font-family: arial; font-size: 20px; text-align: center; color: black; background-color: white; }",
+            "latexPre": "\\documentclass[12pt]{article}\n\\special{papersize=3in,5in}\n\\usepackage[utf8]{inputenc}\n\\usepackage{amssymb,amsmath}\n\\pagestyle{empty}\n\\setlength{\\parindent}{0in}\n\\begin{document}\n",
+            "latexPost": "\\end{document}", "latexsvg": false, "req": [[0, "any", [0]]], "tags": [], "vers": []
+        }
+    });
+    let deck_json = |id: i64, name: &str| json!({
+        "id": id, "name": name, "mod": seconds, "usn": -1, "desc": "", "dyn": 0, "conf": 1, "collapsed": false, "browserCollapsed": false,
+        "newToday": [0, 0], "revToday": [0, 0], "lrnToday": [0, 0], "timeToday": [0, 0], "extendNew": 0, "extendRev": 0
+    });
+    let decks = json!({"1": deck_json(1, "Default"), deck_id.to_string(): deck_json(deck_id, deck)});
+    let dconf = json!({"1": {
+        "id": 1, "name": "Default", "mod": 0, "usn": 0, "maxTaken": 60, "autoplay": true, "timer": 0, "replayq": true,
+        "new": {"bury": true, "delays": [1, 10], "initialFactor": 2500, "ints": [1, 4, 7], "order": 1, "perDay": 20, "separate": true},
+        "rev": {"bury": true, "ease4": 1.3, "fuzz": 0.05, "ivlFct": 1, "maxIvl": 36500, "minSpace": 1, "perDay": 100},
+        "lapse": {"delays": [10], "leechAction": 0, "leechFails": 8, "minInt": 1, "mult": 0}
+    }});
+    let conf = json!({
+        "activeDecks": [1], "curDeck": 1, "curModel": MODEL_ID.to_string(), "nextPos": cards.len() + 1, "newSpread": 0,
+        "collapseTime": 1200, "timeLim": 0, "estTimes": true, "dueCounts": true, "sortType": "noteFld", "sortBackwards": false,
+        "addToCur": true, "newBury": true
+    });
+
+    connection.execute(
+        "INSERT INTO col VALUES (1, ?1, ?2, ?2, 11, 0, 0, 0, ?3, ?4, ?5, ?6, '{}')",
+        params![seconds, milliseconds, conf.to_string(), models.to_string(), decks.to_string(), dconf.to_string()],
+    )?;
+
+    for (index, card) in cards.iter().enumerate() {
+        //note and card ids are creation times in milliseconds, they just have to be unique
+        let id = milliseconds + index as i64;
+        let tags = format!(" {} ", card.tags.join(" "));
+        connection.execute(
+            "INSERT INTO notes VALUES (?1, ?2, ?3, ?4, -1, ?5, ?6, ?7, ?8, 0, '')",
+            params![id, card.guid, MODEL_ID, seconds, tags, format!("{}{}{}", card.front, FIELD_SEPARATOR, card.back), card.front, checksum(&card.front)],
+        )?;
+        connection.execute(
+            "INSERT INTO cards VALUES (?1, ?1, ?2, 0, ?3, -1, 0, 0, ?4, 0, 0, 0, 0, 0, 0, 0, 0, '')",
+            params![id, deck_id, seconds, index as i64 + 1],
+        )?;
+    }
+
+    Ok(())
+}
+
+//returns (cards, 0), there are no links in a deck
+pub fn export(path: &str, deck: &str, source: CardSource) -> Result<(usize, usize), String> {
+    let cards = collect_cards(source);
+    if cards.is_empty() {
+        return Err(match source {
+            CardSource::Links => "there are no links to make cards from".to_string(),
+            _ => format!("no node is tagged \"{}\" (--cards links makes cards from links)", FLASHCARD_TAG),
+        });
+    }
+
+    //the collection is an sqlite file, it is built next to the package and zipped
+    let collection = format!("{}.anki2.tmp", path);
+    let _ = std::fs::remove_file(&collection);
+    let result = create_collection(&collection, deck, &cards)
+        .map_err(|e| format!("can't create the collection: {}", e))
+        .and_then(|()| std::fs::read(&collection).map_err(|e| format!("can't read {}: {}", collection, e)));
+    let _ = std::fs::remove_file(&collection);
+    let collection = result?;
+
+    let file = std::fs::File::create(path).map_err(|e| format!("can't write {}: {}", path, e))?;
+    let mut package = zip::ZipWriter::new(file);
+    let options = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    let write = |package: &mut zip::ZipWriter<std::fs::File>, name: &str, data: &[u8]| -> Result<(), String> {
+        package.start_file(name, options).map_err(|e| e.to_string())?;
+        package.write_all(data).map_err(|e| e.to_string())
+    };
+    write(&mut package, "collection.anki2", &collection)?;
+    write(&mut package, "media", b"{}")?;
+    package.finish().map_err(|e| format!("can't write {}: {}", path, e))?;
+
+    Ok((cards.len(), 0))
+}
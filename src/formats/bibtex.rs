@@ -0,0 +1,519 @@
+use crate::formats::{place_nodes, read_file, register_link, register_node, Layout};
+use crate::structs::link::Link;
+use crate::structs::node::Node;
+use crate::types::ModuleRef;
+use crate::{LINKS, NODES};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+
+//BibTeX (.bib) bibliographies. Every entry becomes a paper node: the title is the content, the citation key,
+//entry type, authors, year, venue, doi, abstract and any other field are properties. Authors and venues can
+//become nodes of their own, linked from their papers. Afterwards every node of the workspace that cites a
+//known key ([@key], @key or \cite{key}) gets a link to that paper.
+
+const PAPER_TAG: &str = "paper";
+const AUTHOR_TAG: &str = "author";
+const VENUE_TAG: &str = "venue";
+const AUTHOR_SEPARATOR: &str = "; ";
+//the fields that say where a paper was published, in order of preference
+const VENUE_FIELDS: [&str; 5] = ["journal", "booktitle", "publisher", "school", "institution"];
+const MONTHS: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+
+#[derive(Default)]
+struct Entry {
+    kind: String,
+    key: String,
+    fields: BTreeMap<String, String>,
+}
+
+//which extra nodes to create, from --entities authors,venues
+#[derive(Default)]
+pub struct Entities {
+    authors: bool,
+    venues: bool,
+}
+
+impl Entities {
+    pub fn parse(names: &[String]) -> Result<Entities, String> {
+        let mut entities = Entities::default();
+        for name in names {
+            match name.as_str() {
+                "authors" => entities.authors = true,
+                "venues" => entities.venues = true,
+                name => return Err(format!("unknown entity \"{}\" (authors, venues)", name)),
+            }
+        }
+        Ok(entities)
+    }
+}
+
+struct Parser<'a> {
+    characters: Vec<char>,
+    index: usize,
+    strings: &'a mut HashMap<String, String>, //@string macros
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.characters.get(self.index).cloned()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().map_or(false, char::is_whitespace) {
+            self.index += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(character) if character == expected => {
+                self.index += 1;
+                Ok(())
+            }
+            found => Err(format!("expected \"{}\" but found {:?} at character {}", expected, found, self.index)),
+        }
+    }
+
+    //entry types, keys, field names and macro names
+    fn read_identifier(&mut self) -> String {
+        self.skip_whitespace();
+        let start = self.index;
+        while self.peek().map_or(false, |c| !c.is_whitespace() && !matches!(c, '{' | '}' | '(' | ')' | ',' | '=' | '#' | '"')) {
+            self.index += 1;
+        }
+        self.characters[start..self.index].iter().collect()
+    }
+
+    //the inside of {...}, braces nest
+    fn read_braced(&mut self) -> Result<String, String> {
+        let start = self.index;
+        let mut depth = 0;
+        while let Some(character) = self.peek() {
+            self.index += 1;
+            match character {
+                '{' => depth += 1,
+                '}' if depth == 0 => return Ok(self.characters[start..self.index - 1].iter().collect()),
+                '}' => depth -= 1,
+                '\\' => self.index += 1, //escaped braces don't count
+                _ => {}
+            }
+        }
+        Err("unclosed \"{\"".to_string())
+    }
+
+    //"..." with braces allowed inside
+    fn read_quoted(&mut self) -> Result<String, String> {
+        let start = self.index;
+        let mut depth = 0;
+        while let Some(character) = self.peek() {
+            self.index += 1;
+            match character {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                '"' if depth == 0 => return Ok(self.characters[start..self.index - 1].iter().collect()),
+                '\\' => self.index += 1,
+                _ => {}
+            }
+        }
+        Err("unclosed quote".to_string())
+    }
+
+    //parts joined with "#": {braced}, "quoted", numbers and @string names
+    fn read_value(&mut self) -> Result<String, String> {
+        let mut value = String::new();
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some('{') => {
+                    self.index += 1;
+                    value.push_str(&self.read_braced()?);
+                }
+                Some('"') => {
+                    self.index += 1;
+                    value.push_str(&self.read_quoted()?);
+                }
+                _ => {
+                    let word = self.read_identifier();
+                    if word.is_empty() {
+                        return Err(format!("missing value at character {}", self.index));
+                    }
+                    let name = word.to_lowercase();
+                    match (self.strings.get(&name), MONTHS.iter().position(|month| *month == name)) {
+                        (Some(string), _) => value.push_str(string),
+                        (None, Some(month)) => value.push_str(&(month + 1).to_string()),
+                        (None, None) => value.push_str(&word),
+                    }
+                }
+            }
+            self.skip_whitespace();
+            if self.peek() == Some('#') {
+                self.index += 1;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    //the next entry, None at the end of the file. Comments, @preamble and @string give empty entries
+    fn read_entry(&mut self) -> Result<Option<Entry>, String> {
+        //anything outside of an entry is a comment
+        while self.peek().map_or(false, |c| c != '@') {
+            self.index += 1;
+        }
+        if self.peek().is_none() {
+            return Ok(None);
+        }
+        self.index += 1;
+
+        let kind = self.read_identifier().to_lowercase();
+        self.skip_whitespace();
+        let close = match self.peek() {
+            Some('{') => '}',
+            Some('(') => ')',
+            _ => return Ok(Some(Entry::default())),
+        };
+        self.index += 1;
+
+        match kind.as_str() {
+            "comment" | "preamble" => {
+                match close {
+                    '}' => drop(self.read_braced()?),
+                    _ => self.index += self.characters[self.index..].iter().position(|c| *c == ')').map_or(0, |end| end + 1),
+                }
+                return Ok(Some(Entry::default()));
+            }
+            "string" => {
+                let name = self.read_identifier().to_lowercase();
+                self.expect('=')?;
+                let value = self.read_value()?;
+                self.strings.insert(name, value);
+                self.expect(close)?;
+                return Ok(Some(Entry::default()));
+            }
+            _ => {}
+        }
+
+        let key = self.read_identifier();
+        let mut fields = BTreeMap::new();
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.index += 1,
+                Some(character) if character == close => {
+                    self.index += 1;
+                    break;
+                }
+                None => return Err(format!("entry \"{}\" is not closed", key)),
+                _ => {
+                    let name = self.read_identifier().to_lowercase();
+                    self.expect('=')?;
+                    let value = self.read_value()?;
+                    fields.insert(name, clean_latex(&value));
+                }
+            }
+        }
+
+        Ok(Some(Entry { kind, key, fields }))
+    }
+}
+
+//turns the usual latex in titles and names into plain text: {Protected} words, \"o accents, \& and friends
+fn clean_latex(value: &str) -> String {
+    const ACCENTS: [(char, &str, &str); 6] = [
+        ('"', "aeiouyAEIOU", "äëïöüÿÄËÏÖÜ"),
+        ('\'', "aeiouycnsAEIOUYCNS", "áéíóúýćńśÁÉÍÓÚÝĆŃŚ"),
+        ('`', "aeiouAEIOU", "àèìòùÀÈÌÒÙ"),
+        ('^', "aeiouAEIOU", "âêîôûÂÊÎÔÛ"),
+        ('~', "anoANO", "ãñõÃÑÕ"),
+        ('c', "cCsS", "çÇşŞ"),
+    ];
+
+    let characters: Vec<char> = value.chars().collect();
+    let mut text = String::new();
+    let mut index = 0;
+    while index < characters.len() {
+        let character = characters[index];
+        index += 1;
+        match character {
+            '{' | '}' => {}
+            '~' => text.push(' '),
+            '\\' => {
+                let command = match characters.get(index) {
+                    Some(command) => *command,
+                    None => break,
+                };
+                index += 1;
+                //\c is a letter command too, it only counts as the cedilla when followed by "{" or " "
+                let accent = ACCENTS.iter().find(|(accent, _, _)| *accent == command)
+                    .filter(|_| !command.is_alphabetic() || matches!(characters.get(index), Some('{') | Some(' ')));
+                if let Some((_, plain, accented)) = accent {
+                    //\"o, \"{o} and \c{c}
+                    while characters.get(index).map_or(false, |c| *c == '{' || (*c == ' ' && command == 'c')) {
+                        index += 1;
+                    }
+                    if let Some(position) = characters.get(index).and_then(|letter| plain.chars().position(|c| c == *letter)) {
+                        text.push(accented.chars().nth(position).unwrap());
+                        index += 1;
+                        continue;
+                    }
+                } else if command.is_alphabetic() {
+                    //other commands (\emph, \textit...) just go away, their argument stays
+                    while characters.get(index).map_or(false, |c| c.is_alphabetic()) {
+                        index += 1;
+                    }
+                } else {
+                    text.push(command); //\& \% \_ \$ \{ \}
+                }
+            }
+            _ => text.push(character),
+        }
+    }
+
+    text.split_whitespace().collect::<Vec<_>>().join(" ").replace("---", "—").replace("--", "–")
+}
+
+//"Last, First and First Last" -> ["First Last", "First Last"]
+fn split_authors(authors: &str) -> Vec<String> {
+    authors
+        .split(" and ")
+        .map(|author| match author.split_once(',') {
+            Some((last, first)) => format!("{} {}", first.trim(), last.trim()),
+            None => author.trim().to_string(),
+        })
+        .filter(|author| !author.is_empty() && author != "others")
+        .collect()
+}
+
+fn parse(data: &str) -> Result<Vec<Entry>, String> {
+    let mut strings = HashMap::new();
+    let mut parser = Parser { characters: data.chars().collect(), index: 0, strings: &mut strings };
+    let mut entries = Vec::new();
+    while let Some(entry) = parser.read_entry()? {
+        if !entry.kind.is_empty() && !entry.key.is_empty() {
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
+}
+
+//the node with this tag and content, made if there isn't one yet. New ones are added to created
+fn find_or_create(content: &str, tag: &str, owner: &str, created: &mut Vec<Arc<RwLock<Node>>>) -> Arc<RwLock<Node>> {
+    let existing = NODES.read().unwrap().iter()
+        .find(|node| {
+            let node = node.read().unwrap();
+            node.get_tags().contains(tag) && node.get_content() == content
+        })
+        .cloned();
+    if let Some(node) = existing {
+        return node;
+    }
+
+    let mut node = Node::create_and_register(content.to_string(), owner.to_string());
+    node.add_tag(tag.to_string());
+    let node = register_node(node);
+    created.push(node.clone());
+    node
+}
+
+//a link between two nodes unless there already is one
+fn link_once(from: i64, to: i64, label: &str, owner: &str) -> bool {
+    let exists = LINKS.read().unwrap().iter().any(|link| {
+        let link = link.read().unwrap();
+        link.get_from_id() == from && link.get_to_id() == to
+    });
+    if exists || from == to {
+        return false;
+    }
+
+    let mut link = Link::create_and_register(from, to, owner.to_string());
+    link.set_label(label.to_string());
+    link.set_relation(label.to_string());
+    register_link(link);
+    true
+}
+
+//returns (nodes, links) created. Entries whose key is already in the workspace are skipped
+pub fn import(path: &str, entities: &Entities, module: &ModuleRef, layout: Layout) -> Result<(usize, usize), String> {
+    let entries = parse(&read_file(path)?).map_err(|e| format!("{}: {}", path, e))?;
+    let owner = module.read().unwrap().get_name();
+
+    let mut papers: HashMap<String, i64> = NODES.read().unwrap().iter()
+        .map(|node| node.read().unwrap())
+        .filter_map(|node| Some((node.get_property("citekey")?.clone(), node.get_id())))
+        .collect();
+
+    let mut created = Vec::new();
+    let mut links = 0;
+    for entry in entries {
+        if papers.contains_key(&entry.key) {
+            continue;
+        }
+
+        let title = entry.fields.get("title").cloned().unwrap_or(entry.key.clone());
+        let mut node = Node::create_and_register(title, owner.clone());
+        node.add_tag(PAPER_TAG.to_string());
+        node.set_property("citekey".to_string(), entry.key.clone());
+        node.set_property("type".to_string(), entry.kind.clone());
+
+        let authors = split_authors(entry.fields.get("author").or(entry.fields.get("editor")).map(String::as_str).unwrap_or_default());
+        if !authors.is_empty() {
+            node.set_property("authors".to_string(), authors.join(AUTHOR_SEPARATOR));
+        }
+        let venue = VENUE_FIELDS.iter().find_map(|field| entry.fields.get(*field)).cloned();
+        if let Some(venue) = &venue {
+            node.set_property("venue".to_string(), venue.clone());
+        }
+        for (name, value) in &entry.fields {
+            if !matches!(name.as_str(), "title" | "author") {
+                node.set_property(name.clone(), value.clone());
+            }
+        }
+
+        let id = node.get_id();
+        papers.insert(entry.key, id);
+        created.push(register_node(node));
+
+        if entities.authors {
+            for author in &authors {
+                let author = find_or_create(author, AUTHOR_TAG, &owner, &mut created);
+                let author = author.read().unwrap().get_id();
+                links += link_once(id, author, "author", &owner) as usize;
+            }
+        }
+        if let (true, Some(venue)) = (entities.venues, venue) {
+            let venue = find_or_create(&venue, VENUE_TAG, &owner, &mut created);
+            let venue = venue.read().unwrap().get_id();
+            links += link_once(id, venue, "published in", &owner) as usize;
+        }
+    }
+
+    links += link_citations(&papers, &owner);
+
+    let edges = new_edges(&created);
+    place_nodes(module, &created, &edges, layout);
+
+    Ok((created.len(), links))
+}
+
+//links every node that mentions a known key to that paper, returns how many links were made
+fn link_citations(papers: &HashMap<String, i64>, owner: &str) -> usize {
+    let citations: Vec<(i64, Vec<String>)> = NODES.read().unwrap().iter()
+        .map(|node| node.read().unwrap())
+        .map(|node| (node.get_id(), find_citations(node.get_content())))
+        .collect();
+
+    let mut links = 0;
+    for (id, keys) in citations {
+        for key in keys {
+            if let Some(paper) = papers.get(&key) {
+                links += link_once(id, *paper, "cites", owner) as usize;
+            }
+        }
+    }
+    links
+}
+
+//pandoc style [@key; @other] and @key, and latex \cite{key,other} (\citep, \citet...)
+fn find_citations(content: &str) -> Vec<String> {
+    let is_key_character = |c: char| c.is_alphanumeric() || matches!(c, '_' | ':' | '.' | '-' | '+' | '/');
+    let mut keys = Vec::new();
+
+    let characters: Vec<char> = content.chars().collect();
+    for (index, character) in characters.iter().enumerate() {
+        //not e-mail addresses
+        if *character == '@' && (index == 0 || !characters[index - 1].is_alphanumeric()) {
+            let key: String = characters[index + 1..].iter().take_while(|c| is_key_character(**c)).collect();
+            let key = key.trim_end_matches(['.', ':']);
+            if !key.is_empty() {
+                keys.push(key.to_string());
+            }
+        }
+    }
+
+    let mut rest = content;
+    while let Some(start) = rest.find("\\cite") {
+        rest = &rest[start + 5..];
+        let open = match rest.find('{') {
+            Some(open) if rest[..open].chars().all(|c| c.is_alphabetic() || c == '*' || c == '[' || c == ']') => open,
+            _ => continue,
+        };
+        let close = match rest[open..].find('}') {
+            Some(close) => open + close,
+            None => break,
+        };
+        keys.extend(rest[open + 1..close].split(',').map(|key| key.trim().to_string()).filter(|key| !key.is_empty()));
+        rest = &rest[close..];
+    }
+
+    let mut seen = HashSet::new();
+    keys.retain(|key| seen.insert(key.clone()));
+    keys
+}
+
+//links among the created nodes as index pairs, for the layout
+fn new_edges(created: &[Arc<RwLock<Node>>]) -> Vec<(usize, usize)> {
+    let index: HashMap<i64, usize> = created.iter().enumerate().map(|(index, node)| (node.read().unwrap().get_id(), index)).collect();
+    LINKS.read().unwrap().iter()
+        .map(|link| link.read().unwrap())
+        .filter_map(|link| Some((*index.get(&link.get_from_id())?, *index.get(&link.get_to_id())?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_fields_and_braces() {
+        let data = r#"
+            a comment outside of entries
+            @string{acm = "ACM"}
+            @comment{ignored {nested} braces}
+            @Article{smith2020,
+                title = {The {DNA} of \emph{Graphs}},
+                author = "Smith, Jane and Bob Jones and others",
+                journal = acm # { Transactions},
+                month = mar,
+                year = 2020,
+                note = {escaped \} brace},
+            }
+            @book(doe, title = "A {"}quoted{"} title")
+        "#;
+        let entries = parse(data).unwrap();
+        assert_eq!(entries.len(), 2);
+
+        let article = &entries[0];
+        assert_eq!((article.kind.as_str(), article.key.as_str()), ("article", "smith2020"));
+        assert_eq!(article.fields["title"], "The DNA of Graphs");
+        assert_eq!(article.fields["journal"], "ACM Transactions");
+        assert_eq!(article.fields["month"], "3");
+        assert_eq!(article.fields["year"], "2020");
+        assert_eq!(article.fields["note"], "escaped } brace");
+        assert_eq!(split_authors(&article.fields["author"]), vec!["Jane Smith", "Bob Jones"]);
+
+        assert_eq!(entries[1].kind, "book");
+        assert_eq!(entries[1].fields["title"], "A \"quoted\" title");
+    }
+
+    #[test]
+    fn unclosed_entries_are_errors() {
+        assert!(parse("@article{key, title = {open").is_err());
+        assert!(parse("@article{key, title = \"open}").is_err());
+        assert!(parse("@article{key, title = {closed}").is_err());
+    }
+
+    #[test]
+    fn latex_becomes_text() {
+        assert_eq!(clean_latex(r#"G{\"o}del, Escher, Bach"#), "Gödel, Escher, Bach");
+        assert_eq!(clean_latex(r"Fran\c{c}ois \& Jos\'e"), "François & José");
+        assert_eq!(clean_latex("pages 1--10, a~b --- c"), "pages 1–10, a b — c");
+        assert_eq!(clean_latex(r"\textbf{bold}   spaced"), "bold spaced");
+    }
+
+    #[test]
+    fn citations() {
+        let content = "see [@smith2020; @doe] and \\citep{knuth:84, lamport} but not me@mail.com or @smith2020.";
+        assert_eq!(find_citations(content), vec!["smith2020", "doe", "knuth:84", "lamport"]);
+    }
+}
@@ -0,0 +1,321 @@
+use crate::formats::{find_node, place_nodes, read_file, register_link, register_node, write_file, Layout};
+use crate::hierarchy;
+use crate::structs::link::{Link, LinkDirection, LinkStyle};
+use crate::structs::node::Node;
+use crate::types::ModuleRef;
+use crate::LINKS;
+use std::collections::{BTreeSet, HashMap};
+
+//spreadsheets: a nodes table (id, content, x, y, tags, parent) and an optional links table (from, to, label, type,
+//direction, weight, style).
+//rows are exported in tree order, so children read back in order below the parent their "parent" cell names.
+//.tsv files are tab separated, everything else comma separated. Any other column of the nodes table
+//becomes a node property, and properties are written back as extra columns.
+
+const TAG_SEPARATOR: char = ';';
+
+//which column holds what. Every field defaults to the column with its own name
+pub struct ColumnMapping {
+    columns: HashMap<String, String>, //field -> column name
+}
+
+impl ColumnMapping {
+    //"content=Title,x=PosX" style pairs, fields are id, content, x, y, tags, parent, from, to, label, type, direction,
+    //weight and style
+    pub fn parse(pairs: &[String]) -> Result<ColumnMapping, String> {
+        let mut columns = HashMap::new();
+        for pair in pairs {
+            let (field, column) = pair.split_once('=').ok_or(format!("column mapping \"{}\" is not field=column", pair))?;
+            let field = field.trim().to_lowercase();
+            let fields = ["id", "content", "x", "y", "tags", "parent", "from", "to", "label", "type", "direction", "weight", "style"];
+            if !fields.contains(&field.as_str()) {
+                return Err(format!("unknown field \"{}\" in column mapping", field));
+            }
+            columns.insert(field, column.trim().to_string());
+        }
+        Ok(ColumnMapping { columns })
+    }
+
+    fn find(&self, header: &[String], field: &str) -> Option<usize> {
+        let column = self.columns.get(field).map(|column| column.as_str()).unwrap_or(field);
+        header.iter().position(|name| name.trim().eq_ignore_ascii_case(column))
+    }
+}
+
+fn delimiter(path: &str) -> char {
+    if path.to_lowercase().ends_with(".tsv") {
+        '\t'
+    } else {
+        ','
+    }
+}
+
+//rfc 4180: quoted fields can contain delimiters, newlines and "" for a quote
+fn parse_table(data: &str, delimiter: char) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+
+    let mut characters = data.trim_start_matches('\u{feff}').chars().peekable();
+    while let Some(character) = characters.next() {
+        if quoted {
+            match character {
+                '"' if characters.peek() == Some(&'"') => {
+                    field.push('"');
+                    characters.next();
+                }
+                '"' => quoted = false,
+                _ => field.push(character),
+            }
+            continue;
+        }
+        match character {
+            '"' if field.is_empty() => quoted = true,
+            '\r' => {}
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            _ if character == delimiter => row.push(std::mem::take(&mut field)),
+            _ => field.push(character),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    //blank lines are not rows
+    rows.retain(|row| row.iter().any(|field| !field.trim().is_empty()));
+    rows
+}
+
+fn write_table(rows: &[Vec<String>], delimiter: char) -> String {
+    let mut data = String::new();
+    for row in rows {
+        let fields: Vec<String> = row
+            .iter()
+            .map(|field| {
+                if field.contains(delimiter) || field.contains('"') || field.contains('\n') || field.contains('\r') {
+                    format!("\"{}\"", field.replace('"', "\"\""))
+                } else {
+                    field.clone()
+                }
+            })
+            .collect();
+        data.push_str(&fields.join(&delimiter.to_string()));
+        data.push('\n');
+    }
+    data
+}
+
+//returns (nodes, links) created
+pub fn import(nodes_path: &str, links_path: Option<&str>, mapping: &ColumnMapping, module: &ModuleRef, layout: Layout) -> Result<(usize, usize), String> {
+    let table = parse_table(&read_file(nodes_path)?, delimiter(nodes_path));
+    let (header, rows) = table.split_first().ok_or(format!("{} is empty", nodes_path))?;
+
+    let content_column = mapping.find(header, "content").ok_or(format!("{} has no content column", nodes_path))?;
+    let id_column = mapping.find(header, "id");
+    let x_column = mapping.find(header, "x");
+    let y_column = mapping.find(header, "y");
+    let tags_column = mapping.find(header, "tags");
+    let parent_column = mapping.find(header, "parent");
+    let known: Vec<usize> = [Some(content_column), id_column, x_column, y_column, tags_column, parent_column].into_iter().flatten().collect();
+
+    let owner = module.read().unwrap().get_name();
+    let mut ids = HashMap::new(); //id in the table -> node id
+    let mut nodes = Vec::new();
+    let mut parents = Vec::new(); //(node id, id of the parent in the table)
+    let mut unplaced = Vec::new(); //indices into nodes of the ones without x/y
+    for (row_index, row) in rows.iter().enumerate() {
+        let cell = |column: Option<usize>| column.and_then(|column| row.get(column)).map(|value| value.trim()).filter(|value| !value.is_empty());
+
+        let mut node = Node::create_and_register(cell(Some(content_column)).unwrap_or_default().to_string(), owner.clone());
+        if let Some(tags) = cell(tags_column) {
+            tags.split(TAG_SEPARATOR).map(|tag| tag.trim()).filter(|tag| !tag.is_empty()).for_each(|tag| node.add_tag(tag.to_string()));
+        }
+        for (column, name) in header.iter().enumerate() {
+            //the modules column of our own exports (owner in older ones) is not a property, imported nodes are
+            //in the module they're placed in
+            if ["modules", "owner"].iter().any(|column| name.trim().eq_ignore_ascii_case(column)) {
+                continue;
+            }
+            if let Some(value) = cell(Some(column)).filter(|_| !known.contains(&column)) {
+                node.set_property(name.trim().to_string(), value.to_string());
+            }
+        }
+
+        //rows without an id can't be linked to, but they are still nodes
+        let id = cell(id_column).map(|id| id.to_string()).unwrap_or(format!("row {}", row_index + 2));
+        if ids.insert(id.clone(), node.get_id()).is_some() {
+            return Err(format!("{}: id \"{}\" is used more than once", nodes_path, id));
+        }
+        if let Some(parent) = cell(parent_column) {
+            parents.push((node.get_id(), parent.to_string()));
+        }
+
+        let node = register_node(node);
+        match (cell(x_column).map(str::parse::<f32>), cell(y_column).map(str::parse::<f32>)) {
+            (Some(Ok(x)), Some(Ok(y))) => module.write().unwrap().place_node(node.clone(), (x, y), None),
+            (None, None) => unplaced.push(nodes.len()),
+            _ => return Err(format!("{}: bad position for \"{}\"", nodes_path, id)),
+        }
+        nodes.push(node);
+    }
+
+    //parents can come after their children in the table
+    for (id, parent) in parents {
+        let parent_id = *ids.get(&parent).ok_or(format!("{}: parent \"{}\" is an unknown id", nodes_path, parent))?;
+        hierarchy::set_parent(id, Some(parent_id), None).map_err(|e| format!("{}: {}", nodes_path, e))?;
+    }
+
+    let mut links = 0;
+    let mut edges = Vec::new();
+    if let Some(links_path) = links_path {
+        let table = parse_table(&read_file(links_path)?, delimiter(links_path));
+        let (header, rows) = table.split_first().ok_or(format!("{} is empty", links_path))?;
+        let from_column = mapping.find(header, "from").ok_or(format!("{} has no from column", links_path))?;
+        let to_column = mapping.find(header, "to").ok_or(format!("{} has no to column", links_path))?;
+        let label_column = mapping.find(header, "label");
+        let type_column = mapping.find(header, "type");
+        let direction_column = mapping.find(header, "direction");
+        let weight_column = mapping.find(header, "weight");
+        let style_column = mapping.find(header, "style");
+
+        for row in rows {
+            let cell = |column: usize| row.get(column).map(|value| value.trim()).unwrap_or_default();
+            let (from, to) = match (ids.get(cell(from_column)), ids.get(cell(to_column))) {
+                (Some(from), Some(to)) => (*from, *to),
+                _ => return Err(format!("{}: link from \"{}\" to \"{}\" refers to an unknown id", links_path, cell(from_column), cell(to_column))),
+            };
+            let mut link = Link::create_and_register(from, to, owner.clone());
+            if let Some(label_column) = label_column {
+                link.set_label(cell(label_column).to_string());
+            }
+            if let Some(type_column) = type_column {
+                link.set_relation(cell(type_column).to_string());
+            }
+            //empty cells keep the defaults
+            if let Some(direction) = direction_column.map(cell).filter(|direction| !direction.is_empty()) {
+                link.set_direction(LinkDirection::parse(direction).map_err(|e| format!("{}: {}", links_path, e))?);
+            }
+            if let Some(weight) = weight_column.map(cell).filter(|weight| !weight.is_empty()) {
+                link.set_weight(weight.parse().map_err(|_| format!("{}: weight \"{}\" is not a number", links_path, weight))?);
+            }
+            if let Some(style) = style_column.map(cell).filter(|style| !style.is_empty()) {
+                link.set_style(Some(LinkStyle::parse(style).map_err(|e| format!("{}: {}", links_path, e))?));
+            }
+            register_link(link);
+            links += 1;
+
+            let index = |id: i64| unplaced.iter().position(|&index| nodes[index].read().unwrap().get_id() == id);
+            if let (Some(a), Some(b)) = (index(from), index(to)) {
+                edges.push((a, b));
+            }
+        }
+    }
+
+    let unplaced: Vec<_> = unplaced.into_iter().map(|index| nodes[index].clone()).collect();
+    place_nodes(module, &unplaced, &edges, layout);
+
+    Ok((nodes.len(), links))
+}
+
+//"map.csv" -> "map-links.csv"
+pub fn default_links_path(nodes_path: &str) -> String {
+    match nodes_path.rfind('.').filter(|&dot| !nodes_path[dot..].contains('/')) {
+        Some(dot) => format!("{}-links{}", &nodes_path[..dot], &nodes_path[dot..]),
+        None => format!("{}-links", nodes_path),
+    }
+}
+
+//every node and link of the workspace, x/y come from the module's layout when the node is placed there
+pub fn export(nodes_path: &str, links_path: &str, module: &ModuleRef) -> Result<(usize, usize), String> {
+    let bounds = module.read().unwrap().get_node_bounds();
+    let nodes: Vec<_> = hierarchy::get_tree_order().into_iter().filter_map(find_node).collect();
+
+    let properties: BTreeSet<String> = nodes.iter().flat_map(|node| node.read().unwrap().get_properties().keys().cloned().collect::<Vec<_>>()).collect();
+
+    let mut rows = vec![["id", "content", "modules", "tags", "x", "y", "parent"].iter().map(|name| name.to_string()).chain(properties.iter().cloned()).collect::<Vec<_>>()];
+    for node in nodes.iter() {
+        let node = node.read().unwrap();
+        let (x, y) = match bounds.get(&node.get_id()) {
+            Some(rect) => (rect.left().to_string(), rect.top().to_string()),
+            None => (String::new(), String::new()),
+        };
+        let mut row = vec![
+            node.get_id().to_string(),
+            node.get_content().clone(),
+            node.get_modules().iter().cloned().collect::<Vec<_>>().join(&TAG_SEPARATOR.to_string()),
+            node.get_tags().iter().cloned().collect::<Vec<_>>().join(&TAG_SEPARATOR.to_string()),
+            x,
+            y,
+            node.get_parent().map(|parent| parent.to_string()).unwrap_or_default(),
+        ];
+        row.extend(properties.iter().map(|key| node.get_property(key).cloned().unwrap_or_default()));
+        rows.push(row);
+    }
+    write_file(nodes_path, &write_table(&rows, delimiter(nodes_path)))?;
+
+    let links = LINKS.read().unwrap();
+    let mut rows = vec![["id", "from", "to", "label", "type", "direction", "weight", "style", "owner"].iter().map(|name| name.to_string()).collect::<Vec<_>>()];
+    for link in links.iter() {
+        let link = link.read().unwrap();
+        rows.push(vec![
+            link.get_id().to_string(),
+            link.get_from_id().to_string(),
+            link.get_to_id().to_string(),
+            link.get_label().clone(),
+            link.get_relation().clone(),
+            link.get_direction().get_name().to_string(),
+            link.get_weight().to_string(),
+            link.get_style().map(|style| style.get_name().to_string()).unwrap_or_default(),
+            link.get_owner().clone(),
+        ]);
+    }
+    write_file(links_path, &write_table(&rows, delimiter(links_path)))?;
+
+    Ok((nodes.len(), links.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoted_fields() {
+        let data = "\u{feff}id,content\r\n1,\"a, b\"\r\n2,\"say \"\"hi\"\"\nsecond line\"\n\n3,plain \"quote\"\n4,";
+        let rows = parse_table(data, ',');
+        assert_eq!(rows, vec![
+            vec!["id", "content"],
+            vec!["1", "a, b"],
+            vec!["2", "say \"hi\"\nsecond line"],
+            vec!["3", "plain \"quote\""],
+            vec!["4", ""],
+        ]);
+    }
+
+    #[test]
+    fn write_then_read() {
+        let rows: Vec<Vec<String>> = vec![
+            vec!["content".to_string(), "tags".to_string()],
+            vec!["tab\there, \"quoted\"\nand a newline".to_string(), "a;b".to_string()],
+        ];
+        for delimiter in [',', '\t'] {
+            assert_eq!(parse_table(&write_table(&rows, delimiter), delimiter), rows);
+        }
+    }
+
+    #[test]
+    fn column_mapping() {
+        let mapping = ColumnMapping::parse(&["Content = Title".to_string()]).unwrap();
+        let header = vec!["ID".to_string(), " title ".to_string()];
+        assert_eq!(mapping.find(&header, "content"), Some(1));
+        assert_eq!(mapping.find(&header, "id"), Some(0));
+        assert_eq!(mapping.find(&header, "x"), None);
+        assert!(ColumnMapping::parse(&["colour=Color".to_string()]).is_err());
+        assert!(ColumnMapping::parse(&["content".to_string()]).is_err());
+        assert_eq!(delimiter("nodes.TSV"), '\t');
+    }
+}
@@ -0,0 +1,284 @@
+use crate::attachments::Attachment;
+use crate::formats::{place_nodes, read_file, register_node, Layout};
+use crate::hierarchy;
+use crate::structs::node::Node;
+use crate::types::ModuleRef;
+use serde::Deserialize;
+use std::fs::File;
+use std::io::Read;
+use std::sync::{Arc, RwLock};
+
+//EPUB books (EPUB 3 nav documents and EPUB 2 NCX tables of contents). The book is a root node, every entry
+//of the table of contents a chapter node, a child of its parent entry. Highlights exported from calibre
+//(.calibre_highlights, or any file with the same JSON) can go below the chapters they were made in.
+
+const BOOK_TAG: &str = "book";
+const CHAPTER_TAG: &str = "chapter";
+const HIGHLIGHT_TAG: &str = "highlight";
+
+struct TocEntry {
+    title: String,
+    href: String, //path inside the epub, with the #fragment
+    children: Vec<TocEntry>,
+}
+
+#[derive(Deserialize)]
+struct Annotations {
+    #[serde(default)]
+    annotations: Vec<Annotation>,
+}
+
+#[derive(Deserialize)]
+struct Annotation {
+    #[serde(default, rename = "type")]
+    kind: String,
+    #[serde(default)]
+    highlighted_text: String,
+    #[serde(default)]
+    notes: Option<String>,
+    #[serde(default)]
+    spine_name: Option<String>, //the chapter's file
+    #[serde(default)]
+    toc_family_titles: Vec<String>, //titles from the outermost to the innermost toc entry
+}
+
+struct Book {
+    archive: zip::ZipArchive<File>,
+}
+
+impl Book {
+    fn open(path: &str) -> Result<Book, String> {
+        let file = File::open(path).map_err(|e| format!("can't read {}: {}", path, e))?;
+        let archive = zip::ZipArchive::new(file).map_err(|e| format!("{} is not an epub: {}", path, e))?;
+        Ok(Book { archive })
+    }
+
+    fn read(&mut self, name: &str) -> Result<String, String> {
+        let mut file = self.archive.by_name(name).map_err(|e| format!("missing {} in the epub: {}", name, e))?;
+        let mut data = String::new();
+        file.read_to_string(&mut data).map_err(|e| format!("can't read {} in the epub: {}", name, e))?;
+        Ok(data)
+    }
+}
+
+//epubs are xhtml, which usually comes with a doctype
+fn parse_xml(text: &str) -> Result<roxmltree::Document<'_>, String> {
+    let options = roxmltree::ParsingOptions { allow_dtd: true, ..Default::default() };
+    roxmltree::Document::parse_with_options(text, options).map_err(|e| e.to_string())
+}
+
+//"OEBPS/content.opf" + "../Text/ch1.xhtml#part" -> "Text/ch1.xhtml#part"
+fn resolve(base_file: &str, href: &str) -> String {
+    let mut parts: Vec<&str> = base_file.split('/').collect();
+    parts.pop();
+    for part in href.split('/') {
+        match part {
+            "." | "" => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+fn element_text(node: roxmltree::Node) -> String {
+    let text: String = node.descendants().filter(|child| child.is_text()).filter_map(|child| child.text()).collect();
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+//<nav epub:type="toc"><ol><li><a href="...">title</a><ol>...</ol></li></ol></nav>
+fn read_nav_list(list: roxmltree::Node, base: &str) -> Vec<TocEntry> {
+    list.children()
+        .filter(|item| item.has_tag_name("li"))
+        .filter_map(|item| {
+            //entries without a link (span headings) still group their children
+            let label = item.children().find(|child| child.has_tag_name("a") || child.has_tag_name("span"))?;
+            let children = item.children().find(|child| child.has_tag_name("ol")).map(|list| read_nav_list(list, base)).unwrap_or_default();
+            Some(TocEntry {
+                title: element_text(label),
+                href: label.attribute("href").map(|href| resolve(base, href)).unwrap_or_default(),
+                children,
+            })
+        })
+        .collect()
+}
+
+fn read_nav(text: &str, base: &str) -> Result<Vec<TocEntry>, String> {
+    let document = parse_xml(text)?;
+    let nav = document
+        .descendants()
+        .filter(|node| node.has_tag_name("nav"))
+        .find(|nav| nav.attributes().any(|attribute| attribute.name() == "type" && attribute.value().split_whitespace().any(|kind| kind == "toc")))
+        .or(document.descendants().find(|node| node.has_tag_name("nav")))
+        .ok_or("the navigation document has no table of contents")?;
+    let list = nav.descendants().find(|node| node.has_tag_name("ol")).ok_or("the table of contents is empty")?;
+    Ok(read_nav_list(list, base))
+}
+
+//<navMap><navPoint><navLabel><text>title</text></navLabel><content src="..."/><navPoint>...</navPoint></navPoint></navMap>
+fn read_ncx_points(parent: roxmltree::Node, base: &str) -> Vec<TocEntry> {
+    parent
+        .children()
+        .filter(|point| point.has_tag_name("navPoint"))
+        .map(|point| TocEntry {
+            title: point.children().find(|child| child.has_tag_name("navLabel")).map(element_text).unwrap_or_default(),
+            href: point
+                .children()
+                .find(|child| child.has_tag_name("content"))
+                .and_then(|content| content.attribute("src"))
+                .map(|src| resolve(base, src))
+                .unwrap_or_default(),
+            children: read_ncx_points(point, base),
+        })
+        .collect()
+}
+
+fn read_ncx(text: &str, base: &str) -> Result<Vec<TocEntry>, String> {
+    let document = parse_xml(text)?;
+    let map = document.descendants().find(|node| node.has_tag_name("navMap")).ok_or("the NCX has no navMap")?;
+    Ok(read_ncx_points(map, base))
+}
+
+//(title, authors, table of contents)
+fn read_book(path: &str) -> Result<(String, Vec<String>, Vec<TocEntry>), String> {
+    let mut book = Book::open(path)?;
+
+    let container = book.read("META-INF/container.xml")?;
+    let container = parse_xml(&container)?;
+    let opf_path = container
+        .descendants()
+        .find(|node| node.has_tag_name("rootfile"))
+        .and_then(|rootfile| rootfile.attribute("full-path"))
+        .ok_or("META-INF/container.xml has no rootfile")?
+        .to_string();
+
+    let opf = book.read(&opf_path)?;
+    let opf = parse_xml(&opf).map_err(|e| format!("{}: {}", opf_path, e))?;
+    let metadata = |name: &str| -> Vec<String> {
+        opf.descendants().filter(|node| node.has_tag_name(name) && node.tag_name().namespace() == Some("http://purl.org/dc/elements/1.1/")).map(element_text).collect()
+    };
+    let title = metadata("title").into_iter().next().unwrap_or(path.rsplit('/').next().unwrap_or(path).to_string());
+    let authors = metadata("creator");
+
+    //epub 3 has a nav document, epub 2 an NCX named by the spine
+    let items: Vec<roxmltree::Node> = opf.descendants().filter(|node| node.has_tag_name("item")).collect();
+    let nav = items.iter().find(|item| item.attribute("properties").map_or(false, |properties| properties.split_whitespace().any(|property| property == "nav")));
+    let ncx = opf
+        .descendants()
+        .find(|node| node.has_tag_name("spine"))
+        .and_then(|spine| spine.attribute("toc"))
+        .and_then(|id| items.iter().find(|item| item.attribute("id") == Some(id)))
+        .or(items.iter().find(|item| item.attribute("media-type") == Some("application/x-dtbncx+xml")));
+
+    let toc = match (nav, ncx) {
+        (Some(nav), _) => {
+            let nav_path = resolve(&opf_path, nav.attribute("href").unwrap_or_default());
+            read_nav(&book.read(&nav_path)?, &nav_path).map_err(|e| format!("{}: {}", nav_path, e))?
+        }
+        (None, Some(ncx)) => {
+            let ncx_path = resolve(&opf_path, ncx.attribute("href").unwrap_or_default());
+            read_ncx(&book.read(&ncx_path)?, &ncx_path).map_err(|e| format!("{}: {}", ncx_path, e))?
+        }
+        (None, None) => return Err(format!("{} has no table of contents", path)),
+    };
+
+    Ok((title, authors, toc))
+}
+
+struct Chapter {
+    index: usize, //into the nodes, for the layout
+    title: String,
+    file: String, //href without the fragment
+    depth: usize,
+}
+
+fn add_entries(
+    entries: Vec<TocEntry>,
+    parent: usize,
+    depth: usize,
+    owner: &str,
+    nodes: &mut Vec<Arc<RwLock<Node>>>,
+    edges: &mut Vec<(usize, usize)>,
+    chapters: &mut Vec<Chapter>,
+) {
+    for entry in entries {
+        let mut node = Node::create_and_register(entry.title.clone(), owner.to_string());
+        node.add_tag(CHAPTER_TAG.to_string());
+        if !entry.href.is_empty() {
+            node.set_property("href".to_string(), entry.href.clone());
+        }
+        let node = register_node(node);
+        hierarchy::set_parent(node.read().unwrap().get_id(), Some(nodes[parent].read().unwrap().get_id()), None).unwrap();
+
+        let index = nodes.len();
+        edges.push((parent, index));
+        nodes.push(node);
+        chapters.push(Chapter {
+            index,
+            title: entry.title,
+            file: entry.href.split('#').next().unwrap_or_default().to_string(),
+            depth,
+        });
+        add_entries(entry.children, index, depth + 1, owner, nodes, edges, chapters);
+    }
+}
+
+//the chapter a highlight was made in: the deepest toc entry with its title, or else the first one in its file
+fn find_chapter(chapters: &[Chapter], annotation: &Annotation) -> Option<usize> {
+    if let Some(title) = annotation.toc_family_titles.last() {
+        let found = chapters.iter().filter(|chapter| chapter.title == *title).max_by_key(|chapter| chapter.depth);
+        if let Some(chapter) = found {
+            return Some(chapter.index);
+        }
+    }
+    let spine_name = annotation.spine_name.as_deref().filter(|name| !name.is_empty())?;
+    chapters.iter().find(|chapter| !chapter.file.is_empty() && (chapter.file.ends_with(spine_name) || spine_name.ends_with(&chapter.file))).map(|chapter| chapter.index)
+}
+
+//returns (nodes, links) created, everything goes into the hierarchy so there are no links
+pub fn import(path: &str, annotations: Option<&str>, module: &ModuleRef, layout: Layout) -> Result<(usize, usize), String> {
+    let (title, authors, toc) = read_book(path)?;
+    let annotations: Vec<Annotation> = match annotations {
+        Some(annotations_path) => serde_json::from_str::<Annotations>(&read_file(annotations_path)?)
+            .map_err(|e| format!("{} is not a calibre highlights file: {}", annotations_path, e))?
+            .annotations,
+        None => Vec::new(),
+    };
+
+    let owner = module.read().unwrap().get_name();
+    let mut book = Node::create_and_register(title, owner.clone());
+    book.add_tag(BOOK_TAG.to_string());
+    book.add_attachment(Attachment::External(path.to_string()));
+    if !authors.is_empty() {
+        book.set_property("authors".to_string(), authors.join("; "));
+    }
+
+    let mut nodes = vec![register_node(book)];
+    let mut edges = Vec::new();
+    let mut chapters = Vec::new();
+    add_entries(toc, 0, 0, &owner, &mut nodes, &mut edges, &mut chapters);
+
+    for annotation in annotations {
+        if annotation.kind != "highlight" || annotation.highlighted_text.trim().is_empty() {
+            continue;
+        }
+        //highlights outside of any chapter go below the book
+        let parent = find_chapter(&chapters, &annotation).unwrap_or(0);
+
+        let mut node = Node::create_and_register(annotation.highlighted_text.trim().to_string(), owner.clone());
+        node.add_tag(HIGHLIGHT_TAG.to_string());
+        if let Some(note) = annotation.notes.filter(|note| !note.trim().is_empty()) {
+            node.set_property("note".to_string(), note.trim().to_string());
+        }
+        let node = register_node(node);
+        hierarchy::set_parent(node.read().unwrap().get_id(), Some(nodes[parent].read().unwrap().get_id()), None).unwrap();
+        edges.push((parent, nodes.len()));
+        nodes.push(node);
+    }
+
+    place_nodes(module, &nodes, &edges, layout);
+
+    Ok((nodes.len(), 0))
+}
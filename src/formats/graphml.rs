@@ -0,0 +1,331 @@
+use crate::attachments::Attachment;
+use crate::formats::{escape_xml, find_link, find_node, place_nodes, read_file, register_link, register_node, write_file, Layout};
+use crate::hierarchy;
+use crate::structs::link::{Link, LinkDirection, LinkSide, LinkStyle};
+use crate::structs::node::Node;
+use crate::structs::property::Property;
+use crate::structs::rich_text::RichText;
+use crate::structs::task::Task;
+use crate::types::ModuleRef;
+use crate::LINKS;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+//GraphML (http://graphml.graphdrawing.org), for Gephi, yEd, Cytoscape, networkx...
+//content goes into "label", link relations into "type" and link weights into "weight" so that those tools show
+//something useful, undirected links are marked directed="false" like graphml wants.
+//everything else rmaps knows about goes into "rmaps:*" attributes so that a round trip loses nothing. Nodes are
+//written in tree order with the graphml id of their parent, so children read back in order.
+//on import, attributes we don't know (degree, modularity_class...) become node properties.
+
+const NAMESPACE: &str = "http://graphml.graphdrawing.org/xmlns";
+
+//(key id, for, attr.name, attr.type)
+const KEYS: [(&str, &str, &str, &str); 22] = [
+    ("label", "node", "label", "string"),
+    ("x", "node", "x", "double"),
+    ("y", "node", "y", "double"),
+    ("node_id", "node", "rmaps:id", "long"),
+    ("content", "node", "rmaps:content", "string"),
+    ("created", "node", "rmaps:created", "long"),
+    ("modified", "node", "rmaps:modified", "long"),
+    ("author", "node", "rmaps:author", "string"),
+    ("modules", "node", "rmaps:modules", "string"),
+    ("tags", "node", "rmaps:tags", "string"),
+    ("attachments", "node", "rmaps:attachments", "string"),
+    ("properties", "node", "rmaps:properties", "string"),
+    ("parent", "node", "rmaps:parent", "string"),
+    ("task", "node", "rmaps:task", "string"),
+    ("edge_label", "edge", "label", "string"),
+    ("type", "edge", "type", "string"),
+    ("edge_id", "edge", "rmaps:id", "long"),
+    ("edge_owner", "edge", "rmaps:owner", "string"),
+    ("sides", "edge", "rmaps:sides", "string"),
+    ("weight", "edge", "weight", "double"),
+    ("direction", "edge", "rmaps:direction", "string"),
+    ("style", "edge", "rmaps:style", "string"),
+];
+
+fn write_data(document: &mut String, key: &str, value: &str) {
+    document.push_str(&format!("      <data key=\"{}\">{}</data>\n", key, escape_xml(value)));
+}
+
+//every node and link of the workspace, x/y come from the module's layout when the node is placed there
+pub fn export(path: &str, module: &ModuleRef) -> Result<(usize, usize), String> {
+    let bounds = module.read().unwrap().get_node_bounds();
+
+    let mut document = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    document.push_str(&format!("<graphml xmlns=\"{}\">\n", NAMESPACE));
+    for (id, domain, name, kind) in KEYS {
+        document.push_str(&format!("  <key id=\"{}\" for=\"{}\" attr.name=\"{}\" attr.type=\"{}\"/>\n", id, domain, name, kind));
+    }
+    document.push_str("  <graph id=\"rmaps\" edgedefault=\"directed\">\n");
+
+    let nodes: Vec<_> = hierarchy::get_tree_order().into_iter().filter_map(find_node).collect();
+    for node in nodes.iter() {
+        let node = node.read().unwrap();
+        document.push_str(&format!("    <node id=\"n{}\">\n", node.get_id()));
+        write_data(&mut document, "label", node.get_content());
+        if let Some(rect) = bounds.get(&node.get_id()) {
+            write_data(&mut document, "x", &rect.left().to_string());
+            write_data(&mut document, "y", &rect.top().to_string());
+        }
+        write_data(&mut document, "node_id", &node.get_id().to_string());
+        //the label is the plain text, the formatting comes along for rmaps
+        if !node.get_rich_content().is_plain() {
+            write_data(&mut document, "content", &serde_json::to_string(node.get_rich_content()).unwrap());
+        }
+        if let Some(created) = node.get_created() {
+            write_data(&mut document, "created", &created.to_string());
+        }
+        if let Some(modified) = node.get_modified() {
+            write_data(&mut document, "modified", &modified.to_string());
+        }
+        if !node.get_author().is_empty() {
+            write_data(&mut document, "author", node.get_author());
+        }
+        write_data(&mut document, "modules", &serde_json::to_string(node.get_modules()).unwrap());
+        if !node.get_tags().is_empty() {
+            write_data(&mut document, "tags", &serde_json::to_string(node.get_tags()).unwrap());
+        }
+        if !node.get_attachments().is_empty() {
+            write_data(&mut document, "attachments", &serde_json::to_string(node.get_attachments()).unwrap());
+        }
+        if !node.get_properties().is_empty() {
+            write_data(&mut document, "properties", &serde_json::to_string(node.get_properties()).unwrap());
+        }
+        if let Some(parent) = node.get_parent() {
+            write_data(&mut document, "parent", &format!("n{}", parent));
+        }
+        if let Some(task) = node.get_task() {
+            write_data(&mut document, "task", &serde_json::to_string(task).unwrap());
+        }
+        document.push_str("    </node>\n");
+    }
+
+    let links = LINKS.read().unwrap();
+    for link in links.iter() {
+        let link = link.read().unwrap();
+        document.push_str(&format!(
+            "    <edge id=\"e{}\" source=\"n{}\" target=\"n{}\"{}>\n",
+            link.get_id(),
+            link.get_from_id(),
+            link.get_to_id(),
+            if link.get_direction() == LinkDirection::Undirected { " directed=\"false\"" } else { "" }
+        ));
+        if !link.get_label().is_empty() {
+            write_data(&mut document, "edge_label", link.get_label());
+        }
+        if !link.get_relation().is_empty() {
+            write_data(&mut document, "type", link.get_relation());
+        }
+        write_data(&mut document, "edge_id", &link.get_id().to_string());
+        write_data(&mut document, "edge_owner", link.get_owner());
+        if link.get_sides() != (None, None) {
+            write_data(&mut document, "sides", &serde_json::to_string(&link.get_sides()).unwrap());
+        }
+        write_data(&mut document, "weight", &link.get_weight().to_string());
+        if link.get_direction() != LinkDirection::Directed {
+            write_data(&mut document, "direction", link.get_direction().get_name());
+        }
+        if let Some(style) = link.get_style() {
+            write_data(&mut document, "style", style.get_name());
+        }
+        document.push_str("    </edge>\n");
+    }
+
+    document.push_str("  </graph>\n</graphml>\n");
+    write_file(path, &document)?;
+
+    Ok((nodes.len(), links.len()))
+}
+
+//the <data> children of a node or edge as attr.name -> value. yEd keeps labels and geometry
+//inside its own elements instead of plain attributes, those are read as label/x/y
+fn read_data(element: roxmltree::Node, keys: &HashMap<String, String>) -> BTreeMap<String, String> {
+    let mut data = BTreeMap::new();
+    for child in element.children().filter(|child| child.has_tag_name("data")) {
+        if let Some(name) = child.attribute("key").and_then(|key| keys.get(key)) {
+            data.insert(name.clone(), child.text().unwrap_or_default().to_string());
+        }
+
+        for descendant in child.descendants() {
+            match descendant.tag_name().name() {
+                "NodeLabel" | "EdgeLabel" => {
+                    let text: String = descendant.descendants().filter(|text| text.is_text()).filter_map(|text| text.text()).collect();
+                    data.entry("label".to_string()).or_insert(text.trim().to_string());
+                }
+                "Geometry" => {
+                    for name in ["x", "y"] {
+                        if let Some(value) = descendant.attribute(name) {
+                            data.entry(name.to_string()).or_insert(value.to_string());
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    data
+}
+
+//returns (nodes, links) created
+pub fn import(path: &str, module: &ModuleRef, layout: Layout) -> Result<(usize, usize), String> {
+    let text = read_file(path)?;
+    let document = roxmltree::Document::parse(&text).map_err(|e| format!("{} is not valid xml: {}", path, e))?;
+    let graphml = document.root_element();
+    if !graphml.has_tag_name("graphml") {
+        return Err(format!("{} is not a graphml file", path));
+    }
+
+    //key id -> attribute name, for nodes and for edges (ids may be reused between the two)
+    let mut node_keys = HashMap::new();
+    let mut edge_keys = HashMap::new();
+    for key in graphml.children().filter(|child| child.has_tag_name("key")) {
+        let (id, name) = match (key.attribute("id"), key.attribute("attr.name")) {
+            (Some(id), Some(name)) => (id.to_string(), name.to_string()),
+            _ => continue,
+        };
+        match key.attribute("for").unwrap_or("all") {
+            "node" => {
+                node_keys.insert(id, name);
+            }
+            "edge" => {
+                edge_keys.insert(id, name);
+            }
+            "all" => {
+                node_keys.insert(id.clone(), name.clone());
+                edge_keys.insert(id, name);
+            }
+            _ => {}
+        }
+    }
+
+    let owner = module.read().unwrap().get_name();
+    let mut ids = HashMap::new(); //graphml id -> node id
+    let mut nodes = Vec::new();
+    let mut parents = Vec::new(); //(node id, graphml id of the parent)
+    let mut unplaced = Vec::new(); //indices into nodes of the ones without x/y
+    for element in graphml.descendants().filter(|element| element.has_tag_name("node")) {
+        let graphml_id = element.attribute("id").ok_or(format!("{}: node without an id", path))?;
+        let mut data = read_data(element, &node_keys);
+
+        let content = data.remove("label").or(data.remove("name")).unwrap_or(graphml_id.to_string());
+        //imported nodes are in the module they're placed in, whatever modules showed them where they came from
+        data.remove("rmaps:modules");
+        data.remove("rmaps:owner");
+        //keep our own ids when importing into a workspace that doesn't have them yet
+        let mut node = match data.remove("rmaps:id").and_then(|id| id.parse::<i64>().ok()) {
+            Some(id) if find_node(id).is_none() => Node::create_with_id(id, content, owner.clone()),
+            _ => Node::create_and_register(content, owner.clone()),
+        };
+
+        if let Some(content) = data.remove("rmaps:content") {
+            let content: RichText = serde_json::from_str(&content).map_err(|e| format!("{}: bad content on {}: {}", path, graphml_id, e))?;
+            node.set_rich_content(content);
+        }
+        if let Some(tags) = data.remove("rmaps:tags") {
+            let tags: BTreeSet<String> = serde_json::from_str(&tags).map_err(|e| format!("{}: bad tags on {}: {}", path, graphml_id, e))?;
+            tags.into_iter().for_each(|tag| node.add_tag(tag));
+        }
+        if let Some(attachments) = data.remove("rmaps:attachments") {
+            let attachments: Vec<Attachment> = serde_json::from_str(&attachments).map_err(|e| format!("{}: bad attachments on {}: {}", path, graphml_id, e))?;
+            attachments.into_iter().for_each(|attachment| node.add_attachment(attachment));
+        }
+        if let Some(properties) = data.remove("rmaps:properties") {
+            let properties: BTreeMap<String, Property> = serde_json::from_str(&properties).map_err(|e| format!("{}: bad properties on {}: {}", path, graphml_id, e))?;
+            properties.into_iter().for_each(|(key, value)| node.set_typed_property(key, value));
+        }
+
+        if let Some(task) = data.remove("rmaps:task") {
+            let task: Task = serde_json::from_str(&task).map_err(|e| format!("{}: bad task on {}: {}", path, graphml_id, e))?;
+            node.set_task(Some(task));
+        }
+        if let Some(parent) = data.remove("rmaps:parent") {
+            parents.push((node.get_id(), parent));
+        }
+        let timestamp = |value: Option<String>| value.and_then(|value| value.trim().parse::<i64>().ok());
+        let (created, modified) = (timestamp(data.remove("rmaps:created")), timestamp(data.remove("rmaps:modified")));
+        let author = data.remove("rmaps:author");
+
+        let position = (data.remove("x").map(|x| x.parse::<f32>()), data.remove("y").map(|y| y.parse::<f32>()));
+        for (key, value) in data {
+            if !value.trim().is_empty() {
+                node.set_property(key, value.trim().to_string());
+            }
+        }
+        //last, everything above counts as a change
+        if created.is_some() || modified.is_some() {
+            node.set_timestamps(created.or(node.get_created()), modified.or(created).or(node.get_modified()));
+        }
+        if let Some(author) = author {
+            node.set_author(author);
+        }
+
+        if ids.insert(graphml_id.to_string(), node.get_id()).is_some() {
+            return Err(format!("{}: node id \"{}\" is used more than once", path, graphml_id));
+        }
+
+        let node = register_node(node);
+        match position {
+            (Some(Ok(x)), Some(Ok(y))) => module.write().unwrap().place_node(node.clone(), (x, y), None),
+            _ => unplaced.push(nodes.len()),
+        }
+        nodes.push(node);
+    }
+
+    for (id, parent) in parents {
+        let parent_id = *ids.get(&parent).ok_or(format!("{}: parent \"{}\" is an unknown node", path, parent))?;
+        hierarchy::set_parent(id, Some(parent_id), None).map_err(|e| format!("{}: {}", path, e))?;
+    }
+
+    let edge_default = graphml.descendants().find(|element| element.has_tag_name("graph"))
+        .and_then(|graph| graph.attribute("edgedefault"))
+        .map(|default| if default == "undirected" { "false" } else { "true" });
+    let mut links = 0;
+    let mut edges = Vec::new();
+    for element in graphml.descendants().filter(|element| element.has_tag_name("edge")) {
+        let (source, target) = (element.attribute("source").unwrap_or_default(), element.attribute("target").unwrap_or_default());
+        let (from, to) = match (ids.get(source), ids.get(target)) {
+            (Some(from), Some(to)) => (*from, *to),
+            _ => return Err(format!("{}: edge from \"{}\" to \"{}\" refers to an unknown node", path, source, target)),
+        };
+        let mut data = read_data(element, &edge_keys);
+
+        let link_owner = data.remove("rmaps:owner").filter(|owner| !owner.is_empty()).unwrap_or(owner.clone());
+        let mut link = match data.remove("rmaps:id").and_then(|id| id.parse::<i64>().ok()) {
+            Some(id) if find_link(id).is_none() => Link::create_with_id(id, from, to, link_owner),
+            _ => Link::create_and_register(from, to, link_owner),
+        };
+        link.set_label(data.remove("label").unwrap_or_default());
+        link.set_relation(data.remove("type").unwrap_or_default());
+        if let Some(sides) = data.remove("rmaps:sides") {
+            let sides: (Option<LinkSide>, Option<LinkSide>) = serde_json::from_str(&sides).map_err(|e| format!("{}: bad sides on an edge: {}", path, e))?;
+            link.set_sides(sides);
+        }
+        //ours, then the edge's own directed attribute, then the graph's default
+        let direction = match data.remove("rmaps:direction") {
+            Some(direction) => LinkDirection::parse(&direction).map_err(|e| format!("{}: {}", path, e))?,
+            None if element.attribute("directed").or(edge_default) == Some("false") => LinkDirection::Undirected,
+            None => LinkDirection::Directed,
+        };
+        link.set_direction(direction);
+        if let Some(weight) = data.remove("weight").and_then(|weight| weight.trim().parse::<f32>().ok()) {
+            link.set_weight(weight);
+        }
+        if let Some(style) = data.remove("rmaps:style") {
+            link.set_style(Some(LinkStyle::parse(&style).map_err(|e| format!("{}: {}", path, e))?));
+        }
+        register_link(link);
+        links += 1;
+
+        let index = |id: i64| unplaced.iter().position(|&index| nodes[index].read().unwrap().get_id() == id);
+        if let (Some(a), Some(b)) = (index(from), index(to)) {
+            edges.push((a, b));
+        }
+    }
+
+    let unplaced: Vec<_> = unplaced.into_iter().map(|index| nodes[index].clone()).collect();
+    place_nodes(module, &unplaced, &edges, layout);
+
+    Ok((nodes.len(), links))
+}
@@ -0,0 +1,163 @@
+use crate::formats::{escape_xml, write_file, LAYOUT_SPACING};
+use crate::hierarchy;
+use crate::layout;
+use crate::structs::link::{LinkDirection, LinkStyle};
+use crate::types::ModuleRef;
+use crate::utils::union_rect;
+use crate::workspace;
+use crate::{LINKS, NODES};
+use serde::Serialize;
+use speedy2d::shape::Rect;
+use std::collections::HashMap;
+
+//a single html file with the map in it and a small viewer (pan, zoom, search) that needs nothing else,
+//for people without RMaps. Export only.
+
+const VIEWER: &str = include_str!("../../res/viewer.html");
+//nodes that the module doesn't place get a box of this size
+const UNPLACED_SIZE: (f32, f32) = (600.0, 200.0);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Scope {
+    Module,    //the nodes placed in the module
+    Workspace, //every node, the ones the module doesn't place are laid out below the others
+}
+
+impl Scope {
+    pub fn parse(name: &str) -> Result<Scope, String> {
+        match name {
+            "module" => Ok(Scope::Module),
+            "workspace" => Ok(Scope::Workspace),
+            name => Err(format!("unknown scope \"{}\" (module, workspace)", name)),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ViewerNode {
+    id: i64,
+    content: String,
+    tags: Vec<String>,
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+}
+
+#[derive(Serialize)]
+struct ViewerLink {
+    from: i64,
+    to: i64,
+    label: String,
+    color: Option<String>, //the link type's, None for the viewer's own
+    direction: &'static str,
+    dash: Option<&'static str>, //stroke-dasharray
+    weight: f32,
+}
+
+#[derive(Serialize)]
+struct ViewerData {
+    nodes: Vec<ViewerNode>,
+    links: Vec<ViewerLink>,
+}
+
+pub fn export(path: &str, module: &ModuleRef, scope: Scope) -> Result<(usize, usize), String> {
+    let name = module.read().unwrap().get_name();
+    let mut bounds = module.read().unwrap().get_node_bounds();
+
+    if scope == Scope::Workspace {
+        place_remaining(&mut bounds);
+    }
+
+    let mut data = ViewerData { nodes: Vec::new(), links: Vec::new() };
+    for node in NODES.read().unwrap().iter() {
+        let node = node.read().unwrap();
+        if let Some(rect) = bounds.get(&node.get_id()) {
+            data.nodes.push(ViewerNode {
+                id: node.get_id(),
+                content: node.get_content().clone(),
+                tags: node.get_tags().iter().cloned().collect(),
+                x: rect.left(),
+                y: rect.top(),
+                width: rect.width(),
+                height: rect.height(),
+            });
+        }
+    }
+    //the viewer draws parents and children like links, only without a label
+    for node in &data.nodes {
+        if let Some(parent) = hierarchy::get_parent(node.id).filter(|parent| bounds.contains_key(parent)) {
+            data.links.push(ViewerLink {
+                from: parent,
+                to: node.id,
+                label: String::new(),
+                color: None,
+                direction: LinkDirection::Directed.get_name(),
+                dash: None,
+                weight: 1.0,
+            });
+        }
+    }
+    for link in LINKS.read().unwrap().iter() {
+        let link = link.read().unwrap();
+        if bounds.contains_key(&link.get_from_id()) && bounds.contains_key(&link.get_to_id()) {
+            let link_type = workspace::get_link_type(link.get_relation());
+            let dash = match link.get_style().or(link_type.as_ref().map(|link_type| link_type.style)).unwrap_or_default() {
+                LinkStyle::Solid => None,
+                LinkStyle::Dashed => Some("24 14"),
+                LinkStyle::Dotted => Some("6 10"),
+            };
+            data.links.push(ViewerLink {
+                from: link.get_from_id(),
+                to: link.get_to_id(),
+                label: link.get_label().clone(),
+                color: link_type.map(|link_type| link_type.color),
+                direction: link.get_direction().get_name(),
+                dash,
+                weight: link.get_weight(),
+            });
+        }
+    }
+
+    //"</script>" inside a node would end the data block, "<\/" means the same thing to JSON
+    let json = serde_json::to_string(&data).map_err(|e| e.to_string())?.replace("</", "<\\/");
+    let page = VIEWER.replace("{{title}}", &escape_xml(&name)).replace("{{data}}", &json);
+    write_file(path, &page)?;
+
+    Ok((data.nodes.len(), data.links.len()))
+}
+
+//gives the nodes that have no bounds yet a force layout below the placed ones
+fn place_remaining(bounds: &mut HashMap<i64, Rect>) {
+    let remaining: Vec<i64> = NODES.read().unwrap().iter()
+        .map(|node| node.read().unwrap().get_id())
+        .filter(|id| !bounds.contains_key(id))
+        .collect();
+    if remaining.is_empty() {
+        return;
+    }
+
+    let edges: Vec<(usize, usize)> = LINKS.read().unwrap().iter()
+        .map(|link| link.read().unwrap())
+        .filter_map(|link| {
+            let from = remaining.iter().position(|id| *id == link.get_from_id())?;
+            let to = remaining.iter().position(|id| *id == link.get_to_id())?;
+            Some((from, to))
+        })
+        .collect();
+    let positions = layout::force_directed(remaining.len(), &edges, LAYOUT_SPACING);
+
+    //the layout is centered around 0, move it so that its top left corner sits below everything else
+    let placed = bounds.values().cloned().reduce(union_rect);
+    let (left, top) = match placed {
+        Some(rect) => (rect.left(), rect.bottom() + LAYOUT_SPACING),
+        None => (0.0, 0.0),
+    };
+    let min_x = positions.iter().map(|position| position.0).fold(f32::MAX, f32::min);
+    let min_y = positions.iter().map(|position| position.1).fold(f32::MAX, f32::min);
+
+    for (id, (x, y)) in remaining.into_iter().zip(positions) {
+        let (x, y) = (x - min_x + left, y - min_y + top);
+        bounds.insert(id, Rect::from_tuples((x, y), (x + UNPLACED_SIZE.0, y + UNPLACED_SIZE.1)));
+    }
+}
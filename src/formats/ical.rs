@@ -0,0 +1,495 @@
+use crate::formats::{find_node, place_nodes, read_file, register_node, write_file, Layout};
+use crate::structs::node::Node;
+use crate::structs::task::{Recurrence, RecurrenceUnit, Task, TaskPriority, TaskStatus};
+use crate::types::ModuleRef;
+use crate::utils::{civil_to_timestamp, now, timestamp_to_civil};
+use crate::NODES;
+
+//iCalendar (.ics). Tasks become VTODOs: their status, due date, priority, recurrence (RRULE) and completion
+//go both ways. Other dates live in node properties: "start" (or "scheduled", like Org), "end", and on nodes that
+//aren't tasks "due" (or "deadline"), written as "2024-05-01", "2024-05-01 10:00" or "2024-05-01 10:00Z" for UTC.
+//nodes with a "todo" keyword or a due date become VTODOs too, nodes with only a start become VEVENTs.
+//imported VTODOs are tasks.
+//the UID holds the node id: importing a calendar that we exported updates those nodes instead of adding
+//new ones, and foreign UIDs are kept in the "uid" property for the same reason.
+
+const UID_PREFIX: &str = "rmaps-node-";
+const UID_DOMAIN: &str = "@rmaps";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct When {
+    date: (i32, u32, u32),
+    time: Option<(u32, u32)>,
+    utc: bool,
+}
+
+impl When {
+    //"2024-05-01", "2024-05-01 10:00", "2024-05-01T10:00:00", and Org's "2024-05-01 Wed 10:00-11:00"
+    fn parse_property(text: &str) -> Option<When> {
+        let text = text.trim();
+        let date = text.get(..10)?;
+        let mut parts = date.split('-');
+        let date = (parts.next()?.parse().ok()?, parts.next()?.parse().ok()?, parts.next()?.parse().ok()?);
+
+        let rest = text[10..].trim_start_matches(['T', ' ']);
+        let utc = rest.trim_end().ends_with('Z');
+        let time = rest
+            .split_whitespace()
+            .find(|word| word.contains(':'))
+            .and_then(|word| {
+                let mut parts = word.trim_end_matches('Z').split(['-', ':']);
+                Some((parts.next()?.parse().ok()?, parts.next()?.parse().ok()?))
+            });
+
+        Some(When { date, time, utc: utc && time.is_some() })
+    }
+
+    //unix time, midnight for dates without a time
+    fn to_timestamp(self) -> i64 {
+        let (year, month, day) = self.date;
+        let (hour, minute) = self.time.unwrap_or((0, 0));
+        civil_to_timestamp(year, month, day) + (hour * 3600 + minute * 60) as i64
+    }
+
+    fn from_timestamp(timestamp: i64) -> When {
+        let (date, (hour, minute, _)) = timestamp_to_civil(timestamp);
+        When { date, time: Some((hour, minute)), utc: true }
+    }
+
+    fn to_property(self) -> String {
+        let (year, month, day) = self.date;
+        match self.time {
+            Some((hour, minute)) => format!("{:04}-{:02}-{:02} {:02}:{:02}{}", year, month, day, hour, minute, if self.utc { "Z" } else { "" }),
+            None => format!("{:04}-{:02}-{:02}", year, month, day),
+        }
+    }
+
+    //"20240501", "20240501T100000", "20240501T100000Z"
+    fn parse_ical(value: &str) -> Option<When> {
+        let date = (value.get(0..4)?.parse().ok()?, value.get(4..6)?.parse().ok()?, value.get(6..8)?.parse().ok()?);
+        let time = match value.get(8..9) {
+            Some("T") => Some((value.get(9..11)?.parse().ok()?, value.get(11..13)?.parse().ok()?)),
+            _ => None,
+        };
+        Some(When { date, time, utc: value.ends_with('Z') })
+    }
+
+    //"NAME;VALUE=DATE:20240501" or "NAME:20240501T100000"
+    fn to_ical(self, name: &str) -> String {
+        let (year, month, day) = self.date;
+        match self.time {
+            Some((hour, minute)) => format!("{}:{:04}{:02}{:02}T{:02}{:02}00{}", name, year, month, day, hour, minute, if self.utc { "Z" } else { "" }),
+            None => format!("{};VALUE=DATE:{:04}{:02}{:02}", name, year, month, day),
+        }
+    }
+}
+
+//"FREQ=WEEKLY;INTERVAL=2". Rules that only a calendar can follow (BYDAY, COUNT...) repeat on the frequency alone
+fn parse_rule(rule: &str) -> Option<Recurrence> {
+    let parts: Vec<(&str, &str)> = rule.split(';').filter_map(|part| part.split_once('=')).collect();
+    let get = |name: &str| parts.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| *value);
+    let unit = match get("FREQ")?.to_uppercase().as_str() {
+        "DAILY" => RecurrenceUnit::Day,
+        "WEEKLY" => RecurrenceUnit::Week,
+        "MONTHLY" => RecurrenceUnit::Month,
+        "YEARLY" => RecurrenceUnit::Year,
+        _ => return None,
+    };
+    let every = get("INTERVAL").and_then(|every| every.parse().ok()).filter(|every| *every > 0).unwrap_or(1);
+    Some(Recurrence { every, unit })
+}
+
+fn to_rule(recurrence: Recurrence) -> String {
+    let frequency = match recurrence.unit {
+        RecurrenceUnit::Day => "DAILY",
+        RecurrenceUnit::Week => "WEEKLY",
+        RecurrenceUnit::Month => "MONTHLY",
+        RecurrenceUnit::Year => "YEARLY",
+    };
+    match recurrence.every {
+        1 => format!("RRULE:FREQ={}", frequency),
+        every => format!("RRULE:FREQ={};INTERVAL={}", frequency, every),
+    }
+}
+
+//the first of these properties holding a date
+fn find_date(node: &Node, properties: &[&str]) -> Option<When> {
+    properties.iter().find_map(|property| When::parse_property(node.get_property(property)?))
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\").replace(';', "\\;").replace(',', "\\,").replace("\r\n", "\n").replace('\n', "\\n")
+}
+
+fn unescape_text(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut characters = text.chars();
+    while let Some(character) = characters.next() {
+        match character {
+            '\\' => match characters.next() {
+                Some('n') | Some('N') => unescaped.push('\n'),
+                Some(other) => unescaped.push(other),
+                None => {}
+            },
+            _ => unescaped.push(character),
+        }
+    }
+    unescaped
+}
+
+//lines longer than 75 bytes continue on the next line after a space
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length = 0;
+    for character in line.chars() {
+        if length + character.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(character);
+        length += character.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+//UTC, for DTSTAMP
+fn now_stamp() -> String {
+    let ((year, month, day), (hour, minute, second)) = timestamp_to_civil(now());
+    format!("{:04}{:02}{:02}T{:02}{:02}{:02}Z", year, month, day, hour, minute, second)
+}
+
+//every node with a date. returns (nodes, 0)
+pub fn export(path: &str) -> Result<(usize, usize), String> {
+    let stamp = now_stamp();
+    let mut calendar = String::from("BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//RMaps//RMaps//EN\r\n");
+
+    let mut exported = 0;
+    for node in NODES.read().unwrap().iter() {
+        let node = node.read().unwrap();
+        let task = node.get_task();
+        //task due dates are UTC
+        let due = match task {
+            Some(task) => task.get_due().and_then(|due| When::parse_property(due)).map(|due| When { utc: due.time.is_some(), ..due }),
+            None => find_date(&node, &["due", "deadline"]),
+        };
+        let start = find_date(&node, &["start", "scheduled"]);
+        let end = find_date(&node, &["end"]);
+        let todo = node.get_property("todo");
+
+        let component = match (task, todo, due, start) {
+            (Some(_), _, _, _) => "VTODO",
+            (_, _, None, None) => continue,
+            (_, None, None, Some(_)) => "VEVENT",
+            _ => "VTODO",
+        };
+
+        let mut lines = vec![
+            format!("BEGIN:{}", component),
+            format!("UID:{}{}{}", UID_PREFIX, node.get_id(), UID_DOMAIN),
+            format!("DTSTAMP:{}", stamp),
+        ];
+
+        let mut content = node.get_content().splitn(2, '\n');
+        lines.push(format!("SUMMARY:{}", escape_text(content.next().unwrap_or_default().trim())));
+        let description: Vec<&str> = content.next().into_iter().chain(node.get_property("body").map(String::as_str)).collect();
+        if !description.is_empty() {
+            lines.push(format!("DESCRIPTION:{}", escape_text(description.join("\n").trim())));
+        }
+        if let Some(location) = node.get_property("location") {
+            lines.push(format!("LOCATION:{}", escape_text(location)));
+        }
+        if !node.get_tags().is_empty() {
+            let tags: Vec<String> = node.get_tags().iter().map(|tag| escape_text(tag)).collect();
+            lines.push(format!("CATEGORIES:{}", tags.join(",")));
+        }
+
+        if let Some(start) = start {
+            lines.push(start.to_ical("DTSTART"));
+        }
+        if component == "VEVENT" {
+            if let Some(end) = end {
+                lines.push(end.to_ical("DTEND"));
+            }
+        } else {
+            if let Some(due) = due {
+                lines.push(due.to_ical("DUE"));
+            }
+            match task {
+                Some(task) => write_task(&mut lines, task),
+                None => write_todo_properties(&mut lines, &node),
+            }
+        }
+        lines.push(format!("END:{}", component));
+
+        lines.iter().for_each(|line| calendar.push_str(&fold(line)));
+        exported += 1;
+    }
+
+    calendar.push_str("END:VCALENDAR\r\n");
+    write_file(path, &calendar)?;
+
+    Ok((exported, 0))
+}
+
+fn write_task(lines: &mut Vec<String>, task: &Task) {
+    match task.priority {
+        Some(TaskPriority::High) => lines.push("PRIORITY:1".to_string()),
+        Some(TaskPriority::Medium) => lines.push("PRIORITY:5".to_string()),
+        Some(TaskPriority::Low) => lines.push("PRIORITY:9".to_string()),
+        None => {}
+    }
+    if let Some(recurrence) = task.recurrence {
+        lines.push(to_rule(recurrence));
+    }
+    lines.push(format!("STATUS:{}", match task.status {
+        TaskStatus::Open => "NEEDS-ACTION",
+        TaskStatus::InProgress => "IN-PROCESS",
+        TaskStatus::Done => "COMPLETED",
+        TaskStatus::Cancelled => "CANCELLED",
+    }));
+    if let Some(completed) = task.completed {
+        lines.push(When::from_timestamp(completed).to_ical("COMPLETED"));
+    }
+}
+
+//todos of nodes that aren't tasks, from Org-like properties
+fn write_todo_properties(lines: &mut Vec<String>, node: &Node) {
+    //Org's priorities: A is high, C is low
+    match node.get_property("priority").map(String::as_str) {
+        Some("A") => lines.push("PRIORITY:1".to_string()),
+        Some("B") => lines.push("PRIORITY:5".to_string()),
+        Some("C") => lines.push("PRIORITY:9".to_string()),
+        _ => {}
+    }
+    if node.get_property("todo").map_or(false, |todo| todo == "DONE") {
+        lines.push("STATUS:COMPLETED".to_string());
+        if let Some(closed) = find_date(node, &["closed"]).filter(|closed| closed.time.is_some()) {
+            lines.push(closed.to_ical("COMPLETED"));
+        }
+    } else {
+        lines.push("STATUS:NEEDS-ACTION".to_string());
+    }
+}
+
+//one VTODO or VEVENT as (name, parameters, value) lines
+type Component = Vec<(String, String, String)>;
+
+fn parse(data: &str) -> Vec<(String, Component)> {
+    //unfold first: a line starting with a space or tab continues the previous one
+    let mut lines: Vec<String> = Vec::new();
+    for line in data.replace("\r\n", "\n").split('\n') {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    let mut components = Vec::new();
+    let mut current: Option<(String, Component)> = None;
+    let mut depth = 0; //VALARMs and the like inside a component are skipped
+    for line in lines {
+        let (name, value) = match line.split_once(':') {
+            Some((name, value)) => (name, value),
+            None => continue,
+        };
+        let (name, parameters) = name.split_once(';').unwrap_or((name, ""));
+        let name = name.to_uppercase();
+
+        match (name.as_str(), value.to_uppercase().as_str(), current.as_mut()) {
+            ("BEGIN", "VTODO" | "VEVENT", None) => current = Some((value.to_uppercase(), Vec::new())),
+            ("BEGIN", _, Some(_)) => depth += 1,
+            ("END", _, Some(_)) if depth > 0 => depth -= 1,
+            ("END", _, Some(_)) => components.extend(current.take()),
+            (_, _, Some((_, properties))) if depth == 0 => properties.push((name, parameters.to_string(), value.to_string())),
+            _ => {}
+        }
+    }
+    components
+}
+
+//the node an entry was exported from, or the one a foreign entry was imported into before
+fn find_existing(uid: &str) -> Option<i64> {
+    let own = uid.strip_prefix(UID_PREFIX).and_then(|rest| rest.strip_suffix(UID_DOMAIN)).and_then(|id| id.parse().ok());
+    if let Some(id) = own.filter(|id| find_node(*id).is_some()) {
+        return Some(id);
+    }
+    NODES.read().unwrap().iter()
+        .map(|node| node.read().unwrap())
+        .find(|node| node.get_property("uid").map_or(false, |existing| existing == uid))
+        .map(|node| node.get_id())
+}
+
+fn apply(node: &mut Node, kind: &str, properties: &Component) {
+    let get = |name: &str| properties.iter().find(|(property, _, _)| property == name).map(|(_, _, value)| value.as_str());
+    let date = |name: &str| get(name).and_then(When::parse_ical).map(When::to_property);
+
+    //nodes that came from Org keep their dates where Org looks for them
+    let name = |property: &'static str, org: &'static str| {
+        if node.get_property(property).is_none() && node.get_property(org).is_some() {
+            org
+        } else {
+            property
+        }
+    };
+    let (due, start) = (name("due", "deadline"), name("start", "scheduled"));
+
+    node.set_content(unescape_text(get("SUMMARY").unwrap_or_default()));
+    let mut dates = vec![
+        ("body", get("DESCRIPTION").map(unescape_text)),
+        ("location", get("LOCATION").map(unescape_text)),
+        (start, date("DTSTART")),
+        ("end", date("DTEND")),
+    ];
+    //the task keeps the due date and completion of todos
+    if kind != "VTODO" {
+        dates.extend([(due, date("DUE")), ("closed", date("COMPLETED"))]);
+    }
+    for (property, value) in dates {
+        match value.filter(|value| !value.is_empty()) {
+            Some(value) => node.set_property(property.to_string(), value),
+            None => node.remove_property(property),
+        }
+    }
+
+    if kind == "VTODO" {
+        let task = read_task(node.get_task().cloned().unwrap_or_else(Task::new), properties);
+        //open todos keep their own keyword (NEXT, WAITING...) for Org, the old todo properties go
+        if task.status.is_closed() || node.get_property("todo").map_or(false, |keyword| keyword == "DONE") {
+            node.remove_property("todo");
+        }
+        for property in ["due", "deadline", "closed", "priority"] {
+            node.remove_property(property);
+        }
+        node.set_task(Some(task));
+    }
+
+    for (name, _, value) in properties {
+        if name == "CATEGORIES" {
+            //commas inside a category are escaped
+            let value = value.replace("\\,", "\u{0}");
+            for tag in value.split(',').map(|tag| unescape_text(&tag.replace('\u{0}', ",")).trim().to_string()).filter(|tag| !tag.is_empty()) {
+                node.add_tag(tag);
+            }
+        }
+    }
+}
+
+//a VTODO over the task the node had (or a new one)
+fn read_task(mut task: Task, properties: &Component) -> Task {
+    let get = |name: &str| properties.iter().find(|(property, _, _)| property == name).map(|(_, _, value)| value.as_str());
+    let completed = get("COMPLETED").and_then(When::parse_ical).map(When::to_timestamp);
+
+    task.status = match get("STATUS").map(str::to_uppercase).as_deref() {
+        Some("COMPLETED") => TaskStatus::Done,
+        Some("IN-PROCESS") => TaskStatus::InProgress,
+        Some("CANCELLED") => TaskStatus::Cancelled,
+        _ if completed.is_some() => TaskStatus::Done,
+        _ => TaskStatus::Open,
+    };
+    task.completed = match task.status {
+        TaskStatus::Done => completed.or(task.completed).or(Some(now())),
+        _ => None,
+    };
+
+    //due dates are kept in UTC, floating times are taken as they are
+    let due = get("DUE").and_then(When::parse_ical).map(|due| When { utc: false, ..due }.to_property());
+    if task.set_due(due.as_deref()).is_err() {
+        task.set_due(None).unwrap();
+    }
+    //1-4 high, 5 medium, 6-9 low, 0 undefined
+    task.priority = match get("PRIORITY").and_then(|priority| priority.trim().parse::<u32>().ok()) {
+        Some(1..=4) => Some(TaskPriority::High),
+        Some(5) => Some(TaskPriority::Medium),
+        Some(6..=9) => Some(TaskPriority::Low),
+        _ => None,
+    };
+    task.recurrence = get("RRULE").and_then(parse_rule);
+    task
+}
+
+//returns (nodes created, 0). Entries that were exported from this workspace update their node
+pub fn import(path: &str, module: &ModuleRef, layout: Layout) -> Result<(usize, usize), String> {
+    let data = read_file(path)?;
+    if !data.trim_start_matches('\u{feff}').trim_start().to_uppercase().starts_with("BEGIN:VCALENDAR") {
+        return Err(format!("{} is not an iCalendar file", path));
+    }
+    let owner = module.read().unwrap().get_name();
+
+    let mut created = Vec::new();
+    for (kind, properties) in parse(&data) {
+        let uid = properties.iter().find(|(name, _, _)| name == "UID").map(|(_, _, value)| value.clone());
+
+        if let Some(node) = uid.as_deref().and_then(find_existing).and_then(find_node) {
+            apply(&mut node.write().unwrap(), &kind, &properties);
+            continue;
+        }
+
+        let mut node = Node::create_and_register(String::new(), owner.clone());
+        apply(&mut node, &kind, &properties);
+        if let Some(uid) = uid {
+            node.set_property("uid".to_string(), uid);
+        }
+        created.push(register_node(node));
+    }
+
+    place_nodes(module, &created, &[], layout);
+
+    Ok((created.len(), 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unfolding_and_components() {
+        let data = "BEGIN:VCALENDAR\r\nBEGIN:VTODO\r\nSUMMARY:a long\r\n  summary\r\nDUE;VALUE=DATE:20240501\r\nBEGIN:VALARM\r\nACTION:DISPLAY\r\nEND:VALARM\r\nEND:VTODO\r\nBEGIN:VEVENT\r\nsummary:event\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+        let components = parse(data);
+        assert_eq!(components.len(), 2);
+        let (kind, properties) = &components[0];
+        assert_eq!(kind, "VTODO");
+        assert_eq!(properties, &vec![
+            ("SUMMARY".to_string(), String::new(), "a long summary".to_string()),
+            ("DUE".to_string(), "VALUE=DATE".to_string(), "20240501".to_string()),
+        ]);
+        assert_eq!(components[1].1[0].0, "SUMMARY");
+    }
+
+    #[test]
+    fn folding_round_trip() {
+        let line = format!("DESCRIPTION:{}", "é".repeat(60));
+        let folded = fold(&line);
+        assert!(folded.split("\r\n").all(|part| part.len() <= 75));
+        assert_eq!(parse(&format!("BEGIN:VTODO\r\n{}END:VTODO\r\n", folded))[0].1[0].2, "é".repeat(60));
+    }
+
+    #[test]
+    fn dates() {
+        let when = When::parse_ical("20240501T103000Z").unwrap();
+        assert_eq!(when, When { date: (2024, 5, 1), time: Some((10, 30)), utc: true });
+        assert_eq!(when.to_property(), "2024-05-01 10:30Z");
+        assert_eq!(when.to_ical("DUE"), "DUE:20240501T103000Z");
+        assert_eq!(When::parse_ical("20240501").unwrap().to_ical("DUE"), "DUE;VALUE=DATE:20240501");
+        assert_eq!(When::parse_ical("2024"), None);
+
+        assert_eq!(When::parse_property("2024-05-01 Wed 10:00-11:00"), Some(When { date: (2024, 5, 1), time: Some((10, 0)), utc: false }));
+        assert_eq!(When::parse_property("2024-05-01T10:00:00Z").map(When::to_property).as_deref(), Some("2024-05-01 10:00Z"));
+        assert_eq!(When::parse_property("2024-05-01").map(When::to_timestamp), Some(1714521600));
+    }
+
+    #[test]
+    fn text_escapes() {
+        let text = "a, b; c\\d\nnext";
+        assert_eq!(escape_text(text), r"a\, b\; c\\d\nnext");
+        assert_eq!(unescape_text(&escape_text(text)), text);
+    }
+
+    #[test]
+    fn recurrence_rules() {
+        assert_eq!(parse_rule("FREQ=WEEKLY;INTERVAL=2;BYDAY=MO"), Some(Recurrence { every: 2, unit: RecurrenceUnit::Week }));
+        assert_eq!(parse_rule("freq=monthly"), Some(Recurrence { every: 1, unit: RecurrenceUnit::Month }));
+        assert_eq!(parse_rule("FREQ=HOURLY"), None);
+        assert_eq!(to_rule(Recurrence { every: 3, unit: RecurrenceUnit::Day }), "RRULE:FREQ=DAILY;INTERVAL=3");
+    }
+}
@@ -0,0 +1,308 @@
+use crate::attachments::Attachment;
+use crate::formats::{read_file, register_link, register_node, write_file};
+use crate::hierarchy;
+use crate::structs::link::{parse_color, Link, LinkDirection, LinkSide};
+use crate::structs::node::Node;
+use crate::types::ModuleRef;
+use crate::utils::union_rect;
+use crate::workspace;
+use crate::{LINKS, NODES};
+use serde::{Deserialize, Deserializer, Serialize};
+use speedy2d::shape::Rect;
+use std::collections::HashMap;
+
+//JSON Canvas (https://jsoncanvas.org), the .canvas files of Obsidian.
+//text nodes are plain nodes, file and link nodes are nodes with an attachment, groups become tags
+//on the nodes inside them. Positions and sizes go through the module's layout.
+//parents and children are edges too, with ids starting with CHILD_EDGE, which rmaps reads back as children.
+//link directions are the arrows at the ends of edges and link types their color, read back as the workspace's
+//link type with that color. Weights and styles have nowhere to go.
+
+const GROUP_PADDING: f32 = 40.0;
+const DEFAULT_GROUP_TAG: &str = "group";
+const CHILD_EDGE: &str = "child-";
+
+#[derive(Serialize, Deserialize, Default)]
+struct Canvas {
+    #[serde(default)]
+    nodes: Vec<CanvasNode>,
+    #[serde(default)]
+    edges: Vec<CanvasEdge>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CanvasNode {
+    id: String,
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(deserialize_with = "deserialize_coordinate")]
+    x: i64,
+    #[serde(deserialize_with = "deserialize_coordinate")]
+    y: i64,
+    #[serde(deserialize_with = "deserialize_coordinate")]
+    width: i64,
+    #[serde(deserialize_with = "deserialize_coordinate")]
+    height: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<String>, //text nodes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<String>, //file nodes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    subpath: Option<String>, //file nodes, starts with "#"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>, //link nodes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    label: Option<String>, //group nodes
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CanvasEdge {
+    id: String,
+    from_node: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    from_side: Option<LinkSide>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    from_end: Option<String>,
+    to_node: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    to_side: Option<LinkSide>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    to_end: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+}
+
+//the spec wants integers but some tools write floats
+fn deserialize_coordinate<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+    Ok(f64::deserialize(deserializer)?.round() as i64)
+}
+
+impl CanvasNode {
+    fn get_rect(&self) -> Rect {
+        Rect::from_tuples(
+            (self.x as f32, self.y as f32),
+            ((self.x + self.width) as f32, (self.y + self.height) as f32),
+        )
+    }
+
+    fn new(id: String, kind: &str, rect: &Rect) -> CanvasNode {
+        CanvasNode {
+            id,
+            kind: kind.to_string(),
+            x: rect.left().round() as i64,
+            y: rect.top().round() as i64,
+            width: rect.width().round() as i64,
+            height: rect.height().round() as i64,
+            color: None,
+            text: None,
+            file: None,
+            subpath: None,
+            url: None,
+            label: None,
+        }
+    }
+}
+
+//what a file or link node shows as its content: the file name (plus the subpath) or the url
+fn attachment_label(attachment: &str) -> String {
+    if attachment.contains("://") {
+        return attachment.to_string();
+    }
+    let (file, subpath) = match attachment.find('#') {
+        Some(index) => attachment.split_at(index),
+        None => (attachment, ""),
+    };
+    let name = file.rsplit('/').next().unwrap_or(file);
+    format!("{}{}", name, subpath)
+}
+
+//returns (nodes, links) created
+pub fn import(path: &str, module: &ModuleRef) -> Result<(usize, usize), String> {
+    let canvas: Canvas = serde_json::from_str(&read_file(path)?)
+        .map_err(|e| format!("{} is not a valid canvas: {}", path, e))?;
+    let owner = module.read().unwrap().get_name();
+
+    let groups: Vec<&CanvasNode> = canvas.nodes.iter().filter(|cnode| cnode.kind == "group").collect();
+
+    let mut ids = HashMap::new(); //canvas id -> node id
+    for cnode in canvas.nodes.iter().filter(|cnode| cnode.kind != "group") {
+        let attachment = match cnode.kind.as_str() {
+            "file" => cnode.file.as_ref().map(|file| format!("{}{}", file, cnode.subpath.as_deref().unwrap_or(""))),
+            "link" => cnode.url.clone(),
+            _ => None,
+        };
+        let content = match &attachment {
+            Some(attachment) => attachment_label(attachment),
+            None => cnode.text.clone().unwrap_or_default(),
+        };
+
+        let mut node = Node::create_and_register(content, owner.clone());
+        if let Some(attachment) = attachment {
+            node.add_attachment(Attachment::External(attachment));
+        }
+
+        //a node belongs to every group its center falls into
+        let rect = cnode.get_rect();
+        let center = (*rect.top_left() + *rect.bottom_right()) * 0.5;
+        for group in &groups {
+            if group.get_rect().contains(center) {
+                node.add_tag(group.label.clone().filter(|label| !label.is_empty()).unwrap_or(DEFAULT_GROUP_TAG.to_string()));
+            }
+        }
+
+        ids.insert(cnode.id.clone(), node.get_id());
+        let node = register_node(node);
+        module.write().unwrap().place_node(
+            node,
+            (cnode.x as f32, cnode.y as f32),
+            Some((cnode.width as f32, cnode.height as f32)),
+        );
+    }
+
+    let mut links = 0;
+    for edge in &canvas.edges {
+        //edges to groups have nothing to point to
+        if let (Some(from), Some(to)) = (ids.get(&edge.from_node), ids.get(&edge.to_node)) {
+            //a child with a parent already (or one that would go in a circle) gets a link instead
+            if edge.id.starts_with(CHILD_EDGE) && hierarchy::get_parent(*to).is_none() && hierarchy::set_parent(*to, Some(*from), None).is_ok() {
+                continue;
+            }
+            //edges have an arrow at their "to" end and none at their "from" end unless they say otherwise.
+            //one with its only arrow at the "from" end is a link the other way round
+            let arrow = |end: &Option<String>, default: bool| end.as_ref().map_or(default, |end| end == "arrow");
+            let (direction, from, to, sides) = match (arrow(&edge.from_end, false), arrow(&edge.to_end, true)) {
+                (false, false) => (LinkDirection::Undirected, from, to, (edge.from_side, edge.to_side)),
+                (true, true) => (LinkDirection::Bidirectional, from, to, (edge.from_side, edge.to_side)),
+                (true, false) => (LinkDirection::Directed, to, from, (edge.to_side, edge.from_side)),
+                (false, true) => (LinkDirection::Directed, from, to, (edge.from_side, edge.to_side)),
+            };
+            let mut link = Link::create_and_register(*from, *to, owner.clone());
+            link.set_label(edge.label.clone().unwrap_or_default());
+            link.set_sides(sides);
+            link.set_direction(direction);
+            if let Some(color) = edge.color.as_deref().and_then(parse_color) {
+                let link_type = workspace::get_settings().link_types.into_iter().find(|link_type| link_type.get_rgb() == Some(color));
+                if let Some(link_type) = link_type {
+                    link.set_relation(link_type.name);
+                }
+            }
+            register_link(link);
+            links += 1;
+        }
+    }
+
+    Ok((ids.len(), links))
+}
+
+//exports every node placed in the module. groups are tags to draw as groups around the nodes carrying them
+pub fn export(path: &str, module: &ModuleRef, groups: &[String]) -> Result<(usize, usize), String> {
+    let bounds = module.read().unwrap().get_node_bounds();
+    let mut canvas = Canvas::default();
+
+    for tag in groups {
+        //same matching as everywhere else: any case, #hashtags in the text included
+        let members: Vec<Rect> = workspace::find_tagged(tag).iter()
+            .filter_map(|id| bounds.get(id).cloned())
+            .collect();
+
+        if let Some(rect) = members.into_iter().reduce(union_rect) {
+            let rect = Rect::from_tuples(
+                (rect.left() - GROUP_PADDING, rect.top() - GROUP_PADDING),
+                (rect.right() + GROUP_PADDING, rect.bottom() + GROUP_PADDING),
+            );
+            //groups go first so that canvas apps draw them below the nodes
+            let mut group = CanvasNode::new(format!("group-{}", tag), "group", &rect);
+            group.label = Some(tag.clone());
+            canvas.nodes.push(group);
+        }
+    }
+
+    let mut exported = 0;
+    for node in NODES.read().unwrap().iter() {
+        let node = node.read().unwrap();
+        let rect = match bounds.get(&node.get_id()) {
+            Some(rect) => rect,
+            None => continue,
+        };
+
+        let mut cnode = CanvasNode::new(node.get_id().to_string(), "text", rect);
+
+        //nodes that only show their attachment go back to being file/link nodes, the others keep their text
+        //(only external ones, files in the workspace's store mean nothing to other apps)
+        match node.get_attachments().first() {
+            Some(Attachment::External(attachment)) if attachment_label(attachment) == *node.get_content() => {
+                if attachment.contains("://") {
+                    cnode.kind = "link".to_string();
+                    cnode.url = Some(attachment.clone());
+                } else {
+                    cnode.kind = "file".to_string();
+                    match attachment.find('#') {
+                        Some(index) => {
+                            cnode.file = Some(attachment[..index].to_string());
+                            cnode.subpath = Some(attachment[index..].to_string());
+                        }
+                        None => cnode.file = Some(attachment.clone()),
+                    }
+                }
+            }
+            _ => cnode.text = Some(node.get_content().clone()),
+        }
+
+        canvas.nodes.push(cnode);
+        exported += 1;
+    }
+
+    //in tree order, so that children come back in the same order
+    for id in hierarchy::get_tree_order() {
+        let parent = match hierarchy::get_parent(id) {
+            Some(parent) if bounds.contains_key(&parent) && bounds.contains_key(&id) => parent,
+            _ => continue,
+        };
+        canvas.edges.push(CanvasEdge {
+            id: format!("{}{}", CHILD_EDGE, id),
+            from_node: parent.to_string(),
+            from_side: None,
+            from_end: None,
+            to_node: id.to_string(),
+            to_side: None,
+            to_end: None,
+            color: None,
+            label: None,
+        });
+    }
+
+    let children = canvas.edges.len();
+    for link in LINKS.read().unwrap().iter() {
+        let link = link.read().unwrap();
+        if !bounds.contains_key(&link.get_from_id()) || !bounds.contains_key(&link.get_to_id()) {
+            continue;
+        }
+        let (from_side, to_side) = link.get_sides();
+        let (from_end, to_end) = match link.get_direction() {
+            LinkDirection::Directed => (None, None),
+            LinkDirection::Bidirectional => (Some("arrow".to_string()), None),
+            LinkDirection::Undirected => (None, Some("none".to_string())),
+        };
+        canvas.edges.push(CanvasEdge {
+            id: format!("link-{}", link.get_id()),
+            from_node: link.get_from_id().to_string(),
+            from_side,
+            from_end,
+            to_node: link.get_to_id().to_string(),
+            to_side,
+            to_end,
+            color: workspace::get_link_type(link.get_relation()).map(|link_type| link_type.color),
+            label: Some(link.get_label().clone()).filter(|label| !label.is_empty()),
+        });
+    }
+
+    let serialized = serde_json::to_string_pretty(&canvas).map_err(|e| e.to_string())?;
+    write_file(path, &serialized)?;
+
+    Ok((exported, canvas.edges.len() - children))
+}
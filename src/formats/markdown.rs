@@ -0,0 +1,315 @@
+use crate::formats::{place_nodes, read_file, register_link, register_node, Layout};
+use crate::structs::link::Link;
+use crate::structs::node::Node;
+use crate::utils::{find_hashtags, percent_decode};
+use crate::types::ModuleRef;
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Component, Path, PathBuf};
+
+//a folder of markdown notes, like an Obsidian vault. One node per note (title and body),
+//[[wikilinks]] and [markdown](links.md) between notes become links, frontmatter becomes properties
+//and tags (frontmatter "tags" plus inline #hashtags) become node tags.
+
+struct Note {
+    path: PathBuf, //relative to the vault, without the extension
+    title: String,
+    body: String,
+    frontmatter: Vec<(String, Vec<String>)>,
+    tags: BTreeSet<String>,
+}
+
+//returns (nodes, links) created
+pub fn import(folder: &str, module: &ModuleRef, layout: Layout) -> Result<(usize, usize), String> {
+    let root = Path::new(folder);
+    let mut files = Vec::new();
+    collect_markdown_files(root, &mut files)?;
+    files.sort();
+
+    let mut notes = Vec::new();
+    for file in &files {
+        let data = read_file(&file.to_string_lossy())?;
+        let path = file.strip_prefix(root).unwrap_or(file).with_extension("");
+        notes.push(parse_note(path, &data));
+    }
+
+    //notes can be referred to by path, by file name or by one of their aliases
+    let mut names: HashMap<String, usize> = HashMap::new();
+    for (index, note) in notes.iter().enumerate() {
+        names.insert(normalize_name(&note.path.to_string_lossy()), index);
+    }
+    for (index, note) in notes.iter().enumerate() {
+        let stem = note.path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        names.entry(normalize_name(&stem)).or_insert(index);
+        for (key, values) in &note.frontmatter {
+            if key == "aliases" || key == "alias" {
+                for alias in values {
+                    names.entry(normalize_name(alias)).or_insert(index);
+                }
+            }
+        }
+    }
+
+    let owner = module.read().unwrap().get_name();
+    let mut nodes = Vec::new();
+    for note in &notes {
+        let content = if note.body.is_empty() {
+            note.title.clone()
+        } else {
+            format!("{}\n{}", note.title, note.body)
+        };
+        let mut node = Node::create_and_register(content, owner.clone());
+        for tag in &note.tags {
+            node.add_tag(tag.clone());
+        }
+        for (key, values) in &note.frontmatter {
+            if key != "tags" && key != "tag" {
+                node.set_property(key.clone(), values.join(", "));
+            }
+        }
+        node.set_property("source".to_string(), note.path.with_extension("md").to_string_lossy().to_string());
+        nodes.push(register_node(node));
+    }
+
+    let mut edges = Vec::new();
+    for (index, note) in notes.iter().enumerate() {
+        for (target, label) in find_references(&note.body) {
+            let resolved = if target.contains("://") || target.starts_with("mailto:") {
+                None
+            } else if let Some(relative) = target.strip_suffix(".md") {
+                //markdown links are relative to the note (or to the vault with a leading "/"), wikilinks are just names
+                let base = if relative.starts_with('/') {
+                    Path::new("")
+                } else {
+                    note.path.parent().unwrap_or(Path::new(""))
+                };
+                let path = normalize_path(&base.join(relative.trim_start_matches('/')));
+                names.get(&normalize_name(&path)).or(names.get(&normalize_name(relative)))
+            } else {
+                names.get(&normalize_name(&target))
+            };
+
+            if let Some(&target) = resolved {
+                if target == index || edges.contains(&(index, target)) {
+                    continue;
+                }
+                let mut link = Link::create_and_register(
+                    nodes[index].read().unwrap().get_id(),
+                    nodes[target].read().unwrap().get_id(),
+                    owner.clone(),
+                );
+                link.set_label(label.unwrap_or_default());
+                register_link(link);
+                edges.push((index, target));
+            }
+        }
+    }
+
+    place_nodes(module, &nodes, &edges, layout);
+
+    Ok((nodes.len(), edges.len()))
+}
+
+fn collect_markdown_files(folder: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = std::fs::read_dir(folder).map_err(|e| format!("can't read {}: {}", folder.display(), e))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let hidden = entry.file_name().to_string_lossy().starts_with('.'); //.obsidian, .git, .trash...
+        if hidden {
+            continue;
+        }
+        if path.is_dir() {
+            collect_markdown_files(&path, files)?;
+        } else if path.extension().map_or(false, |extension| extension.eq_ignore_ascii_case("md")) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn normalize_name(name: &str) -> String {
+    name.trim().replace('\\', "/").to_lowercase()
+}
+
+//resolves "." and ".." without touching the file system
+fn normalize_path(path: &Path) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                parts.pop();
+            }
+            Component::Normal(part) => parts.push(part.to_string_lossy().to_string()),
+            _ => {}
+        }
+    }
+    parts.join("/")
+}
+
+fn parse_note(path: PathBuf, data: &str) -> Note {
+    let data = data.replace("\r\n", "\n");
+    let (frontmatter, body) = split_frontmatter(&data);
+
+    //the title is the frontmatter title, or a leading "# heading", or the file name
+    let mut body = body.trim_start();
+    let mut title = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    if let Some(heading) = body.lines().next().and_then(|line| line.strip_prefix("# ")) {
+        title = heading.trim().to_string();
+        body = &body[body.find('\n').unwrap_or(body.len())..];
+    }
+
+    let mut tags = BTreeSet::new();
+    for (key, values) in &frontmatter {
+        match key.as_str() {
+            "tags" | "tag" => values.iter().for_each(|tag| {
+                tags.insert(tag.trim_start_matches('#').to_string());
+            }),
+            "title" => title = values.join(" "),
+            _ => {}
+        }
+    }
+    tags.extend(find_hashtags(body));
+
+    Note {
+        path,
+        title,
+        body: body.trim().to_string(),
+        frontmatter,
+        tags,
+    }
+}
+
+//splits the yaml frontmatter (only "key: value", "key: [a, b]" and "- item" lists) from the body
+fn split_frontmatter(data: &str) -> (Vec<(String, Vec<String>)>, &str) {
+    let mut frontmatter: Vec<(String, Vec<String>)> = Vec::new();
+
+    let rest = match data.strip_prefix("---\n") {
+        Some(rest) => rest,
+        None => return (frontmatter, data),
+    };
+    let end = match rest.find("\n---") {
+        Some(end) => end,
+        None => return (frontmatter, data),
+    };
+    let body = rest[end + 4..].trim_start_matches(|c| c != '\n').trim_start_matches('\n');
+
+    for line in rest[..end].lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if let Some(item) = trimmed.strip_prefix("- ") {
+            if let Some((_, values)) = frontmatter.last_mut() {
+                values.push(unquote(item));
+            }
+        } else if let Some((key, value)) = trimmed.split_once(':') {
+            let value = value.trim();
+            let values = if value.is_empty() {
+                Vec::new()
+            } else if value.starts_with('[') && value.ends_with(']') {
+                value[1..value.len() - 1].split(',').map(unquote).filter(|item| !item.is_empty()).collect()
+            } else {
+                vec![unquote(value)]
+            };
+            frontmatter.push((key.trim().to_string(), values));
+        }
+    }
+
+    (frontmatter, body)
+}
+
+fn unquote(value: &str) -> String {
+    value.trim().trim_matches('"').trim_matches('\'').to_string()
+}
+
+//every [[wikilink]] and [markdown](link) in the body as (target, label). Targets lose their #heading
+fn find_references(body: &str) -> Vec<(String, Option<String>)> {
+    let mut references = Vec::new();
+
+    let mut rest = body;
+    while let Some(start) = rest.find("[[") {
+        rest = &rest[start + 2..];
+        let end = match rest.find("]]") {
+            Some(end) => end,
+            None => break,
+        };
+        let inner = &rest[..end];
+        let (target, alias) = match inner.split_once('|') {
+            Some((target, alias)) => (target, Some(alias.trim().to_string())),
+            None => (inner, None),
+        };
+        let target = target.split('#').next().unwrap_or_default().trim();
+        if !target.is_empty() {
+            references.push((target.to_string(), alias));
+        }
+        rest = &rest[end + 2..];
+    }
+
+    let mut rest = body;
+    while let Some(start) = rest.find("](") {
+        let text = rest[..start].rsplit('[').next().unwrap_or_default();
+        rest = &rest[start + 2..];
+        let end = match rest.find(')') {
+            Some(end) => end,
+            None => break,
+        };
+        let target = percent_decode(rest[..end].split('#').next().unwrap_or_default().trim().trim_matches(|c| c == '<' || c == '>'));
+        if !target.is_empty() {
+            let label = Some(text.trim().to_string()).filter(|text| !text.is_empty() && !target.ends_with(&format!("{}.md", text)));
+            references.push((target, label));
+        }
+        rest = &rest[end + 1..];
+    }
+
+    references
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frontmatter() {
+        let data = "---\ntitle: \"Graphs\"\ntags: [math, 'theory']\n# a comment\naliases:\n  - graph theory\n  - networks\nempty:\n---\nbody text\n";
+        let (frontmatter, body) = split_frontmatter(data);
+        assert_eq!(frontmatter, vec![
+            ("title".to_string(), vec!["Graphs".to_string()]),
+            ("tags".to_string(), vec!["math".to_string(), "theory".to_string()]),
+            ("aliases".to_string(), vec!["graph theory".to_string(), "networks".to_string()]),
+            ("empty".to_string(), Vec::new()),
+        ]);
+        assert_eq!(body, "body text\n");
+
+        //no closing line: it's all body
+        let (frontmatter, body) = split_frontmatter("---\ntitle: x\nno end");
+        assert!(frontmatter.is_empty());
+        assert_eq!(body, "---\ntitle: x\nno end");
+    }
+
+    #[test]
+    fn notes_take_their_title_and_tags() {
+        let note = parse_note(PathBuf::from("folder/file"), "---\r\ntags:\r\n  - '#reading'\r\n---\r\n# Heading\r\nsome #idea here\r\n");
+        assert_eq!(note.title, "Heading");
+        assert_eq!(note.body, "some #idea here");
+        assert_eq!(note.tags, BTreeSet::from(["idea".to_string(), "reading".to_string()]));
+
+        let note = parse_note(PathBuf::from("folder/file"), "no heading");
+        assert_eq!(note.title, "file");
+    }
+
+    #[test]
+    fn references() {
+        let body = "see [[Other Note#Section|the other one]], [[plain]] and [text](sub/Some%20Note.md#part), [Some Note](<Some Note.md>), [[]] [[open";
+        assert_eq!(find_references(body), vec![
+            ("Other Note".to_string(), Some("the other one".to_string())),
+            ("plain".to_string(), None),
+            ("sub/Some Note.md".to_string(), Some("text".to_string())),
+            ("Some Note.md".to_string(), None),
+        ]);
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(normalize_path(Path::new("a/./b/../c")), "a/c");
+        assert_eq!(normalize_name(" Folder\\Note "), "folder/note");
+    }
+}
@@ -0,0 +1,98 @@
+use crate::formats::find_node;
+use crate::hierarchy;
+use crate::structs::link::{LinkDirection, LinkStyle};
+use crate::workspace;
+use crate::LINKS;
+use std::collections::HashSet;
+
+//mermaid diagrams (https://mermaid.js.org), export only: a mindmap of the tree below a node,
+//or a flowchart of some nodes with the links between them (and thick arrows from parents to children).
+//links keep their direction and style (mermaid has no dashes, those are dotted too), links with a type get
+//its color through linkStyle and show its name when they have no label.
+
+//node text always goes inside quotes, so quotes, "#" (which starts an entity) and angle brackets
+//become entities and newlines become <br>
+fn escape(text: &str) -> String {
+    text.replace('#', "#35;")
+        .replace('"', "#quot;")
+        .replace('<', "#lt;")
+        .replace('>', "#gt;")
+        .replace("\r\n", "\n")
+        .replace('\n', "<br>")
+}
+
+fn node_text(id: i64) -> String {
+    find_node(id)
+        .map(|node| escape(node.read().unwrap().get_content()))
+        .unwrap_or_default()
+}
+
+//the diagram, how many nodes and how many edges (parent to child) it has. None if there is no such node
+pub fn mindmap(root: i64) -> Option<(String, usize, usize)> {
+    find_node(root)?;
+
+    let mut diagram = String::from("mindmap\n");
+    let mut visited = HashSet::new();
+    write_mindmap_node(&mut diagram, root, 1, &mut visited);
+    Some((diagram, visited.len(), visited.len() - 1))
+}
+
+fn write_mindmap_node(diagram: &mut String, id: i64, depth: usize, visited: &mut HashSet<i64>) {
+    if !visited.insert(id) {
+        return;
+    }
+
+    diagram.push_str(&format!("{}n{}[\"{}\"]\n", "  ".repeat(depth), id, node_text(id)));
+    for child in hierarchy::get_children(id) {
+        write_mindmap_node(diagram, child, depth + 1, visited);
+    }
+}
+
+//the diagram, how many nodes and how many edges (hierarchy and links) it has
+pub fn flowchart(ids: &[i64]) -> (String, usize, usize) {
+    let mut diagram = String::from("flowchart LR\n");
+
+    for id in ids {
+        diagram.push_str(&format!("    n{}[\"{}\"]\n", id, node_text(*id)));
+    }
+
+    //linkStyle goes by the position of the edge among all of them
+    let mut edges = 0;
+    for id in ids {
+        if let Some(parent) = hierarchy::get_parent(*id).filter(|parent| ids.contains(parent)) {
+            diagram.push_str(&format!("    n{} ==> n{}\n", parent, id));
+            edges += 1;
+        }
+    }
+
+    let mut styles = Vec::new();
+    for link in LINKS.read().unwrap().iter() {
+        let link = link.read().unwrap();
+        if !ids.contains(&link.get_from_id()) || !ids.contains(&link.get_to_id()) {
+            continue;
+        }
+        let link_type = workspace::get_link_type(link.get_relation());
+        let dotted = link.get_style().or(link_type.as_ref().map(|link_type| link_type.style)).unwrap_or_default() != LinkStyle::Solid;
+        let arrow = match (link.get_direction(), dotted) {
+            (LinkDirection::Directed, false) => "-->",
+            (LinkDirection::Bidirectional, false) => "<-->",
+            (LinkDirection::Undirected, false) => "---",
+            (LinkDirection::Directed, true) => "-.->",
+            (LinkDirection::Bidirectional, true) => "<-.->",
+            (LinkDirection::Undirected, true) => "-.-",
+        };
+        let label = if link.get_label().is_empty() { link.get_relation() } else { link.get_label() };
+        if label.is_empty() {
+            diagram.push_str(&format!("    n{} {} n{}\n", link.get_from_id(), arrow, link.get_to_id()));
+        } else {
+            diagram.push_str(&format!("    n{} {}|\"{}\"| n{}\n", link.get_from_id(), arrow, escape(label), link.get_to_id()));
+        }
+        if let Some(link_type) = link_type {
+            styles.push(format!("    linkStyle {} stroke:{},color:{}\n", edges, link_type.color, link_type.color));
+        }
+        edges += 1;
+    }
+    styles.iter().for_each(|style| diagram.push_str(style));
+
+    (diagram, ids.len(), edges)
+}
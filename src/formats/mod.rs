@@ -0,0 +1,97 @@
+pub mod anki;
+pub mod bibtex;
+pub mod csv;
+pub mod epub;
+pub mod graphml;
+pub mod html;
+pub mod ical;
+pub mod json_canvas;
+pub mod markdown;
+pub mod mermaid;
+pub mod org;
+pub mod pdf;
+#[cfg(test)]
+pub mod test_support;
+
+use crate::layout;
+use crate::structs::link::Link;
+use crate::structs::node::Node;
+use crate::types::ModuleRef;
+use crate::workspace;
+use crate::{LINKS, NODES};
+use std::sync::{Arc, RwLock};
+
+//import/export of other applications' file formats. Importers create nodes and links through the helpers
+//below and place them in a module, exporters read NODES and LINKS plus the module's layout.
+
+pub fn register_node(node: Node) -> Arc<RwLock<Node>> {
+    workspace::index_node(&node);
+    let node = Arc::new(RwLock::new(node));
+    NODES.write().unwrap().push(node.clone());
+    node
+}
+
+pub fn register_link(link: Link) -> Arc<RwLock<Link>> {
+    let link = Arc::new(RwLock::new(link));
+    LINKS.write().unwrap().push(link.clone());
+    link
+}
+
+pub fn find_node(id: i64) -> Option<Arc<RwLock<Node>>> {
+    NODES.read().unwrap().iter().find(|node| node.read().unwrap().get_id() == id).cloned()
+}
+
+pub fn find_link(id: i64) -> Option<Arc<RwLock<Link>>> {
+    LINKS.read().unwrap().iter().find(|link| link.read().unwrap().get_id() == id).cloned()
+}
+
+pub fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+pub fn read_file(path: &str) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|e| format!("can't read {}: {}", path, e))
+}
+
+pub fn write_file(path: &str, contents: &str) -> Result<(), String> {
+    std::fs::write(path, contents).map_err(|e| format!("can't write {}: {}", path, e))
+}
+
+pub(crate) const LAYOUT_SPACING: f32 = 700.0;
+
+//how to place imported nodes that come without a position
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Layout {
+    None, //don't place them at all
+    Grid,
+    Force,
+}
+
+impl Layout {
+    pub fn parse(name: &str) -> Result<Layout, String> {
+        match name {
+            "none" => Ok(Layout::None),
+            "grid" => Ok(Layout::Grid),
+            "force" => Ok(Layout::Force),
+            name => Err(format!("unknown layout \"{}\" (none, grid, force)", name)),
+        }
+    }
+}
+
+//edges are pairs of indices into nodes, they only matter for the force layout
+pub fn place_nodes(module: &ModuleRef, nodes: &[Arc<RwLock<Node>>], edges: &[(usize, usize)], layout: Layout) {
+    let positions = match layout {
+        Layout::None => return,
+        Layout::Grid => layout::grid(nodes.len(), LAYOUT_SPACING),
+        Layout::Force => layout::force_directed(nodes.len(), edges, LAYOUT_SPACING),
+    };
+
+    let mut module = module.write().unwrap();
+    for (node, position) in nodes.iter().zip(positions) {
+        module.place_node(node.clone(), position, None);
+    }
+}
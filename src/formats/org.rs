@@ -0,0 +1,532 @@
+use crate::formats::{find_node, place_nodes, read_file, register_node, write_file, Layout};
+use crate::hierarchy;
+use crate::structs::node::Node;
+use crate::structs::property::{Property, PropertyKind};
+use crate::structs::task::{Recurrence, Task, TaskPriority, TaskStatus};
+use crate::types::ModuleRef;
+use crate::utils::format_timestamp;
+use std::collections::HashSet;
+
+//Emacs Org-mode outlines. Every headline is a node, a child of its parent headline in the hierarchy (a #+TITLE
+//becomes the root above the top level ones). Headlines with a TODO keyword become tasks: the keyword gives the
+//status, [#A]/[#B]/[#C] the priority, DEADLINE the due date (its +1w repeater the recurrence) and CLOSED when it
+//was done. The scheduled timestamp, the property drawer and the body text become node properties, headline tags
+//become node tags. Exporting writes the tree below a node back as headlines.
+
+const DEFAULT_KEYWORDS: [&str; 2] = ["TODO", "DONE"];
+//properties that are part of the headline itself and don't go into the drawer on export
+const HEADLINE_PROPERTIES: [&str; 6] = ["todo", "priority", "scheduled", "deadline", "closed", "body"];
+const PLANNING: [(&str, &str); 3] = [("SCHEDULED:", "scheduled"), ("DEADLINE:", "deadline"), ("CLOSED:", "closed")];
+
+struct Headline {
+    level: usize,
+    title: String,
+    keyword: Option<String>,
+    done: bool, //the keyword is one of the done ones, after the "|" in #+TODO
+    priority: Option<char>,
+    tags: Vec<String>,
+    properties: Vec<(String, String)>,
+    body: Vec<String>,
+}
+
+//"TODO [#A] title :tag:tag:", what follows the stars
+fn parse_headline(level: usize, text: &str, keywords: &[String], done_keywords: &[String]) -> Headline {
+    let mut text = text.trim();
+
+    let mut keyword = None;
+    let (first, rest) = text.split_once(' ').unwrap_or((text, ""));
+    if keywords.iter().any(|known| known == first) {
+        keyword = Some(first.to_string());
+        text = rest.trim_start();
+    }
+
+    let mut priority = None;
+    if let Some(rest) = text.strip_prefix("[#") {
+        let mut characters = rest.chars();
+        if let (Some(letter), Some(']')) = (characters.next(), characters.next()) {
+            priority = Some(letter);
+            text = characters.as_str().trim_start();
+        }
+    }
+
+    let mut tags = Vec::new();
+    if let Some(last) = text.rsplit(|c: char| c.is_whitespace()).next() {
+        if last.len() > 2 && last.starts_with(':') && last.ends_with(':') && last != text {
+            tags = last.trim_matches(':').split(':').filter(|tag| !tag.is_empty()).map(String::from).collect();
+            text = text[..text.len() - last.len()].trim_end();
+        }
+    }
+
+    Headline {
+        level,
+        title: text.to_string(),
+        done: keyword.as_ref().map_or(false, |keyword| done_keywords.contains(keyword)),
+        keyword,
+        priority,
+        tags,
+        properties: Vec::new(),
+        body: Vec::new(),
+    }
+}
+
+//"#+TODO: TODO NEXT(n) | DONE(d) CANCELLED" -> (TODO, NEXT), (DONE, CANCELLED). Without a "|" the last one is done
+fn parse_keywords(line: &str) -> (Vec<String>, Vec<String>) {
+    let words = |part: &str| -> Vec<String> {
+        part.split_whitespace().map(|word| word.split('(').next().unwrap_or(word).to_string()).collect()
+    };
+    match line.split_once('|') {
+        Some((open, done)) => (words(open), words(done)),
+        None => {
+            let mut open = words(line);
+            let done = open.pop().into_iter().collect();
+            (open, done)
+        }
+    }
+}
+
+//what a keyword means for the task: the done ones are done (or cancelled), a few open ones say it's started
+fn get_status(keyword: &str, done: bool) -> TaskStatus {
+    match (keyword.to_uppercase().as_str(), done) {
+        ("CANCELLED" | "CANCELED", _) => TaskStatus::Cancelled,
+        (_, true) => TaskStatus::Done,
+        ("STARTED" | "DOING" | "IN-PROGRESS" | "INPROGRESS", _) => TaskStatus::InProgress,
+        _ => TaskStatus::Open,
+    }
+}
+
+//the keyword written for a status, unless the node remembers its own (NEXT, WAITING...) in the "todo" property
+fn get_keyword(status: TaskStatus) -> &'static str {
+    match status {
+        TaskStatus::Open => "TODO",
+        TaskStatus::InProgress => "IN-PROGRESS",
+        TaskStatus::Done => "DONE",
+        TaskStatus::Cancelled => "CANCELLED",
+    }
+}
+
+//"2024-05-01 Wed 10:00-11:00 +1w" -> ("2024-05-01 10:00", weekly). Habits' ".+1w" and "++1w" repeat like "+1w"
+fn parse_timestamp(timestamp: &str) -> (Option<String>, Option<Recurrence>) {
+    let mut words = timestamp.split_whitespace();
+    let date = match words.next() {
+        Some(date) => date,
+        None => return (None, None),
+    };
+    let mut due = date.to_string();
+    let mut recurrence = None;
+    for word in words {
+        if let Some(repeater) = word.strip_prefix(".+").or_else(|| word.strip_prefix("++")).or_else(|| word.strip_prefix('+')) {
+            recurrence = Recurrence::parse(&format!("+{}", repeater)).ok();
+        } else if word.contains(':') {
+            due = format!("{} {}", date, word.split('-').next().unwrap_or(word));
+        }
+    }
+    (Some(due), recurrence)
+}
+
+//the task of a headline with a keyword. Deadline and closed timestamps that make sense move into it
+fn read_task(keyword: &str, done: bool, priority: Option<char>, properties: &mut Vec<(String, String)>) -> Task {
+    let mut task = Task::new();
+    task.status = get_status(keyword, done);
+    task.priority = match priority {
+        Some('A') => Some(TaskPriority::High),
+        Some('B') => Some(TaskPriority::Medium),
+        Some('C') => Some(TaskPriority::Low),
+        _ => None,
+    };
+
+    properties.retain(|(key, value)| match key.as_str() {
+        "deadline" => {
+            let (due, recurrence) = parse_timestamp(value);
+            task.recurrence = recurrence;
+            task.set_due(due.as_deref()).is_err()
+        }
+        "closed" if task.status.is_closed() => {
+            let (closed, _) = parse_timestamp(value);
+            task.completed = closed.and_then(|closed| Property::parse(&PropertyKind::Date, &closed).ok()?.as_date());
+            task.completed.is_none()
+        }
+        _ => true,
+    });
+    task
+}
+
+fn parse(data: &str) -> (Option<String>, Vec<Headline>) {
+    let mut keywords: Vec<String> = DEFAULT_KEYWORDS.iter().map(|keyword| keyword.to_string()).collect();
+    let mut done_keywords = vec![DEFAULT_KEYWORDS[1].to_string()];
+    let mut title = None;
+    let mut headlines: Vec<Headline> = Vec::new();
+    let mut in_drawer = false;
+
+    for line in data.lines() {
+        let stars = line.chars().take_while(|c| *c == '*').count();
+        if stars > 0 && line[stars..].starts_with(' ') {
+            headlines.push(parse_headline(stars, &line[stars..], &keywords, &done_keywords));
+            in_drawer = false;
+            continue;
+        }
+
+        let trimmed = line.trim();
+        let headline = match headlines.last_mut() {
+            Some(headline) => headline,
+            None => {
+                //settings before the first headline
+                if let Some(value) = strip_setting(trimmed, "#+TITLE:") {
+                    title = Some(value.trim().to_string());
+                }
+                for setting in ["#+TODO:", "#+SEQ_TODO:", "#+TYP_TODO:"] {
+                    if let Some(value) = strip_setting(trimmed, setting) {
+                        let (open, done) = parse_keywords(value);
+                        keywords.extend(open.into_iter().chain(done.iter().cloned()));
+                        done_keywords.extend(done);
+                    }
+                }
+                continue;
+            }
+        };
+
+        if in_drawer {
+            if trimmed.eq_ignore_ascii_case(":END:") {
+                in_drawer = false;
+            } else if let Some((key, value)) = trimmed.strip_prefix(':').and_then(|rest| rest.split_once(':')) {
+                headline.properties.push((key.to_string(), value.trim().to_string()));
+            }
+            continue;
+        }
+
+        //the planning line and the property drawer come right after the headline
+        if headline.body.iter().all(|line| line.trim().is_empty()) {
+            if trimmed.eq_ignore_ascii_case(":PROPERTIES:") {
+                in_drawer = true;
+                continue;
+            }
+            if PLANNING.iter().any(|(keyword, _)| trimmed.starts_with(keyword)) {
+                for (keyword, property) in PLANNING {
+                    if let Some(timestamp) = find_timestamp(trimmed, keyword) {
+                        headline.properties.push((property.to_string(), timestamp));
+                    }
+                }
+                continue;
+            }
+        }
+
+        headline.body.push(line.to_string());
+    }
+
+    (title, headlines)
+}
+
+//settings are case insensitive, "#+title:" works as well
+fn strip_setting<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    match line.get(..name.len()) {
+        Some(prefix) if prefix.eq_ignore_ascii_case(name) => Some(&line[name.len()..]),
+        _ => None,
+    }
+}
+
+//the timestamp after "SCHEDULED:" and the like, without its brackets
+fn find_timestamp(line: &str, keyword: &str) -> Option<String> {
+    let rest = line[line.find(keyword)? + keyword.len()..].trim_start();
+    let close = match rest.chars().next()? {
+        '<' => '>',
+        '[' => ']',
+        _ => return None,
+    };
+    let end = rest.find(close)?;
+    //ranges like <2024-05-01>--<2024-05-03> stay whole
+    let end = match rest[end + 1..].strip_prefix("--") {
+        Some(range) if range.starts_with(['<', '[']) => end + 3 + range.find(close)?,
+        _ => end,
+    };
+    Some(rest[1..end].to_string())
+}
+
+//returns (nodes, links) created, headlines only make children so there are no links
+pub fn import(path: &str, module: &ModuleRef, layout: Layout) -> Result<(usize, usize), String> {
+    let data = read_file(path)?.replace("\r\n", "\n");
+    let (title, headlines) = parse(&data);
+    if headlines.is_empty() {
+        return Err(format!("{} has no headlines", path));
+    }
+
+    let owner = module.read().unwrap().get_name();
+    let mut nodes = Vec::new();
+    let mut edges = Vec::new();
+
+    //(level, index into nodes) of the headlines that can still get children
+    let mut parents: Vec<(usize, usize)> = Vec::new();
+    if let Some(title) = title {
+        nodes.push(register_node(Node::create_and_register(title, owner.clone())));
+        parents.push((0, 0));
+    }
+
+    for mut headline in headlines {
+        let mut node = Node::create_and_register(headline.title, owner.clone());
+        headline.tags.into_iter().for_each(|tag| node.add_tag(tag));
+        match &headline.keyword {
+            Some(keyword) => {
+                let task = read_task(keyword, headline.done, headline.priority, &mut headline.properties);
+                //keywords of our own (NEXT, WAITING...) are remembered for the export
+                if keyword != get_keyword(task.status) {
+                    node.set_property("todo".to_string(), keyword.clone());
+                }
+                if task.priority.is_none() {
+                    headline.priority.into_iter().for_each(|priority| node.set_property("priority".to_string(), priority.to_string()));
+                }
+                node.set_task(Some(task));
+            }
+            None => {
+                if let Some(priority) = headline.priority {
+                    node.set_property("priority".to_string(), priority.to_string());
+                }
+            }
+        }
+        for (key, value) in headline.properties {
+            node.set_property(key, value);
+        }
+        let body = headline.body.join("\n");
+        if !body.trim().is_empty() {
+            node.set_property("body".to_string(), body.trim().to_string());
+        }
+
+        while parents.last().map_or(false, |(level, _)| *level >= headline.level) {
+            parents.pop();
+        }
+        let parent = parents.last().map(|&(_, parent)| parent);
+        if let Some(parent) = parent {
+            edges.push((parent, nodes.len()));
+        }
+
+        parents.push((headline.level, nodes.len()));
+        let node = register_node(node);
+        if let Some(parent) = parent {
+            let (id, parent) = (node.read().unwrap().get_id(), nodes[parent].read().unwrap().get_id());
+            hierarchy::set_parent(id, Some(parent), None).unwrap();
+        }
+        nodes.push(node);
+    }
+
+    place_nodes(module, &nodes, &edges, layout);
+
+    Ok((nodes.len(), 0))
+}
+
+//org tags are words, anything else becomes "_"
+fn tag_name(tag: &str) -> String {
+    tag.chars().map(|c| if c.is_alphanumeric() || matches!(c, '_' | '@' | '#' | '%') { c } else { '_' }).collect()
+}
+
+//(keyword, done) a node is written with: its task's, or the "todo" property of nodes that aren't tasks
+fn get_node_keyword(node: &Node) -> Option<(String, bool)> {
+    let custom = node.get_property("todo");
+    match node.get_task() {
+        Some(task) => {
+            let closed = task.status.is_closed();
+            let keyword = custom.filter(|keyword| get_status(keyword, closed) == task.status).map_or(get_keyword(task.status), |keyword| keyword);
+            Some((keyword.to_string(), closed))
+        }
+        None => custom.map(|keyword| (keyword.clone(), keyword == DEFAULT_KEYWORDS[1])),
+    }
+}
+
+//scheduled, deadline and closed timestamps. A task's come from the task
+fn get_planning(node: &Node) -> Vec<String> {
+    let task = node.get_task();
+    PLANNING
+        .iter()
+        .filter_map(|(keyword, property)| {
+            let timestamp = match (*property, task) {
+                ("deadline", Some(task)) => match task.recurrence {
+                    Some(recurrence) => format!("{} {}", task.get_due()?, recurrence.get_repeater()),
+                    None => task.get_due()?.clone(),
+                },
+                ("closed", Some(task)) => format_timestamp(task.completed?),
+                _ => node.get_property(property)?.clone(),
+            };
+            Some(match *property {
+                "closed" => format!("{} [{}]", keyword, timestamp),
+                _ => format!("{} <{}>", keyword, timestamp),
+            })
+        })
+        .collect()
+}
+
+fn write_headline(document: &mut String, id: i64, level: usize, visited: &mut HashSet<i64>) {
+    if !visited.insert(id) {
+        return;
+    }
+    let node = match find_node(id) {
+        Some(node) => node,
+        None => return,
+    };
+
+    {
+        let node = node.read().unwrap();
+        let mut lines = node.get_content().lines();
+
+        let mut headline = format!("{} ", "*".repeat(level));
+        if let Some((keyword, _)) = get_node_keyword(&node) {
+            headline.push_str(&format!("{} ", keyword));
+        }
+        let priority = match node.get_task().and_then(|task| task.priority) {
+            Some(TaskPriority::High) => Some("A"),
+            Some(TaskPriority::Medium) => Some("B"),
+            Some(TaskPriority::Low) => Some("C"),
+            None => node.get_property("priority").map(String::as_str),
+        };
+        if let Some(priority) = priority {
+            headline.push_str(&format!("[#{}] ", priority));
+        }
+        headline.push_str(lines.next().unwrap_or_default().trim());
+        if !node.get_tags().is_empty() {
+            let tags: Vec<String> = node.get_tags().iter().map(|tag| tag_name(tag)).collect();
+            headline.push_str(&format!(" :{}:", tags.join(":")));
+        }
+        document.push_str(headline.trim_end());
+        document.push('\n');
+
+        let planning = get_planning(&node);
+        if !planning.is_empty() {
+            document.push_str(&format!("{}\n", planning.join(" ")));
+        }
+
+        let drawer: Vec<_> = node.get_properties().iter().filter(|(key, _)| !HEADLINE_PROPERTIES.contains(&key.as_str())).collect();
+        if !drawer.is_empty() {
+            document.push_str(":PROPERTIES:\n");
+            for (key, value) in drawer {
+                //drawer values are one line
+                document.push_str(&format!(":{}: {}\n", key.replace([' ', ':'], "_"), value.get_text().replace('\n', " ")));
+            }
+            document.push_str(":END:\n");
+        }
+
+        //more lines of content than the headline go before the body. Lines that would read as headlines get indented
+        for line in lines.chain(node.get_property("body").map(|body| body.lines()).into_iter().flatten()) {
+            let stars = line.chars().take_while(|c| *c == '*').count();
+            if stars > 0 && line[stars..].starts_with(' ') {
+                document.push(' ');
+            }
+            document.push_str(line);
+            document.push('\n');
+        }
+    }
+
+    for child in hierarchy::get_children(id) {
+        write_headline(document, child, level + 1, visited);
+    }
+}
+
+//the tree below root, or the trees of every node of the module whose parent isn't in the module.
+//returns (nodes, links) written, which is never any links
+pub fn export(path: &str, root: Option<i64>, module: &ModuleRef) -> Result<(usize, usize), String> {
+    let roots = match root {
+        Some(root) => {
+            find_node(root).ok_or(format!("no node with id {}", root))?;
+            vec![root]
+        }
+        None => {
+            let placed: HashSet<i64> = module.read().unwrap().get_node_bounds().into_keys().collect();
+            hierarchy::get_tree_order().into_iter()
+                .filter(|id| placed.contains(id) && !hierarchy::get_parent(*id).map_or(false, |parent| placed.contains(&parent)))
+                .collect()
+        }
+    };
+
+    let mut document = String::new();
+    let mut visited = HashSet::new();
+    for root in roots {
+        write_headline(&mut document, root, 1, &mut visited);
+    }
+
+    //keywords other than TODO/DONE have to be declared, or reading the file back makes them part of the title.
+    //the done ones go after the "|"
+    let mut keywords: Vec<(bool, String)> = visited.iter()
+        .filter_map(|id| get_node_keyword(&find_node(*id)?.read().unwrap()))
+        .filter(|(keyword, _)| !DEFAULT_KEYWORDS.contains(&keyword.as_str()))
+        .map(|(keyword, done)| (done, keyword))
+        .collect();
+    keywords.sort();
+    keywords.dedup();
+    if !keywords.is_empty() {
+        let list = |done: bool| keywords.iter().filter(|keyword| keyword.0 == done).map(|(_, keyword)| format!(" {}", keyword)).collect::<String>();
+        document = format!("#+TODO: TODO{} | DONE{}\n{}", list(false), list(true), document);
+    }
+
+    write_file(path, &document)?;
+
+    Ok((visited.len(), 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headline_keyword_priority_and_tags() {
+        let keywords = vec!["TODO".to_string(), "DONE".to_string()];
+        let headline = parse_headline(2, " TODO [#A] write the report :work:urgent:", &keywords, &keywords[1..]);
+        assert_eq!(headline.level, 2);
+        assert_eq!(headline.keyword.as_deref(), Some("TODO"));
+        assert!(!headline.done);
+        assert_eq!(headline.priority, Some('A'));
+        assert_eq!(headline.title, "write the report");
+        assert_eq!(headline.tags, vec!["work", "urgent"]);
+
+        //a word that only looks like a keyword or tags stays in the title
+        let headline = parse_headline(1, " TODOS :not tags: here", &keywords, &keywords[1..]);
+        assert_eq!(headline.keyword, None);
+        assert_eq!(headline.title, "TODOS :not tags: here");
+        assert!(headline.tags.is_empty());
+    }
+
+    #[test]
+    fn planning_drawer_and_body() {
+        let data = "#+TITLE: Notes\n* TODO call\nDEADLINE: <2024-05-01 Wed> SCHEDULED: <2024-04-30>\n:PROPERTIES:\n:ID: abc\n:Effort: 1:00\n:END:\nfirst line\n** child\n";
+        let (title, headlines) = parse(data);
+        assert_eq!(title.as_deref(), Some("Notes"));
+        assert_eq!(headlines.len(), 2);
+        let properties: Vec<(&str, &str)> = headlines[0].properties.iter().map(|(key, value)| (key.as_str(), value.as_str())).collect();
+        assert_eq!(properties, vec![("scheduled", "2024-04-30"), ("deadline", "2024-05-01 Wed"), ("ID", "abc"), ("Effort", "1:00")]);
+        assert_eq!(headlines[0].body, vec!["first line"]);
+        assert_eq!(headlines[1].level, 2);
+    }
+
+    #[test]
+    fn custom_keywords() {
+        assert_eq!(parse_keywords(" NEXT(n) WAITING | FINISHED(f) CANCELLED"), (vec!["NEXT".to_string(), "WAITING".to_string()], vec!["FINISHED".to_string(), "CANCELLED".to_string()]));
+        //without "|" the last one is the done one
+        assert_eq!(parse_keywords(" OPEN CLOSED"), (vec!["OPEN".to_string()], vec!["CLOSED".to_string()]));
+
+        let (_, headlines) = parse("#+TODO: NEXT | FINISHED\n* FINISHED a\n* NEXT b\n* DONE c\n");
+        assert!(headlines[0].done);
+        assert_eq!(headlines[1].keyword.as_deref(), Some("NEXT"));
+        assert!(headlines[2].done);
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(find_timestamp("DEADLINE: <2024-05-01 Wed 10:00>", "DEADLINE:").as_deref(), Some("2024-05-01 Wed 10:00"));
+        assert_eq!(find_timestamp("CLOSED: [2024-05-01 Wed 10:00]", "CLOSED:").as_deref(), Some("2024-05-01 Wed 10:00"));
+        assert_eq!(find_timestamp("SCHEDULED: <2024-05-01>--<2024-05-03>", "SCHEDULED:").as_deref(), Some("2024-05-01>--<2024-05-03"));
+        assert_eq!(find_timestamp("DEADLINE: 2024-05-01", "DEADLINE:"), None);
+
+        let (due, recurrence) = parse_timestamp("2024-05-03 Fri 10:00-11:00 .+2w");
+        assert_eq!(due.as_deref(), Some("2024-05-03 10:00"));
+        assert_eq!(recurrence.map(|recurrence| recurrence.get_name()).as_deref(), Some("every 2 weeks"));
+        assert_eq!(parse_timestamp("2024-05-03 Fri"), (Some("2024-05-03".to_string()), None));
+    }
+
+    #[test]
+    fn keywords_make_tasks() {
+        let mut properties = vec![("deadline".to_string(), "2024-01-31 +1m".to_string()), ("closed".to_string(), "2024-02-01 Thu 09:30".to_string())];
+        let task = read_task("DONE", true, Some('B'), &mut properties);
+        assert_eq!(task.status, TaskStatus::Done);
+        assert_eq!(task.priority, Some(TaskPriority::Medium));
+        assert_eq!(task.get_due().map(String::as_str), Some("2024-01-31"));
+        assert_eq!(task.completed, Some(1706779800));
+        assert!(properties.is_empty());
+
+        assert_eq!(get_status("CANCELLED", true), TaskStatus::Cancelled);
+        assert_eq!(get_status("STARTED", false), TaskStatus::InProgress);
+        assert_eq!(get_status("NEXT", false), TaskStatus::Open);
+    }
+}
@@ -0,0 +1,246 @@
+use crate::attachments::Attachment;
+use crate::formats::{place_nodes, register_node, Layout};
+use crate::hierarchy;
+use crate::structs::node::Node;
+use crate::structs::property::Property;
+use crate::types::ModuleRef;
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+//PDF documents. The document is a node with the file as its attachment, its bookmarks (the outline) become
+//nodes below it and so do highlights and notes: below the last bookmark that starts on or before their page.
+//page numbers are kept in the "page" property. Highlights only store the note written on them, pdfs don't
+//keep the highlighted text itself.
+
+const DOCUMENT_TAG: &str = "document";
+const ANNOTATION_TAG: &str = "annotation";
+//the markup and note annotations, links, widgets, popups and such are skipped
+const ANNOTATION_TYPES: [&str; 6] = ["Highlight", "Underline", "StrikeOut", "Squiggly", "Text", "FreeText"];
+
+struct Bookmark {
+    title: String,
+    page: Option<u32>,
+    children: Vec<Bookmark>,
+}
+
+struct Annotation {
+    kind: String,
+    page: u32,
+    contents: String,
+    author: String,
+}
+
+//text strings are UTF-16 with a byte order mark, or PDFDocEncoding which is close enough to latin-1
+fn decode_text(bytes: &[u8]) -> String {
+    if let Some(utf16) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        let units: Vec<u16> = utf16.chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect();
+        String::from_utf16_lossy(&units)
+    } else if let Some(utf8) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        String::from_utf8_lossy(utf8).to_string()
+    } else {
+        bytes.iter().map(|byte| *byte as char).collect()
+    }
+}
+
+struct Reader {
+    document: Document,
+    pages: HashMap<ObjectId, u32>, //page object -> page number
+}
+
+impl Reader {
+    fn resolve<'a>(&'a self, object: &'a Object) -> Option<&'a Object> {
+        self.document.dereference(object).ok().map(|(_, object)| object)
+    }
+
+    fn get<'a>(&'a self, dictionary: &'a Dictionary, key: &[u8]) -> Option<&'a Object> {
+        self.resolve(dictionary.get(key).ok()?)
+    }
+
+    fn get_text(&self, dictionary: &Dictionary, key: &[u8]) -> Option<String> {
+        self.get(dictionary, key)?.as_str().ok().map(decode_text)
+    }
+
+    //a destination is [page /XYZ ...], a name to look up, or an action with one in /D
+    fn find_page(&self, destination: &Object, depth: usize) -> Option<u32> {
+        if depth > 8 {
+            return None;
+        }
+        match self.resolve(destination)? {
+            Object::Array(array) => self.pages.get(&array.first()?.as_reference().ok()?).cloned(),
+            Object::Dictionary(dictionary) => self.find_page(dictionary.get(b"D").ok()?, depth + 1),
+            Object::Name(name) | Object::String(name, _) => self.find_page(self.find_named_destination(name)?, depth + 1),
+            _ => None,
+        }
+    }
+
+    //old style /Dests dictionary in the catalog, or the /Dests name tree in /Names
+    fn find_named_destination(&self, name: &[u8]) -> Option<&Object> {
+        let catalog = self.document.catalog().ok()?;
+        if let Some(Object::Dictionary(dests)) = self.get(catalog, b"Dests") {
+            if let Ok(destination) = dests.get(name) {
+                return Some(destination);
+            }
+        }
+        let names = self.get(catalog, b"Names")?.as_dict().ok()?;
+        let tree = self.get(names, b"Dests")?.as_dict().ok()?;
+        self.search_name_tree(tree, name, 0)
+    }
+
+    fn search_name_tree<'a>(&'a self, node: &'a Dictionary, name: &[u8], depth: usize) -> Option<&'a Object> {
+        if depth > 32 {
+            return None;
+        }
+        if let Some(Object::Array(names)) = self.get(node, b"Names") {
+            for pair in names.chunks(2) {
+                if let (Ok(key), Some(value)) = (pair[0].as_str(), pair.get(1)) {
+                    if key == name {
+                        return Some(value);
+                    }
+                }
+            }
+        }
+        if let Some(Object::Array(kids)) = self.get(node, b"Kids") {
+            for kid in kids {
+                if let Some(found) = self.resolve(kid).and_then(|kid| kid.as_dict().ok()).and_then(|kid| self.search_name_tree(kid, name, depth + 1)) {
+                    return Some(found);
+                }
+            }
+        }
+        None
+    }
+
+    //the /First, /Next chain of an outline item. visited guards against broken files that loop
+    fn read_outline(&self, parent: &Dictionary, visited: &mut HashSet<ObjectId>) -> Vec<Bookmark> {
+        let mut bookmarks = Vec::new();
+        let mut next = parent.get(b"First").ok().and_then(|first| first.as_reference().ok());
+        while let Some(id) = next.filter(|id| visited.insert(*id)) {
+            let item = match self.document.get_dictionary(id) {
+                Ok(item) => item,
+                Err(_) => break,
+            };
+            let destination = item.get(b"Dest").ok().or(item.get(b"A").ok());
+            bookmarks.push(Bookmark {
+                title: self.get_text(item, b"Title").unwrap_or_default().trim().to_string(),
+                page: destination.and_then(|destination| self.find_page(destination, 0)),
+                children: self.read_outline(item, visited),
+            });
+            next = item.get(b"Next").ok().and_then(|next| next.as_reference().ok());
+        }
+        bookmarks
+    }
+
+    fn read_annotations(&self) -> Vec<Annotation> {
+        let mut pages: Vec<(ObjectId, u32)> = self.pages.iter().map(|(id, page)| (*id, *page)).collect();
+        pages.sort_by_key(|(_, page)| *page);
+
+        let mut annotations = Vec::new();
+        for (id, page) in pages {
+            for annotation in self.document.get_page_annotations(id) {
+                let kind = match self.get(annotation, b"Subtype").and_then(|subtype| subtype.as_name_str().ok()) {
+                    Some(kind) if ANNOTATION_TYPES.contains(&kind) => kind.to_string(),
+                    _ => continue,
+                };
+                annotations.push(Annotation {
+                    kind,
+                    page,
+                    contents: self.get_text(annotation, b"Contents").unwrap_or_default().trim().to_string(),
+                    author: self.get_text(annotation, b"T").unwrap_or_default().trim().to_string(),
+                });
+            }
+        }
+        annotations
+    }
+}
+
+//adds the bookmarks below parent. pages collects (page, index) of the ones that have a page, for placing annotations
+fn add_bookmarks(
+    bookmarks: Vec<Bookmark>,
+    parent: usize,
+    owner: &str,
+    nodes: &mut Vec<Arc<RwLock<Node>>>,
+    edges: &mut Vec<(usize, usize)>,
+    pages: &mut Vec<(u32, usize)>,
+) {
+    for bookmark in bookmarks {
+        let mut node = Node::create_and_register(bookmark.title, owner.to_string());
+        if let Some(page) = bookmark.page {
+            node.set_typed_property("page".to_string(), Property::number(page as f64));
+        }
+        let node = register_node(node);
+        hierarchy::set_parent(node.read().unwrap().get_id(), Some(nodes[parent].read().unwrap().get_id()), None).unwrap();
+
+        let index = nodes.len();
+        edges.push((parent, index));
+        nodes.push(node);
+        if let Some(page) = bookmark.page {
+            pages.push((page, index));
+        }
+        add_bookmarks(bookmark.children, index, owner, nodes, edges, pages);
+    }
+}
+
+//returns (nodes, links) created, everything goes into the hierarchy so there are no links
+pub fn import(path: &str, module: &ModuleRef, layout: Layout) -> Result<(usize, usize), String> {
+    let document = Document::load(path).map_err(|e| format!("can't read {}: {}", path, e))?;
+    let pages = document.get_pages().into_iter().map(|(number, id)| (id, number)).collect();
+    let reader = Reader { document, pages };
+
+    let info = reader.document.trailer.get(b"Info").ok().and_then(|info| reader.resolve(info)).and_then(|info| info.as_dict().ok());
+    let title = info
+        .and_then(|info| reader.get_text(info, b"Title"))
+        .map(|title| title.trim().to_string())
+        .filter(|title| !title.is_empty())
+        .unwrap_or(Path::new(path).file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or(path.to_string()));
+
+    let outline = reader
+        .document
+        .catalog()
+        .ok()
+        .and_then(|catalog| reader.get(catalog, b"Outlines"))
+        .and_then(|outlines| outlines.as_dict().ok())
+        .map(|outlines| reader.read_outline(outlines, &mut HashSet::new()))
+        .unwrap_or_default();
+    let annotations = reader.read_annotations();
+
+    let owner = module.read().unwrap().get_name();
+    let mut root = Node::create_and_register(title, owner.clone());
+    root.add_tag(DOCUMENT_TAG.to_string());
+    root.add_attachment(Attachment::External(path.to_string()));
+    root.set_typed_property("pages".to_string(), Property::number(reader.pages.len() as f64));
+    if let Some(author) = info.and_then(|info| reader.get_text(info, b"Author")).filter(|author| !author.trim().is_empty()) {
+        root.set_property("author".to_string(), author.trim().to_string());
+    }
+
+    let mut nodes = vec![register_node(root)];
+    let mut edges = Vec::new();
+    let mut pages = Vec::new();
+    add_bookmarks(outline, 0, &owner, &mut nodes, &mut edges, &mut pages);
+
+    for annotation in annotations {
+        //the last bookmark starting on or before the page, bookmarks come in reading order
+        let parent = pages.iter().rev().find(|(page, _)| *page <= annotation.page).map_or(0, |(_, index)| *index);
+
+        let content = if annotation.contents.is_empty() {
+            format!("{} on page {}", annotation.kind, annotation.page)
+        } else {
+            annotation.contents
+        };
+        let mut node = Node::create_and_register(content, owner.clone());
+        node.add_tag(ANNOTATION_TAG.to_string());
+        node.set_typed_property("page".to_string(), Property::number(annotation.page as f64));
+        node.set_property("type".to_string(), annotation.kind.to_lowercase());
+        if !annotation.author.is_empty() {
+            node.set_property("author".to_string(), annotation.author);
+        }
+        let node = register_node(node);
+        hierarchy::set_parent(node.read().unwrap().get_id(), Some(nodes[parent].read().unwrap().get_id()), None).unwrap();
+        edges.push((parent, nodes.len()));
+        nodes.push(node);
+    }
+
+    place_nodes(module, &nodes, &edges, layout);
+
+    Ok((nodes.len(), 0))
+}
@@ -0,0 +1,10 @@
+use crate::formats::register_node;
+use crate::structs::node::Node;
+
+//for tests that work on the global NODES: every test gets nodes of its own, with ids from NODE_COUNTER like any
+//other node, so tests running at the same time never touch each other's nodes
+
+//registers N new nodes, their ids in order
+pub fn make_nodes<const N: usize>() -> [i64; N] {
+    std::array::from_fn(|_| register_node(Node::create_and_register("test node".to_string(), String::new())).read().unwrap().get_id())
+}
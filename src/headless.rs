@@ -0,0 +1,198 @@
+use lazy_static::lazy_static;
+use rusttype::{point, Font, Scale};
+use speedy2d::color::Color;
+use speedy2d::dimen::Vec2;
+use speedy2d::shape::RoundRect;
+use std::fs::File;
+use std::io::BufWriter;
+
+lazy_static! {
+    static ref FONT: Font<'static> = Font::try_from_bytes(include_bytes!("../res/OpenSans-SemiBold.ttf")).unwrap();
+}
+
+//cpu only rgba image. Modules draw into this when there is no window (and no opengl) around,
+//mirroring the few Graphics2D calls they use so the output looks like the real thing.
+pub struct Raster {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+//the largest image render_headless makes, in pixels on either side. 16384 x 16384 is a gigabyte of rgba already
+pub const MAX_SIDE: u32 = 16384;
+
+impl Raster {
+    pub fn new(width: u32, height: u32, background: Color) -> Result<Raster, String> {
+        if width > MAX_SIDE || height > MAX_SIDE {
+            return Err(format!("a {}x{} image is too big, {} pixels on either side at most (try a smaller --scale)", width, height, MAX_SIDE));
+        }
+        let count = (width as usize).checked_mul(height as usize).ok_or(format!("a {}x{} image is too big", width, height))?;
+        let mut pixels = Vec::with_capacity(count.checked_mul(4).ok_or(format!("a {}x{} image is too big", width, height))?);
+        for _ in 0..count {
+            pixels.extend_from_slice(&[
+                (background.r() * 255.0) as u8,
+                (background.g() * 255.0) as u8,
+                (background.b() * 255.0) as u8,
+                255,
+            ]);
+        }
+        Ok(Raster {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+
+    //alpha blend a color over a pixel. coverage is how much of the pixel the shape covers (antialiasing)
+    fn blend(&mut self, x: i32, y: i32, color: Color, coverage: f32) {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return;
+        }
+        let alpha = (color.a() * coverage).clamp(0.0, 1.0);
+        if alpha <= 0.0 {
+            return;
+        }
+        let index = (y as usize * self.width as usize + x as usize) * 4;
+        for (channel, value) in [color.r(), color.g(), color.b()].into_iter().enumerate() {
+            let old = self.pixels[index + channel] as f32 / 255.0;
+            self.pixels[index + channel] = ((value * alpha + old * (1.0 - alpha)) * 255.0).round() as u8;
+        }
+        let old_alpha = self.pixels[index + 3] as f32 / 255.0;
+        self.pixels[index + 3] = ((alpha + old_alpha * (1.0 - alpha)) * 255.0).round() as u8;
+    }
+
+    pub fn draw_rounded_rectangle(&mut self, rect: &RoundRect, color: Color) {
+        let top_left = *rect.top_left();
+        let bottom_right = *rect.bottom_right();
+        let center = (top_left + bottom_right) * 0.5;
+        let half_size = (bottom_right - top_left) * 0.5;
+        let radius = rect.radius().min(half_size.x).min(half_size.y).max(0.0);
+
+        for y in top_left.y.floor() as i32..bottom_right.y.ceil() as i32 {
+            for x in top_left.x.floor() as i32..bottom_right.x.ceil() as i32 {
+                //signed distance from the pixel center to the rounded rectangle border
+                let qx = (x as f32 + 0.5 - center.x).abs() - (half_size.x - radius);
+                let qy = (y as f32 + 0.5 - center.y).abs() - (half_size.y - radius);
+                let outside = Vec2::new(qx.max(0.0), qy.max(0.0)).magnitude();
+                let distance = outside + qx.max(qy).min(0.0) - radius;
+                self.blend(x, y, color, 0.5 - distance);
+            }
+        }
+    }
+
+    pub fn draw_line(&mut self, start: Vec2, end: Vec2, thickness: f32, color: Color) {
+        let direction = end - start;
+        let length_squared = direction.magnitude_squared();
+        let min = Vec2::new(start.x.min(end.x), start.y.min(end.y)) - Vec2::new(thickness, thickness);
+        let max = Vec2::new(start.x.max(end.x), start.y.max(end.y)) + Vec2::new(thickness, thickness);
+
+        for y in min.y.floor() as i32..max.y.ceil() as i32 {
+            for x in min.x.floor() as i32..max.x.ceil() as i32 {
+                //distance from the pixel center to the closest point of the segment
+                let pixel = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+                let t = if length_squared > 0.0 {
+                    (((pixel.x - start.x) * direction.x + (pixel.y - start.y) * direction.y) / length_squared).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                let distance = (pixel - (start + direction * t)).magnitude();
+                self.blend(x, y, color, thickness / 2.0 + 0.5 - distance);
+            }
+        }
+    }
+
+    pub fn draw_circle(&mut self, center: Vec2, radius: f32, color: Color) {
+        for y in (center.y - radius).floor() as i32..(center.y + radius).ceil() as i32 {
+            for x in (center.x - radius).floor() as i32..(center.x + radius).ceil() as i32 {
+                let distance = (Vec2::new(x as f32 + 0.5, y as f32 + 0.5) - center).magnitude();
+                self.blend(x, y, color, radius + 0.5 - distance);
+            }
+        }
+    }
+
+    //same as Graphics2D::draw_triangle, the points can go either way round
+    pub fn draw_triangle(&mut self, points: [Vec2; 3], color: Color) {
+        let min = Vec2::new(points.iter().map(|p| p.x).fold(f32::MAX, f32::min), points.iter().map(|p| p.y).fold(f32::MAX, f32::min));
+        let max = Vec2::new(points.iter().map(|p| p.x).fold(f32::MIN, f32::max), points.iter().map(|p| p.y).fold(f32::MIN, f32::max));
+
+        for y in min.y.floor() as i32..max.y.ceil() as i32 {
+            for x in min.x.floor() as i32..max.x.ceil() as i32 {
+                //signed distance from the pixel center to each edge, positive inside whatever the winding
+                let pixel = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+                let mut inside = f32::MAX;
+                let mut outside = f32::MAX;
+                for i in 0..3 {
+                    let (a, b) = (points[i], points[(i + 1) % 3]);
+                    let edge = b - a;
+                    let length = edge.magnitude();
+                    if length == 0.0 {
+                        continue;
+                    }
+                    let distance = (edge.x * (pixel.y - a.y) - edge.y * (pixel.x - a.x)) / length;
+                    inside = inside.min(distance);
+                    outside = outside.min(-distance);
+                }
+                self.blend(x, y, color, inside.max(outside) + 0.5);
+            }
+        }
+    }
+
+    //rgba pixels stretched over the rectangle from position to position + size, nearest pixel
+    pub fn draw_image(&mut self, position: Vec2, size: Vec2, width: u32, height: u32, pixels: &[u8]) {
+        if width == 0 || height == 0 || size.x <= 0.0 || size.y <= 0.0 {
+            return;
+        }
+        for y in position.y.floor() as i32..(position.y + size.y).ceil() as i32 {
+            for x in position.x.floor() as i32..(position.x + size.x).ceil() as i32 {
+                let source_x = (((x as f32 + 0.5 - position.x) / size.x * width as f32) as i32).clamp(0, width as i32 - 1);
+                let source_y = (((y as f32 + 0.5 - position.y) / size.y * height as f32) as i32).clamp(0, height as i32 - 1);
+                let index = (source_y as usize * width as usize + source_x as usize) * 4;
+                let pixel = &pixels[index..index + 4];
+                let color = Color::from_int_rgba(pixel[0], pixel[1], pixel[2], pixel[3]);
+                self.blend(x, y, color, 1.0);
+            }
+        }
+    }
+
+    //same placement as Graphics2D::draw_text: position is the top left corner of the first line
+    pub fn draw_text(&mut self, position: Vec2, size: f32, color: Color, text: &str) {
+        self.draw_slanted_text(position, size, color, text, 0.0);
+    }
+
+    //slant moves every pixel right by that much per pixel above the baseline, for faking italics
+    pub fn draw_slanted_text(&mut self, position: Vec2, size: f32, color: Color, text: &str, slant: f32) {
+        let scale = Scale::uniform(size);
+        let metrics = FONT.v_metrics(scale);
+        let line_height = metrics.ascent - metrics.descent + metrics.line_gap;
+
+        for (line_index, line) in text.lines().enumerate() {
+            let baseline = position.y + metrics.ascent + line_height * line_index as f32;
+            for glyph in FONT.layout(line, scale, point(position.x, baseline)) {
+                if let Some(bounding_box) = glyph.pixel_bounding_box() {
+                    glyph.draw(|x, y, coverage| {
+                        let y = bounding_box.min.y + y as i32;
+                        let shift = ((baseline - y as f32) * slant).round() as i32;
+                        self.blend(bounding_box.min.x + x as i32 + shift, y, color, coverage);
+                    });
+                }
+            }
+        }
+    }
+
+    pub fn save_png(&self, path: &str) -> Result<(), String> {
+        let file = File::create(path).map_err(|e| e.to_string())?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+        writer.write_image_data(&self.pixels).map_err(|e| e.to_string())
+    }
+}
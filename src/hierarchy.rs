@@ -0,0 +1,335 @@
+use crate::formats::find_node;
+use crate::{LINKS, NODES};
+use std::collections::{HashMap, HashSet};
+
+//the tree of nodes: every node has at most one parent and its children in order. It's kept apart from links,
+//which connect anything to anything, and it's what outlines, mindmap exports and indenting go by.
+//both sides are stored (the parent in the child, the children in the parent) and only changed through here.
+
+pub fn get_parent(id: i64) -> Option<i64> {
+    find_node(id)?.read().unwrap().get_parent()
+}
+
+pub fn get_children(id: i64) -> Vec<i64> {
+    find_node(id).map(|node| node.read().unwrap().get_children().clone()).unwrap_or_default()
+}
+
+//nodes without a parent, in the order they were made
+pub fn get_roots() -> Vec<i64> {
+    NODES.read().unwrap().iter()
+        .map(|node| node.read().unwrap())
+        .filter(|node| node.get_parent().is_none())
+        .map(|node| node.get_id())
+        .collect()
+}
+
+//parent first, root last
+pub fn get_ancestors(id: i64) -> Vec<i64> {
+    let mut ancestors = Vec::new();
+    let mut current = get_parent(id);
+    while let Some(parent) = current {
+        //repair() keeps circles out, this is in case something got past it
+        if parent == id || ancestors.contains(&parent) {
+            break;
+        }
+        ancestors.push(parent);
+        current = get_parent(parent);
+    }
+    ancestors
+}
+
+//everything below a node, depth first, in order
+pub fn get_descendants(id: i64) -> Vec<i64> {
+    let mut descendants = Vec::new();
+    let mut visited = HashSet::from([id]);
+    let mut stack: Vec<i64> = get_children(id).into_iter().rev().collect();
+    while let Some(child) = stack.pop() {
+        if !visited.insert(child) {
+            continue;
+        }
+        descendants.push(child);
+        stack.extend(get_children(child).into_iter().rev());
+    }
+    descendants
+}
+
+//the other children of the node's parent, in order. Roots have no siblings
+pub fn get_siblings(id: i64) -> Vec<i64> {
+    match get_parent(id) {
+        Some(parent) => get_children(parent).into_iter().filter(|child| *child != id).collect(),
+        None => Vec::new(),
+    }
+}
+
+pub fn is_ancestor(ancestor: i64, id: i64) -> bool {
+    get_ancestors(id).contains(&ancestor)
+}
+
+//every node once: each root (in the order they were made) followed by everything below it. Exporters that
+//write the parent of each node list them this way so that children can be read back in the same order
+pub fn get_tree_order() -> Vec<i64> {
+    get_roots().into_iter()
+        .flat_map(|root| std::iter::once(root).chain(get_descendants(root)))
+        .collect()
+}
+
+//moves a node (with everything below it) under another one, at index among its children (None: last).
+//None as the parent makes it a root
+pub fn set_parent(id: i64, parent: Option<i64>, index: Option<usize>) -> Result<(), String> {
+    let node = find_node(id).ok_or(format!("there is no node {}", id))?;
+    let new_parent = match parent {
+        Some(parent) if parent == id => return Err(format!("{} can't be its own parent", id)),
+        Some(parent) if is_ancestor(id, parent) => {
+            return Err(format!("{} is below {}, it can't be its parent", parent, id));
+        }
+        Some(parent) => Some(find_node(parent).ok_or(format!("there is no node {}", parent))?),
+        None => None,
+    };
+
+    detach(id);
+    node.write().unwrap().set_parent(parent);
+    if let Some(new_parent) = new_parent {
+        let mut new_parent = new_parent.write().unwrap();
+        let children = new_parent.get_children_mut();
+        let index = index.unwrap_or(children.len()).min(children.len());
+        children.insert(index, id);
+    }
+    Ok(())
+}
+
+//takes the node out of its parent's children, the node itself still says it has that parent
+fn detach(id: i64) {
+    if let Some(parent) = get_parent(id).and_then(find_node) {
+        parent.write().unwrap().get_children_mut().retain(|child| *child != id);
+    }
+}
+
+//the node becomes the last child of the sibling before it
+pub fn indent(id: i64) -> Result<(), String> {
+    let parent = get_parent(id).ok_or(format!("{} has no parent, so no sibling to go below", id))?;
+    let children = get_children(parent);
+    let position = children.iter().position(|child| *child == id).unwrap_or(0);
+    match position.checked_sub(1) {
+        Some(previous) => set_parent(id, Some(children[previous]), None),
+        None => Err(format!("{} is the first child of {}, there's nothing to indent it below", id, parent)),
+    }
+}
+
+//the node goes right after its parent, among its parent's siblings
+pub fn outdent(id: i64) -> Result<(), String> {
+    let parent = get_parent(id).ok_or(format!("{} is a root already", id))?;
+    let grandparent = get_parent(parent);
+    let index = grandparent.map(|grandparent| {
+        get_children(grandparent).iter().position(|child| *child == parent).map_or(0, |index| index + 1)
+    });
+    set_parent(id, grandparent, index)
+}
+
+//moves the node that many places up (negative) or down among its siblings, stopping at either end
+pub fn reorder(id: i64, offset: i64) -> Result<(), String> {
+    let parent = get_parent(id).ok_or(format!("{} has no parent, roots have no order", id))?;
+    let children = get_children(parent);
+    let position = children.iter().position(|child| *child == id).unwrap_or(0) as i64;
+    let index = (position + offset).clamp(0, children.len() as i64 - 1) as usize;
+    set_parent(id, Some(parent), Some(index))
+}
+
+//moves each of ids the same way (indent, outdent, reorder...) in tree order, bottom up when reversed so that
+//neighbours keep their order. A node that can't move doesn't stop the others: Ok is how many moved, Err says why not
+pub fn move_all(ids: &[i64], change: impl Fn(i64) -> Result<(), String>, reversed: bool) -> Result<usize, String> {
+    let mut ids: Vec<i64> = get_tree_order().into_iter().filter(|id| ids.contains(id)).collect();
+    if ids.is_empty() {
+        return Err("there are no nodes to move".to_string());
+    }
+    if reversed {
+        ids.reverse();
+    }
+    let errors: Vec<String> = ids.iter().filter_map(|id| change(*id).err()).collect();
+    match errors.as_slice() {
+        [] => Ok(ids.len()),
+        [error] => Err(error.clone()),
+        [error, others @ ..] => Err(format!("{} (and {} more can't move)", error, others.len())),
+    }
+}
+
+//for nodes about to be deleted: their children take their place in their parent (or become roots).
+//returns what went wrong on the way, children that couldn't move up
+pub fn remove(id: i64) -> Vec<String> {
+    let parent = get_parent(id);
+    let index = parent.and_then(|parent| get_children(parent).iter().position(|child| *child == id));
+    let mut problems = Vec::new();
+    for (offset, child) in get_children(id).into_iter().enumerate() {
+        let index = index.map(|index| index + offset + 1);
+        if let Err(error) = set_parent(child, parent, index) {
+            problems.push(format!("node {} can't move up: {}", child, error));
+        }
+    }
+    detach(id);
+    if let Some(node) = find_node(id) {
+        node.write().unwrap().set_parent(None);
+    }
+    problems
+}
+
+//trees used to be made of links (importers linked every child from its parent). Links without a label or a
+//relation become parent -> child pairs, in the order they were made, unless the child has a parent already or
+//it would make a circle. Returns how many links went
+pub fn adopt_links() -> usize {
+    let mut links: Vec<(i64, i64, i64)> = LINKS.read().unwrap().iter()
+        .map(|link| link.read().unwrap())
+        .filter(|link| link.get_label().is_empty() && link.get_relation().is_empty())
+        .map(|link| (link.get_id(), link.get_from_id(), link.get_to_id()))
+        .collect();
+    links.sort();
+
+    let mut adopted = Vec::new();
+    for (link, from, to) in links {
+        if get_parent(to).is_none() && set_parent(to, Some(from), None).is_ok() {
+            adopted.push(link);
+        }
+    }
+    LINKS.write().unwrap().retain(|link| !adopted.contains(&link.read().unwrap().get_id()));
+    adopted.len()
+}
+
+//makes parents and children agree after loading. The parent a node names wins: children lists keep their
+//order but lose the nodes that don't name them, and gain (at the end) the ones that do. Parents that don't
+//exist and parents that would make a circle are dropped. Returns what had to be dropped, nothing for a
+//workspace that was fine
+pub fn repair() -> Vec<String> {
+    let nodes = NODES.read().unwrap();
+    let mut parents: HashMap<i64, i64> = HashMap::new();
+    for node in nodes.iter() {
+        let node = node.read().unwrap();
+        if let Some(parent) = node.get_parent() {
+            parents.insert(node.get_id(), parent);
+        }
+    }
+    let ids: HashSet<i64> = nodes.iter().map(|node| node.read().unwrap().get_id()).collect();
+    let mut problems = Vec::new();
+    parents.retain(|id, parent| {
+        let kept = id != parent && ids.contains(parent);
+        if !kept {
+            problems.push(format!("node {} had node {} as its parent, which isn't there, it's a root now", id, parent));
+        }
+        kept
+    });
+
+    //walking up from every node, the first parent that leads back to somewhere already on the way is cut
+    for node in nodes.iter() {
+        let mut path = vec![node.read().unwrap().get_id()];
+        while let Some(parent) = parents.get(path.last().unwrap()).copied() {
+            if path.contains(&parent) {
+                let child = *path.last().unwrap();
+                problems.push(format!("node {} was in a circle of parents, it's a root now", child));
+                parents.remove(&child);
+                break;
+            }
+            path.push(parent);
+        }
+    }
+
+    let mut children: HashMap<i64, Vec<i64>> = HashMap::new();
+    for node in nodes.iter() {
+        let node = node.read().unwrap();
+        let id = node.get_id();
+        let mut own: Vec<i64> = Vec::new();
+        for child in node.get_children() {
+            if parents.get(child) == Some(&id) && !own.contains(child) {
+                own.push(*child);
+            }
+        }
+        children.insert(id, own);
+    }
+    for node in nodes.iter() {
+        let id = node.read().unwrap().get_id();
+        if let Some(parent) = parents.get(&id) {
+            let siblings = children.get_mut(parent).unwrap();
+            if !siblings.contains(&id) {
+                siblings.push(id);
+            }
+        }
+    }
+
+    for node in nodes.iter() {
+        let mut node = node.write().unwrap();
+        let id = node.get_id();
+        node.set_parent(parents.get(&id).copied());
+        *node.get_children_mut() = children.remove(&id).unwrap_or_default();
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::test_support::make_nodes;
+
+    #[test]
+    fn circles_are_rejected() {
+        let [a, b, c, d] = make_nodes();
+        set_parent(b, Some(a), None).unwrap();
+        set_parent(c, Some(b), None).unwrap();
+
+        assert!(set_parent(a, Some(a), None).is_err());
+        assert!(set_parent(a, Some(c), None).is_err());
+        assert!(set_parent(b, Some(c), None).is_err());
+        assert!(set_parent(d, Some(-1), None).is_err());
+        //nothing moved
+        assert_eq!(get_parent(a), None);
+        assert_eq!(get_children(a), vec![b]);
+        assert_eq!(get_ancestors(c), vec![b, a]);
+
+        //moving a subtree keeps it whole, both sides agree
+        set_parent(b, Some(d), Some(0)).unwrap();
+        assert_eq!(get_children(a), Vec::<i64>::new());
+        assert_eq!(get_children(d), vec![b]);
+        assert_eq!(get_descendants(d), vec![b, c]);
+        assert!(is_ancestor(d, c));
+    }
+
+    #[test]
+    fn outliner_moves() {
+        let [root, x, y, z] = make_nodes();
+        for child in [x, y, z] {
+            set_parent(child, Some(root), None).unwrap();
+        }
+
+        assert!(indent(x).is_err());
+        indent(y).unwrap();
+        assert_eq!(get_children(root), vec![x, z]);
+        assert_eq!(get_children(x), vec![y]);
+        outdent(y).unwrap();
+        assert_eq!(get_children(root), vec![x, y, z]);
+
+        reorder(z, -5).unwrap();
+        assert_eq!(get_children(root), vec![z, x, y]);
+        assert_eq!(get_siblings(x), vec![z, y]);
+
+        //children of a removed node take its place
+        set_parent(y, Some(x), None).unwrap();
+        assert!(remove(x).is_empty());
+        assert_eq!(get_children(root), vec![z, y]);
+        assert_eq!(get_parent(y), Some(root));
+    }
+
+    #[test]
+    fn moving_many_keeps_their_order() {
+        let [root, a, b, c] = make_nodes();
+        for child in [a, b, c] {
+            set_parent(child, Some(root), None).unwrap();
+        }
+
+        //a and b go down together, bottom up, so b makes room for a instead of swapping with it
+        assert_eq!(move_all(&[b, a], |id| reorder(id, 1), true), Ok(2));
+        assert_eq!(get_children(root), vec![c, a, b]);
+        assert_eq!(move_all(&[a, b], |id| reorder(id, -1), false), Ok(2));
+        assert_eq!(get_children(root), vec![a, b, c]);
+
+        //the first child can't be indented, the others still are
+        assert!(move_all(&[c, b, a], indent, false).is_err());
+        assert_eq!(get_children(root), vec![a]);
+        assert_eq!(get_children(a), vec![b, c]);
+        assert!(move_all(&[], indent, false).is_err());
+    }
+}
@@ -0,0 +1,62 @@
+use speedy2d::dimen::Vec2;
+
+//automatic layouts for nodes that come without positions (imports mostly).
+//both return the top left corner of every node, nodes are referred to by index.
+
+const FORCE_ITERATIONS: usize = 300;
+
+pub fn grid(count: usize, spacing: f32) -> Vec<(f32, f32)> {
+    let columns = (count as f32).sqrt().ceil().max(1.0) as usize;
+    (0..count)
+        .map(|index| ((index % columns) as f32 * spacing, (index / columns) as f32 * spacing))
+        .collect()
+}
+
+//classic Fruchterman-Reingold: linked nodes pull each other, every pair pushes apart.
+//spacing is roughly the distance between two linked nodes once everything settles
+pub fn force_directed(count: usize, edges: &[(usize, usize)], spacing: f32) -> Vec<(f32, f32)> {
+    //start on a spiral so that nothing overlaps and the result is always the same
+    let mut positions: Vec<Vec2> = (0..count)
+        .map(|index| {
+            let angle = index as f32 * 2.4;
+            let radius = spacing * (index as f32).sqrt();
+            Vec2::new(radius * angle.cos(), radius * angle.sin())
+        })
+        .collect();
+
+    let mut temperature = spacing * (count as f32).sqrt();
+    for _ in 0..FORCE_ITERATIONS {
+        let mut displacement = vec![Vec2::ZERO; count];
+
+        for a in 0..count {
+            for b in a + 1..count {
+                let delta = positions[a] - positions[b];
+                let distance = delta.magnitude().max(1.0);
+                let push = delta * (spacing * spacing / (distance * distance));
+                displacement[a] = displacement[a] + push;
+                displacement[b] = displacement[b] - push;
+            }
+        }
+
+        for &(a, b) in edges {
+            if a == b || a >= count || b >= count {
+                continue;
+            }
+            let delta = positions[a] - positions[b];
+            let pull = delta * (delta.magnitude() / spacing);
+            displacement[a] = displacement[a] - pull;
+            displacement[b] = displacement[b] + pull;
+        }
+
+        //move at most "temperature" per step, and cool down
+        for (position, displacement) in positions.iter_mut().zip(displacement) {
+            let length = displacement.magnitude();
+            if length > 0.0 {
+                *position = *position + displacement * (length.min(temperature) / length);
+            }
+        }
+        temperature *= 0.97;
+    }
+
+    positions.into_iter().map(|position| (position.x, position.y)).collect()
+}
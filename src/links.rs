@@ -0,0 +1,83 @@
+use crate::formats::register_link;
+use crate::structs::link::{Link, LinkDirection, LinkStyle};
+use crate::workspace;
+use crate::LINKS;
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+
+//links made and changed a bunch at a time: from a group of nodes to another one, or all the links among a group
+//(the selected nodes of a map). The cli changes single links by id, see cli.rs
+
+//links with both ends among ids
+pub fn get_links_between(ids: &[i64]) -> Vec<Arc<RwLock<Link>>> {
+    LINKS.read().unwrap().iter().filter(|link| {
+        let link = link.read().unwrap();
+        ids.contains(&link.get_from_id()) && ids.contains(&link.get_to_id())
+    }).cloned().collect()
+}
+
+//links every node in ids to target, unless it links there already. Ok is how many links were made
+pub fn link_to(ids: &[i64], target: i64, relation: &str, module: &str) -> Result<usize, String> {
+    if ids.is_empty() {
+        return Err(format!("there is nothing to link to node {}", target));
+    }
+    let existing: HashSet<(i64, i64)> = LINKS.read().unwrap().iter()
+        .map(|link| (link.read().unwrap().get_from_id(), link.read().unwrap().get_to_id()))
+        .collect();
+    let mut count = 0;
+    for id in ids.iter().filter(|id| **id != target && !existing.contains(&(**id, target))) {
+        let mut link = Link::create_and_register(*id, target, module.to_string());
+        link.set_relation(relation.to_string());
+        register_link(link);
+        count += 1;
+    }
+    Ok(count)
+}
+
+//runs change on every link between ids. Ok is how many there were
+pub fn change_between(ids: &[i64], change: impl Fn(&mut Link)) -> Result<usize, String> {
+    let links = get_links_between(ids);
+    if links.is_empty() {
+        return Err("there are no links between the selected nodes".to_string());
+    }
+    for link in &links {
+        change(&mut link.write().unwrap());
+    }
+    Ok(links.len())
+}
+
+//plain links ("") first, then the link types of the workspace in their order
+pub fn get_next_type(relation: &str) -> String {
+    let mut names = vec![String::new()];
+    names.extend(workspace::get_settings().link_types.into_iter().map(|link_type| link_type.name));
+    let index = names.iter().position(|name| name == relation).map_or(0, |index| (index + 1) % names.len());
+    names.swap_remove(index)
+}
+
+//every link between ids takes the direction after the one the first of them has
+pub fn cycle_direction(ids: &[i64]) -> Result<LinkDirection, String> {
+    let first = get_links_between(ids).first().map(|link| link.read().unwrap().get_direction());
+    let first = first.ok_or("there are no links between the selected nodes")?;
+    let index = LinkDirection::ALL.iter().position(|direction| *direction == first).unwrap();
+    let direction = LinkDirection::ALL[(index + 1) % LinkDirection::ALL.len()];
+    change_between(ids, |link| link.set_direction(direction))?;
+    Ok(direction)
+}
+
+//solid, dashed, dotted, then whatever their type says again (None)
+pub fn cycle_style(ids: &[i64]) -> Result<Option<LinkStyle>, String> {
+    let first = get_links_between(ids).first().map(|link| link.read().unwrap().get_style());
+    let style = match first.ok_or("there are no links between the selected nodes")? {
+        None => Some(LinkStyle::Solid),
+        Some(style) => LinkStyle::ALL.into_iter().skip_while(|other| *other != style).nth(1),
+    };
+    change_between(ids, |link| link.set_style(style))?;
+    Ok(style)
+}
+
+//removes the links between ids, how many that was
+pub fn remove_between(ids: &[i64]) -> usize {
+    let removed: Vec<i64> = get_links_between(ids).iter().map(|link| link.read().unwrap().get_id()).collect();
+    LINKS.write().unwrap().retain(|link| !removed.contains(&link.read().unwrap().get_id()));
+    removed.len()
+}
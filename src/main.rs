@@ -1,7 +1,19 @@
+mod attachments;
+mod cli;
+mod clipboard;
+mod formats;
+mod headless;
+mod hierarchy;
+mod layout;
+mod links;
+mod membership;
 mod modules;
+mod site;
 mod structs;
+mod tasks;
 mod types;
 mod utils;
+mod workspace;
 
 use crate::g_node_container::generic_node_container::GenericNodeContainer;
 use crate::node_container::node_container::NodeContainer;
@@ -11,7 +23,7 @@ use crate::modules::*;
 use crate::structs::link::Link;
 use crate::structs::module::Module;
 use crate::structs::mouse_position::MousePosition;
-use crate::structs::node::{Node, NODE_COUNTER};
+use crate::structs::node::Node;
 use crate::types::DoublePointerSafe;
 use lazy_static::lazy_static;
 use speedy2d::color::Color;
@@ -19,9 +31,6 @@ use speedy2d::dimen::{UVec2, Vec2, Vector2};
 use speedy2d::shape::{Rect, Rectangle};
 use speedy2d::window::{KeyScancode, MouseButton, MouseScrollDistance, VirtualKeyCode, WindowCreationOptions, WindowHandler, WindowHelper, WindowPosition, WindowSize};
 use speedy2d::{Graphics2D, Window};
-use std::fs::File;
-use std::fs::OpenOptions;
-use std::io::{Read, Write};
 use std::sync::Arc;
 use std::sync::RwLock;
 use std::time::{Duration, Instant, SystemTime};
@@ -39,8 +48,16 @@ lazy_static! {
         modules.iter_mut().for_each(|module| {
             module.write().unwrap().load();
         });
+        *MODULE_NAMES.write().unwrap() = modules.iter().map(|module| {
+            let module = module.read().unwrap();
+            (module.get_name(), module.shows_nodes())
+        }).collect();
         Arc::new(RwLock::new(modules))
     };
+    //names of the MODULES (and whether they show nodes), same order. Looking a module up by name goes through
+    //these and never locks a module, the one handling an event (write locked until it's done) can still find
+    //the others and be found
+    static ref MODULE_NAMES: RwLock<Vec<(String, bool)>> = RwLock::new(Vec::new());
     //double pointer. The main one (first arc/rwlock) is the static one while we modify the second one by replacing it with pointers to other modules.
     static ref ACTIVE_MODULE: DoublePointerSafe<Box<dyn Module+Send+Sync>> = Arc::new(RwLock::new(MODULES.read().unwrap()[0].clone()));
 }
@@ -49,45 +66,23 @@ static REFRESH_RATE: f64 = 165.0*2.0;
 
 fn main() {
 
+    //anything on the command line is a headless subcommand, no window needed
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        std::process::exit(cli::run(&args));
+    }
+
     let window = Window::new_with_options(
         "RMaps", WindowCreationOptions::new_windowed(WindowSize::PhysicalPixels(UVec2::new(1500, 1000)), Some(WindowPosition::Center)).with_vsync(false)).unwrap();
 
     //new_centered("Speedy2D", (2560, 1600)).unwrap();
 
-    //create data folder if it doesn't exist
-    std::fs::create_dir_all("data").unwrap();
-
-    //load nodes from nodes.data
-    let mut file = OpenOptions::new()
-        .read(true)
-        .write(true)
-        .create(true)
-        .open("data/nodes.data")
-        .unwrap();
-    let mut data = String::new();
-    file.read_to_string(&mut data).unwrap();
-
-    let nodes_owned: Vec<Node> = if !data.is_empty() {
-        serde_json::from_str(&data).unwrap()
-    } else {
-        Vec::new()
-    };
-
-    //get id as the maximum id of the nodes
-    nodes_owned.iter().for_each(|node| {
-        if node.get_id() > NODE_COUNTER.load(std::sync::atomic::Ordering::Relaxed) {
-            NODE_COUNTER.store(node.get_id(), std::sync::atomic::Ordering::Relaxed);
-        }
-    });
-    NODE_COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-
-    //refcellize the nodes vector
-    nodes_owned.iter().for_each(|node| {
-        NODES
-            .write()
-            .unwrap()
-            .push(Arc::new(RwLock::new(node.clone())))
-    });
+    //what was wrong in the data shows up in the first module, like anything else that went wrong
+    let problems = workspace::load();
+    if let [problem, others @ ..] = problems.as_slice() {
+        let text = if others.is_empty() { problem.clone() } else { format!("{} (and {} more problems)", problem, others.len()) };
+        ACTIVE_MODULE.read().unwrap().write().unwrap().show_notice(Err(text));
+    }
 
     window.run_loop(RMaps {
         mouse_position: Vector2 { x: 0.0, y: 0.0 },
@@ -115,22 +110,7 @@ struct RMaps {
 
 impl Drop for RMaps {
     fn drop(&mut self) {
-        for module in MODULES.read().unwrap().iter() {
-            println!("unloading module \"{}\"", module.read().unwrap().get_name());
-            module.write().unwrap().unload();
-        }
-
-        //save nodes to data/nodes.data using serde
-
-        let mut file = File::create("data/nodes.data").unwrap();
-
-        let mut nodes = Vec::new();
-        for node in NODES.read().unwrap().iter() {
-            nodes.push(node.read().unwrap().to_owned());
-        }
-
-        let serialized = serde_json::to_string(&nodes).unwrap();
-        file.write_all(serialized.as_bytes()).unwrap();
+        workspace::save();
     }
 }
 
@@ -154,6 +134,18 @@ impl WindowHandler for RMaps {
 
         graphics.clear_screen(Color::from_rgb(0.8, 0.9, 1.0));
 
+        //a module asked to show a node in another one
+        if let Some((module, id)) = membership::take_show_request() {
+            ACTIVE_MODULE.write().unwrap().write().unwrap().close();
+            module.write().unwrap().open();
+            *ACTIVE_MODULE.write().unwrap() = module.clone();
+            let mut module = module.write().unwrap();
+            if !module.show_node(id) {
+                let text = format!("{} can't show node {}", module.get_name(), id);
+                module.show_notice(Err(text));
+            }
+        }
+
         let top_panel_height = window_size.1 * top_panel::DEFAULT_HEIGHT_RATIO;
         let side_panel_width = window_size.0 * side_panel::DEFAULT_WIDTH_RATIO;
         let offset = Vec2::new(side_panel_width, top_panel_height);
@@ -175,8 +167,9 @@ impl WindowHandler for RMaps {
             helper,
             graphics,
         );
+        let breadcrumbs = ACTIVE_MODULE.read().unwrap().read().unwrap().get_breadcrumbs();
         self.top_panel
-            .draw((window_size.0, top_panel_height), (0.0, 0.0), graphics);
+            .draw((window_size.0, top_panel_height), (0.0, 0.0), graphics, &breadcrumbs);
 
         //end fps counter and wait to sync for refresh rate
         let end = Instant::now();
@@ -228,8 +221,11 @@ impl WindowHandler for RMaps {
             self.side_panel
                 .handle_click(self.mouse_position, self.click_count_down, button);
         } else if self.top_panel.get_bounds().contains(self.mouse_position) {
-            self.top_panel
+            let breadcrumb = self.top_panel
                 .handle_click(self.mouse_position, self.click_count_down, button);
+            if let Some(index) = breadcrumb {
+                ACTIVE_MODULE.read().unwrap().write().unwrap().open_breadcrumb(index);
+            }
         } else {
             ACTIVE_MODULE.read().unwrap().write().unwrap().handle_mouse_down(
                 MousePosition::new(
@@ -260,8 +256,7 @@ impl WindowHandler for RMaps {
             self.side_panel
                 .handle_click(self.mouse_position, self.click_count_up, button);
         } else if self.top_panel.get_bounds().contains(self.mouse_position) {
-            self.top_panel
-                .handle_click(self.mouse_position, self.click_count_up, button);
+            //breadcrumbs open on mouse down
         } else {
             ACTIVE_MODULE.read().unwrap().write().unwrap().handle_mouse_up(
                 MousePosition::new(
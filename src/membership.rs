@@ -0,0 +1,114 @@
+use crate::formats::{find_node, LAYOUT_SPACING};
+use crate::types::ModuleRef;
+use crate::utils::union_rect;
+use crate::workspace;
+use lazy_static::lazy_static;
+use std::sync::Mutex;
+
+//which modules show which nodes. A node isn't owned by the module it was made in: it can be sent to any
+//other module and shows up there too, the same node (edits show everywhere) in a map, an outline, a todo list...
+//every node knows the names of the modules it's in, the modules know where they put it.
+
+const PLACE_BELOW_GAP: f32 = 60.0;
+
+lazy_static! {
+    //the module (by name) and node to bring up next, see request_show
+    static ref SHOW_REQUEST: Mutex<Option<(String, i64)>> = Mutex::new(None);
+}
+
+//the module of that name, or an error naming it
+fn get_module(name: &str) -> Result<ModuleRef, String> {
+    workspace::find_module(Some(name)).ok_or(format!("no module named \"{}\"", name))
+}
+
+//the node shows up in the module too, to the right of what's already there. Ok is the module's name
+pub fn send(id: i64, module: &str) -> Result<String, String> {
+    let node = find_node(id).ok_or(format!("there is no node {}", id))?;
+    let module = get_module(module)?;
+    let name = module.read().unwrap().get_name();
+    if !workspace::shows_nodes(&name) {
+        return Err(format!("{} can't show nodes", name));
+    }
+    if node.read().unwrap().is_in(&name) {
+        return Err(format!("node {} is already in {}", id, name));
+    }
+
+    let position = module.read().unwrap().get_node_bounds().into_values()
+        .reduce(union_rect)
+        .map_or((0.0, 0.0), |bounds| (bounds.right() + LAYOUT_SPACING, bounds.top()));
+    module.write().unwrap().place_node(node.clone(), position, None);
+    node.write().unwrap().add_module(name.clone());
+    Ok(name)
+}
+
+//sends every node in ids to the module. A node that can't go doesn't stop the others: Ok is how many went,
+//Err says why not
+pub fn send_all(ids: &[i64], module: &str) -> Result<usize, String> {
+    if ids.is_empty() {
+        return Err("there are no nodes to send".to_string());
+    }
+    let errors: Vec<String> = ids.iter().filter_map(|id| send(*id, module).err()).collect();
+    match errors.as_slice() {
+        [] => Ok(ids.len()),
+        [error] => Err(error.clone()),
+        [error, others @ ..] => Err(format!("{} (and {} more can't go)", error, others.len())),
+    }
+}
+
+//the module after the one given among the others than asking (the module that will send the nodes) that can
+//show nodes. The first of them when after is None or gone
+pub fn get_next_module(asking: &str, after: Option<&str>) -> Result<String, String> {
+    let names: Vec<String> = workspace::get_module_names().into_iter()
+        .filter(|name| name != asking && workspace::shows_nodes(name))
+        .collect();
+    let index = after
+        .and_then(|after| names.iter().position(|name| name == after))
+        .map_or(0, |index| index + 1);
+    names.get(index % names.len().max(1)).cloned().ok_or("there is no other module that can show nodes".to_string())
+}
+
+//a module showing the node other than the one asking
+pub fn get_other_module(id: i64, asking: &str) -> Result<String, String> {
+    let node = find_node(id).ok_or(format!("there is no node {}", id))?;
+    let node = node.read().unwrap();
+    node.get_modules().iter().find(|module| *module != asking && workspace::shows_nodes(module)).cloned().ok_or(format!("no other module shows node {}", id))
+}
+
+//the module stops showing the node. It stays in the workspace (and in the other modules) either way
+pub fn remove(id: i64, module: &str) -> Result<String, String> {
+    let node = find_node(id).ok_or(format!("there is no node {}", id))?;
+    let module = get_module(module)?;
+    let name = module.read().unwrap().get_name();
+    if !node.write().unwrap().remove_module(&name) {
+        return Err(format!("node {} is not in {}", id, name));
+    }
+    module.write().unwrap().remove_node(id);
+    Ok(name)
+}
+
+//a node made from another one (the next occurrence of a task) shows up below it, in every module it's in
+//but except: the module that made it places the node itself (and is busy doing that, it can't be locked)
+pub fn place_below(id: i64, original: i64, except: Option<&str>) {
+    let node = match find_node(id) {
+        Some(node) => node,
+        None => return,
+    };
+    let modules = node.read().unwrap().get_modules().clone();
+    let others = modules.iter().filter(|name| Some(name.as_str()) != except);
+    for module in others.filter_map(|name| workspace::find_module(Some(name))) {
+        let position = module.read().unwrap().get_node_bounds().get(&original)
+            .map_or((0.0, 0.0), |bounds| (bounds.left(), bounds.bottom() + PLACE_BELOW_GAP));
+        module.write().unwrap().place_node(node.clone(), position, None);
+    }
+}
+
+//asks the window to switch to the module and bring the node up there. Modules can't switch to another one
+//themselves while they're handling an event, the window does it before drawing the next frame
+pub fn request_show(module: &str, id: i64) {
+    *SHOW_REQUEST.lock().unwrap() = Some((module.to_string(), id));
+}
+
+pub fn take_show_request() -> Option<(ModuleRef, i64)> {
+    let (name, id) = SHOW_REQUEST.lock().unwrap().take()?;
+    Some((workspace::find_module(Some(&name))?, id))
+}
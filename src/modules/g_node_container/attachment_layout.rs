@@ -0,0 +1,180 @@
+use crate::attachments::{get_preview, Attachment, Preview};
+use crate::headless::Raster;
+use lazy_static::lazy_static;
+use speedy2d::color::Color;
+use speedy2d::dimen::Vec2;
+use speedy2d::font::{Font, FormattedTextBlock, TextLayout, TextOptions};
+use speedy2d::image::{ImageHandle, ImageDataType, ImageSmoothingMode};
+use speedy2d::shape::{Rect, RoundRect};
+use speedy2d::Graphics2D;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Arc;
+
+//what a node shows of its attachments, below its text: image thumbnails, the first lines of text files and the
+//name of anything else. Like RichTextLayout, positions are relative to the top left corner of the area.
+
+lazy_static! {
+    static ref PREVIEW_BACKGROUND_COLOR: Color = Color::from_hex_rgb(0xF5F3FF);
+    static ref PREVIEW_TEXT_COLOR: Color = Color::from_hex_rgb(0x3B3B3B);
+    static ref NAME_COLOR: Color = Color::from_hex_rgb(0x3D5AA8);
+}
+
+thread_local! {
+    //thumbnails on the gpu, by attachment key, or why one couldn't go there (it isn't tried again).
+    //Image handles can't leave the window's thread
+    static IMAGES: RefCell<HashMap<String, Result<ImageHandle, String>>> = RefCell::new(HashMap::new());
+}
+
+pub const ATTACHMENTS_GAP: f32 = 16.0; //between the text and the attachments, at scale 1
+const IMAGE_SIZE: f32 = 320.0; //longest side of a thumbnail, at scale 1
+const SMALL_TEXT: f32 = 0.5; //of the node's font size
+const ITEM_SPACING: f32 = 10.0; //at scale 1
+const PREVIEW_PADDING: f32 = 8.0; //at scale 1
+
+enum Item {
+    Image {
+        key: String,
+        preview: Arc<Preview>,
+    },
+    Text {
+        lines: Vec<(Rc<FormattedTextBlock>, String)>,
+        line_height: f32,
+        padding: f32,
+    },
+    Name {
+        block: Rc<FormattedTextBlock>,
+        text: String,
+    },
+}
+
+struct LaidOutItem {
+    item: Item,
+    position: Vec2,
+    size: Vec2,
+}
+
+pub struct AttachmentLayout {
+    items: Vec<LaidOutItem>,
+    pub size: Vec2,
+    small_font_size: f32,
+}
+
+//font_size is the node's (already scaled) font size
+pub fn layout(font: &Font, attachments: &[Attachment], font_size: f32, scale: f32) -> AttachmentLayout {
+    let small_font_size = font_size * SMALL_TEXT;
+    let mut items = Vec::new();
+    let mut y = 0.0;
+    let mut width: f32 = 0.0;
+
+    for attachment in attachments {
+        let preview = get_preview(attachment);
+        let key = attachment.get_key();
+        //images without a thumbnail (or that didn't make it to the gpu) go by name, saying why
+        let failed = match preview.as_ref() {
+            Preview::NoThumbnail(error) => Some(error.clone()),
+            _ => IMAGES.with(|images| images.borrow().get(&key).and_then(|image| image.as_ref().err().cloned())),
+        };
+        let (item, size) = match (preview.as_ref(), failed) {
+            (_, Some(error)) => {
+                let text = format!("{} ({})", attachment.get_name(), error);
+                let block = font.layout_text(&text, small_font_size, TextOptions::new());
+                let size = Vec2::new(block.width(), block.height());
+                (Item::Name { block, text }, size)
+            }
+            (Preview::Image { width, height, .. }, None) => {
+                let longest = (*width).max(*height) as f32;
+                let size = Vec2::new(*width as f32, *height as f32) * (IMAGE_SIZE * scale / longest);
+                (Item::Image { key, preview: preview.clone() }, size)
+            }
+            (Preview::Text(text), _) => {
+                let padding = PREVIEW_PADDING * scale;
+                let line_height = font.layout_text("Ag", small_font_size, TextOptions::new()).height();
+                let lines: Vec<(Rc<FormattedTextBlock>, String)> = text.iter()
+                    .map(|line| (font.layout_text(line, small_font_size, TextOptions::new()), line.clone()))
+                    .collect();
+                let text_width = lines.iter().map(|(block, _)| block.width()).fold(0.0, f32::max);
+                let size = Vec2::new(text_width + padding * 2.0, lines.len() as f32 * line_height + padding * 2.0);
+                (Item::Text { lines, line_height, padding }, size)
+            }
+            (Preview::Nothing, _) | (Preview::NoThumbnail(_), None) => {
+                let text = attachment.get_name().to_string();
+                let block = font.layout_text(&text, small_font_size, TextOptions::new());
+                let size = Vec2::new(block.width(), block.height());
+                (Item::Name { block, text }, size)
+            }
+        };
+        items.push(LaidOutItem { item, position: Vec2::new(0.0, y), size });
+        y += size.y + ITEM_SPACING * scale;
+        width = width.max(size.x);
+    }
+
+    let height = if items.is_empty() { 0.0 } else { y - ITEM_SPACING * scale };
+    AttachmentLayout { items, size: Vec2::new(width, height), small_font_size }
+}
+
+impl AttachmentLayout {
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    fn get_background(position: Vec2, size: Vec2, padding: f32) -> RoundRect {
+        RoundRect::new(position, position + size, padding)
+    }
+
+    //position is where the top left corner of the area goes
+    pub fn draw(&self, graphics: &mut Graphics2D, position: Vec2) {
+        for laid_out in &self.items {
+            let top_left = position + laid_out.position;
+            match &laid_out.item {
+                Item::Image { key, preview } => {
+                    let (width, height, pixels) = match preview.as_ref() {
+                        Preview::Image { width, height, pixels } => (*width, *height, pixels),
+                        _ => continue,
+                    };
+                    IMAGES.with(|images| {
+                        let mut images = images.borrow_mut();
+                        let image = images.entry(key.clone()).or_insert_with(|| {
+                            graphics.create_image_from_raw_pixels(ImageDataType::RGBA, ImageSmoothingMode::Linear, (width, height), pixels)
+                                .map_err(|error| format!("can't show the thumbnail: {:?}", error))
+                        });
+                        //a failed one shows up by name from the next layout on
+                        if let Ok(image) = image {
+                            graphics.draw_rectangle_image(Rect::new(top_left, top_left + laid_out.size), image);
+                        }
+                    });
+                }
+                Item::Text { lines, line_height, padding } => {
+                    graphics.draw_rounded_rectangle(Self::get_background(top_left, laid_out.size, *padding), *PREVIEW_BACKGROUND_COLOR);
+                    for (index, (block, _)) in lines.iter().enumerate() {
+                        let line_position = top_left + Vec2::new(*padding, *padding + index as f32 * line_height);
+                        graphics.draw_text(line_position, *PREVIEW_TEXT_COLOR, block);
+                    }
+                }
+                Item::Name { block, .. } => graphics.draw_text(top_left, *NAME_COLOR, block),
+            }
+        }
+    }
+
+    pub fn draw_headless(&self, raster: &mut Raster, position: Vec2) {
+        for laid_out in &self.items {
+            let top_left = position + laid_out.position;
+            match &laid_out.item {
+                Item::Image { preview, .. } => {
+                    if let Preview::Image { width, height, pixels } = preview.as_ref() {
+                        raster.draw_image(top_left, laid_out.size, *width, *height, pixels);
+                    }
+                }
+                Item::Text { lines, line_height, padding } => {
+                    raster.draw_rounded_rectangle(&Self::get_background(top_left, laid_out.size, *padding), *PREVIEW_BACKGROUND_COLOR);
+                    for (index, (_, text)) in lines.iter().enumerate() {
+                        let line_position = top_left + Vec2::new(*padding, *padding + index as f32 * line_height);
+                        raster.draw_text(line_position, self.small_font_size, *PREVIEW_TEXT_COLOR, text);
+                    }
+                }
+                Item::Name { text, .. } => raster.draw_text(top_left, self.small_font_size, *NAME_COLOR, text),
+            }
+        }
+    }
+}
@@ -4,24 +4,43 @@ use crate::modules::g_node_container::key_bindings::*;
 use crate::structs::module::Module;
 use crate::structs::mouse_position::MousePosition;
 use crate::structs::node::Node;
-use crate::NODES;
+use crate::{LINKS, NODES};
 use lazy_static::lazy_static;
 use speedy2d::color::Color;
 use speedy2d::shape::{Rect, Rectangle};
 use speedy2d::window::{KeyScancode, MouseButton, MouseScrollDistance, VirtualKeyCode};
+use speedy2d::font::Font;
 use speedy2d::Graphics2D;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fs::OpenOptions;
 use std::io::Read;
+use std::path::Path;
 use std::sync::{Arc, RwLock};
 use speedy2d::dimen::Vec2;
-use crate::modules::g_node_container::wrapped_node::NodeWrapper;
+use crate::modules::g_node_container::wrapped_node::{NodeWrapper, FONT};
+use crate::modules::g_node_container::link_shape::LinkShape;
+use crate::modules::g_node_container::node_clipboard;
+use crate::modules::g_node_container::notice::Notice;
+use crate::modules::g_node_container::property_inspector::PropertyInspector;
+use crate::modules::g_node_container::submaps::{self, Level, Submaps};
+use crate::headless::Raster;
+use crate::clipboard;
+use crate::attachments::{self, Attachment};
+use crate::formats::{find_node, mermaid};
+use crate::hierarchy;
+use crate::links;
+use crate::membership;
+use crate::tasks;
+use crate::utils::{percent_decode, shorten, union_rect};
+use crate::workspace;
 
 lazy_static! {
     pub static ref WRAPPED_NODE_BORDER_COLOR: Color = Color::from_hex_rgb(0x8EA7E9);
     pub static ref WRAPPED_NODE_COLOR: Color = Color::from_hex_rgb(0xE5E0FF);
     pub static ref WRAPPED_NODE_SELECTED_COLOR: Color = Color::from_hex_rgb(0x8EA7E9);
-    static ref BACKGROUND_COLOR: Color = Color::from_hex_rgb(0xcad2c5);
+    pub static ref RECENTLY_EDITED_COLOR: Color = Color::from_hex_rgb(0xFFC98B);
+    pub static ref BACKGROUND_COLOR: Color = Color::from_hex_rgb(0xcad2c5);
+    static ref HIERARCHY_LINE_COLOR: Color = Color::from_hex_rgb(0x6B7BB0);
     static ref SELECTION_RECTANGLE_COLOR: Color = Color::from_hex_rgb(0x8EA7E9);
     static ref SELECTION_RECTANGLE_BORDER_COLOR: Color = Color::from_hex_rgb(0x8EA7E9);
 }
@@ -36,6 +55,40 @@ pub const ROUNDED_RECT_BORDER_RADIUS: f32 = 20.0;
 
 pub static FONT_SIZE: f32 = 60.0;
 
+pub const MODULE_NAME: &str = "Generic Node Container";
+
+pub const DIMMED_NODE_FADE: f32 = 0.7; //how far nodes left out by the tag filter fade into the background
+
+const HEADLESS_MARGIN: f32 = 40.0; //empty space around the nodes when rendering without a window
+const HIERARCHY_LINE_WIDTH: f32 = 6.0; //parent to child, at scale 1
+const WEIGHT_STEP: f32 = 0.5; //ctrl+= and ctrl+-
+const BREADCRUMB_LENGTH: usize = 32; //characters of a node's first line in the top panel
+const NEXT_OCCURRENCE_GAP: f32 = 60.0; //between a done recurring task and the next one, below it
+
+//what ctrl+r goes through when highlighting recently changed nodes: (seconds, name)
+const RECENT_WINDOWS: [(i64, &str); 3] = [(3600, "hour"), (86400, "day"), (7 * 86400, "week")];
+
+//ctrl+t: nodes with one of these tags stay as they are, the rest gets dimmed or hidden
+struct TagFilter {
+    tags: BTreeSet<String>, //normalized, see workspace::normalize_tag
+    hide: bool,
+}
+
+fn get_center(rect: &Rect) -> Vec2 {
+    (*rect.top_left() + *rect.bottom_right()) * 0.5
+}
+
+//the links whose both ends are among bounds (node id -> (bounds on screen, parent)), ready to draw
+fn get_link_shapes(font: &Font, bounds: &HashMap<i64, (Rect, Option<i64>)>, scale: f32, dimmed: impl Fn(i64) -> bool) -> Vec<LinkShape> {
+    LINKS.read().unwrap().iter().filter_map(|link| {
+        let link = link.read().unwrap();
+        let (from, _) = bounds.get(&link.get_from_id())?;
+        let (to, _) = bounds.get(&link.get_to_id())?;
+        let dimmed = dimmed(link.get_from_id()) || dimmed(link.get_to_id());
+        LinkShape::new(font, &link, from.clone(), to.clone(), scale, dimmed)
+    }).collect()
+}
+
 pub struct GenericNodeContainer {
     wrapped_nodes: Vec<Arc<RwLock<NodeWrapper>>>,
     viewport: Rect,
@@ -48,6 +101,16 @@ pub struct GenericNodeContainer {
     drag_vector: Option<(Vec2, Vec2)>, //(start, move_vector)
     are_we_moving_nodes: Option<(Vec2, bool)>, //start, did_we_just_start_doing_that
     selection_rectangle: Option<(Vec2, Vec2)>, //(start, end)
+    pressed_keys: Vec<VirtualKeyCode>,
+    recent_highlight: Option<usize>, //index into RECENT_WINDOWS
+    tag_filter: Option<TagFilter>,
+    inspector: Option<PropertyInspector>, //open with ctrl+p
+    link_type: String, //relation of the links ctrl+l makes, picked with ctrl+shift+l ("" is a plain link)
+    send_target: Option<String>, //module ctrl+s sends nodes to, picked with ctrl+shift+s. None: the first other one
+    notice: Option<Notice>, //what the last shortcut did
+
+    //maps inside nodes, see submaps.rs. wrapped_nodes is the one on screen
+    maps: Submaps,
 }
 
 impl GenericNodeContainer {
@@ -62,12 +125,584 @@ impl GenericNodeContainer {
             drag_vector: None,
             are_we_moving_nodes: None,
             selection_rectangle: None,
+            pressed_keys: Vec::new(),
+            recent_highlight: None,
+            tag_filter: None,
+            inspector: None,
+            link_type: String::new(),
+            send_target: None,
+            notice: None,
+            maps: Submaps::new(),
         }
     }
 
     pub fn get_selected_nodes(&self) -> Vec<Arc<RwLock<NodeWrapper>>> {
         self.wrapped_nodes.iter().filter(|wnode| wnode.read().unwrap().selected).map(|wnode| wnode.clone()).collect()
     }
+
+    fn get_selected_ids(&self) -> Vec<i64> {
+        self.get_selected_nodes().iter().map(|wnode| wnode.read().unwrap().get_node_id()).collect()
+    }
+
+    //what a shortcut did (or why it didn't), at the bottom of the window
+    fn notify(&mut self, result: Result<String, String>) {
+        self.notice = Some(match result {
+            Ok(text) => Notice::new(text, false),
+            Err(text) => Notice::new(text, true),
+        });
+    }
+
+    fn is_control_down(&self) -> bool {
+        self.pressed_keys.contains(&VirtualKeyCode::LControl) || self.pressed_keys.contains(&VirtualKeyCode::RControl)
+    }
+
+    fn is_shift_down(&self) -> bool {
+        self.pressed_keys.contains(&VirtualKeyCode::LShift) || self.pressed_keys.contains(&VirtualKeyCode::RShift)
+    }
+
+    //shortcuts that work when no node is being edited
+    fn handle_shortcut(&mut self, key: VirtualKeyCode) {
+        if self.handle_tree_shortcut(key) || self.handle_map_shortcut(key) {
+            return;
+        }
+        if !self.is_control_down() || self.handle_link_shortcut(key) || self.handle_membership_shortcut(key) || self.handle_task_shortcut(key) {
+            return;
+        }
+        match key {
+            //ctrl+m: the selected node and everything below it as a mermaid mindmap
+            //ctrl+shift+m: the selected nodes and their links as a mermaid flowchart
+            VirtualKeyCode::M => self.notify(self.copy_as_mermaid(self.is_shift_down())),
+            //ctrl+c, ctrl+x, ctrl+v: the selected nodes and the links between them, pasted at the cursor
+            VirtualKeyCode::C => self.notify(self.copy_selection()),
+            VirtualKeyCode::X => {
+                let result = self.copy_selection();
+                let copied = result.is_ok();
+                self.notify(result);
+                if copied {
+                    self.delete_selected_nodes();
+                }
+            }
+            //ctrl+shift+v: attach the files (or urls) on the clipboard to the selected nodes
+            VirtualKeyCode::V => {
+                if self.is_shift_down() {
+                    let result = self.attach_from_clipboard();
+                    self.notify(result);
+                } else {
+                    let result = self.paste();
+                    self.notify(result);
+                }
+            }
+            //ctrl+o: open the attachments of the selected nodes
+            VirtualKeyCode::O => self.notify(self.open_attachments()),
+            //ctrl+r: tint the nodes changed in the last hour, day, week, then nothing again
+            VirtualKeyCode::R => self.cycle_recent_highlight(),
+            //ctrl+p: show or hide the properties of the selected node
+            VirtualKeyCode::P => {
+                self.inspector = match self.inspector {
+                    Some(_) => None,
+                    None => Some(PropertyInspector::new()),
+                };
+            }
+            //ctrl+t: filter by the tags of the selected nodes (nothing selected: no filter), ctrl+shift+t: dim or hide
+            VirtualKeyCode::T => {
+                if self.is_shift_down() {
+                    self.toggle_tag_filter_mode();
+                } else {
+                    self.filter_by_selected_tags();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn cycle_recent_highlight(&mut self) {
+        self.recent_highlight = match self.recent_highlight {
+            None => Some(0),
+            Some(index) if index + 1 < RECENT_WINDOWS.len() => Some(index + 1),
+            Some(_) => None,
+        };
+        let text = match self.recent_highlight {
+            Some(index) => format!("highlighting nodes changed in the last {}", RECENT_WINDOWS[index].1),
+            None => "not highlighting recent changes".to_string(),
+        };
+        self.notify(Ok(text));
+    }
+
+    fn filter_by_selected_tags(&mut self) {
+        let tags: BTreeSet<String> = self.get_selected_nodes().iter()
+            .flat_map(|wnode| wnode.read().unwrap().get_node().read().unwrap().get_all_tags())
+            .map(|tag| workspace::normalize_tag(&tag))
+            .collect();
+
+        if tags.is_empty() {
+            if self.get_selected_nodes().is_empty() {
+                self.notify(Ok("not filtering by tags".to_string()));
+            } else {
+                self.notify(Err("the selected nodes have no tags to filter by".to_string()));
+            }
+            self.tag_filter = None;
+            return;
+        }
+
+        self.notify(Ok(format!("showing nodes tagged {}", tags.iter().map(|tag| format!("#{}", tag)).collect::<Vec<_>>().join(" or "))));
+        let hide = self.tag_filter.as_ref().map_or(false, |filter| filter.hide);
+        self.tag_filter = Some(TagFilter { tags, hide });
+        self.deselect_hidden();
+    }
+
+    fn toggle_tag_filter_mode(&mut self) {
+        let result = match &mut self.tag_filter {
+            Some(filter) => {
+                filter.hide = !filter.hide;
+                Ok(format!("{} nodes without those tags", if filter.hide { "hiding" } else { "dimming" }))
+            }
+            None => Err("select some tagged nodes and press ctrl+t to filter by their tags first".to_string()),
+        };
+        self.notify(result);
+        self.deselect_hidden();
+    }
+
+    //ids of the nodes with one of the filter's tags, None without a filter. Read from the tag index, so it follows edits
+    fn get_matching_ids(&self) -> Option<HashSet<i64>> {
+        self.tag_filter.as_ref().map(|filter| filter.tags.iter().flat_map(|tag| workspace::find_tagged(tag)).collect())
+    }
+
+    //the nodes the filter hides can't be clicked or selected either
+    fn get_hidden_ids(&self) -> HashSet<i64> {
+        match (&self.tag_filter, self.get_matching_ids()) {
+            (Some(filter), Some(matching)) if filter.hide => self.wrapped_nodes.iter()
+                .map(|wnode| wnode.read().unwrap().get_node_id())
+                .filter(|id| !matching.contains(id))
+                .collect(),
+            _ => HashSet::new(),
+        }
+    }
+
+    //tab, ctrl+up, ctrl+down and ctrl+j move the selected nodes in the hierarchy, false for other keys
+    fn handle_tree_shortcut(&mut self, key: VirtualKeyCode) -> bool {
+        let ids = self.get_selected_ids();
+        let (control, shift) = (self.is_control_down(), self.is_shift_down());
+        let result = match key {
+            //tab, shift+tab: indent the selected nodes below the sibling before them, or outdent them next to their parent
+            VirtualKeyCode::Tab if shift => {
+                hierarchy::move_all(&ids, hierarchy::outdent, true).map(|count| format!("outdented {} nodes", count))
+            }
+            VirtualKeyCode::Tab => hierarchy::move_all(&ids, hierarchy::indent, false).map(|count| format!("indented {} nodes", count)),
+            //ctrl+up, ctrl+down: move the selected nodes among their siblings
+            VirtualKeyCode::Up if control => {
+                hierarchy::move_all(&ids, |id| hierarchy::reorder(id, -1), false).map(|count| format!("moved {} nodes up", count))
+            }
+            VirtualKeyCode::Down if control => {
+                hierarchy::move_all(&ids, |id| hierarchy::reorder(id, 1), true).map(|count| format!("moved {} nodes down", count))
+            }
+            //ctrl+j: the selected nodes become children of the node under the cursor, ctrl+shift+j: roots
+            VirtualKeyCode::J if control && shift => {
+                hierarchy::move_all(&ids, |id| hierarchy::set_parent(id, None, None), false)
+                    .map(|count| format!("{} nodes are roots now", count))
+            }
+            VirtualKeyCode::J if control => match self.get_node_under_pivot() {
+                Some(parent) => hierarchy::move_all(&ids, |id| hierarchy::set_parent(id, Some(parent), None), false)
+                    .map(|count| format!("{} nodes are below node {} now", count, parent)),
+                None => Err("point at the node that should be the parent of the selected ones".to_string()),
+            },
+            _ => return false,
+        };
+        self.notify(result);
+        true
+    }
+
+    fn get_node_under_pivot(&mut self) -> Option<i64> {
+        let hidden = self.get_hidden_ids();
+        self.wrapped_nodes.iter().find(|wnode| {
+            let mut wnode = wnode.write().unwrap();
+            !wnode.selected
+                && !hidden.contains(&wnode.get_node_id())
+                && wnode.calculate_bounds(&self.original_viewport, &self.viewport).contains(self.pivot)
+        }).map(|wnode| wnode.read().unwrap().get_node_id())
+    }
+
+    //ctrl+ shortcuts for the links of the selected nodes, false for other keys. See links.rs
+    fn handle_link_shortcut(&mut self, key: VirtualKeyCode) -> bool {
+        let ids = self.get_selected_ids();
+        let shift = self.is_shift_down();
+        let result = match key {
+            //ctrl+shift+l: next link type, for new links and the links between the selected nodes
+            VirtualKeyCode::L if shift => {
+                self.link_type = links::get_next_type(&self.link_type);
+                let relation = self.link_type.clone();
+                let kind = if relation.is_empty() { "plain links".to_string() } else { relation.clone() };
+                match links::change_between(&ids, |link| link.set_relation(relation.clone())) {
+                    Ok(count) => Ok(format!("new links are {}, so are the {} between the selected nodes", kind, count)),
+                    //less than two nodes selected, there was nothing to retype
+                    Err(_) if ids.len() < 2 => Ok(format!("new links are {}", kind)),
+                    Err(error) => Err(format!("new links are {}, but {}", kind, error)),
+                }
+            }
+            //ctrl+l: link the selected nodes to the node under the cursor
+            VirtualKeyCode::L => match self.get_node_under_pivot() {
+                Some(target) => links::link_to(&ids, target, &self.link_type, &self.get_name())
+                    .map(|count| format!("made {} links to node {}", count, target)),
+                None => Err("point at the node the selected ones should link to".to_string()),
+            },
+            //ctrl+d: the links between the selected nodes go one way, both ways, no way. ctrl+shift+d: their style
+            VirtualKeyCode::D if shift => {
+                links::cycle_style(&ids).map(|style| format!("links are {}", style.map_or("styled by their type", |style| style.get_name())))
+            }
+            VirtualKeyCode::D => links::cycle_direction(&ids).map(|direction| format!("links are {} now", direction.get_name())),
+            //ctrl+=, ctrl+-: make the links between the selected nodes heavier or lighter
+            VirtualKeyCode::Equals => {
+                links::change_between(&ids, |link| link.set_weight(link.get_weight() + WEIGHT_STEP))
+                    .map(|count| format!("{} links are heavier", count))
+            }
+            VirtualKeyCode::Minus => {
+                links::change_between(&ids, |link| link.set_weight((link.get_weight() - WEIGHT_STEP).max(WEIGHT_STEP)))
+                    .map(|count| format!("{} links are lighter", count))
+            }
+            //ctrl+u: remove the links between the selected nodes
+            VirtualKeyCode::U => Ok(format!("removed {} links", links::remove_between(&ids))),
+            _ => return false,
+        };
+        self.notify(result);
+        true
+    }
+
+    //ctrl+s and ctrl+g: the selected nodes in other modules, see membership.rs. False for other keys
+    fn handle_membership_shortcut(&mut self, key: VirtualKeyCode) -> bool {
+        let ids = self.get_selected_ids();
+        let result = match key {
+            //ctrl+shift+s: which module ctrl+s sends nodes to
+            VirtualKeyCode::S if self.is_shift_down() => membership::get_next_module(MODULE_NAME, self.send_target.as_deref()).map(|target| {
+                let text = format!("ctrl+s sends nodes to {}", target);
+                self.send_target = Some(target);
+                text
+            }),
+            //ctrl+s: the selected nodes show up in another module too
+            VirtualKeyCode::S => {
+                let target = self.send_target.clone().map_or_else(|| membership::get_next_module(MODULE_NAME, None), Ok);
+                target.and_then(|target| membership::send_all(&ids, &target).map(|count| format!("{} nodes are in {} too", count, target)))
+            }
+            //ctrl+g: switch to another module showing the selected node, and show it there
+            VirtualKeyCode::G => match ids.as_slice() {
+                [id] => match membership::get_other_module(*id, MODULE_NAME) {
+                    Ok(module) => {
+                        membership::request_show(&module, *id);
+                        return true;
+                    }
+                    Err(error) => Err(error),
+                },
+                _ => Err("select the one node to show in another module".to_string()),
+            },
+            _ => return false,
+        };
+        self.notify(result);
+        true
+    }
+
+    //ctrl+k: the selected nodes become tasks (or notes again), ctrl+shift+k: their next status. See tasks.rs
+    fn handle_task_shortcut(&mut self, key: VirtualKeyCode) -> bool {
+        if key != VirtualKeyCode::K {
+            return false;
+        }
+        let ids = self.get_selected_ids();
+        let result = if self.is_shift_down() {
+            tasks::cycle_statuses(&ids).map(|changed| {
+                let mut notes = match changed.as_slice() {
+                    [(id, status, _)] => vec![format!("node {} is {}", id, status.get_name())],
+                    _ => vec![format!("{} tasks changed status", changed.len())],
+                };
+                for (id, _, next) in changed {
+                    if let Some(next) = next {
+                        notes.push(self.place_next_occurrence(id, next));
+                    }
+                }
+                notes.join(", ")
+            })
+        } else {
+            tasks::toggle_all(&ids).map(|made| format!("{} nodes are {}", ids.len(), if made { "tasks" } else { "notes again" }))
+        };
+        self.notify(result);
+        true
+    }
+
+    //what clicking a checkbox does, see tasks::toggle
+    fn toggle_task(&mut self, id: i64) {
+        match tasks::toggle(id) {
+            Ok(Some(next)) => {
+                let text = self.place_next_occurrence(id, next);
+                self.notify(Ok(text));
+            }
+            Ok(None) => {}
+            Err(error) => self.notify(Err(error)),
+        }
+    }
+
+    //the next occurrence of a recurring task goes below the done one, here and in the other modules showing it
+    fn place_next_occurrence(&mut self, id: i64, next: i64) -> String {
+        let bounds = self.get_node_bounds().get(&id).cloned();
+        if let (Some(node), Some(bounds)) = (find_node(next), bounds) {
+            self.place_node(node, (bounds.left(), bounds.bottom() + NEXT_OCCURRENCE_GAP), None);
+        }
+        membership::place_below(next, id, Some(MODULE_NAME));
+        format!("node {} comes back as node {}", id, next)
+    }
+
+    //puts the map on screen away and brings up the one at the end of path, making it if that node had none
+    fn open_level(&mut self, path: Vec<i64>) {
+        if path == *self.maps.get_path() {
+            return;
+        }
+        let current = Level {
+            wrapped_nodes: std::mem::take(&mut self.wrapped_nodes),
+            viewport: Some(self.target_viewport.clone()),
+        };
+        let origin = *self.original_viewport.top_left() + Vec2::new(WRAPPED_NODE_PADDING, WRAPPED_NODE_PADDING) * 4.0;
+        let level = self.maps.switch(current, path, origin);
+        self.wrapped_nodes = level.wrapped_nodes;
+        for wnode in &self.wrapped_nodes {
+            wnode.write().unwrap().selected = false;
+        }
+        //each map comes back the way it was left, new ones with the whole view
+        self.target_viewport = level.viewport.unwrap_or(self.original_viewport.clone());
+        self.viewport = self.target_viewport.clone();
+
+        self.node_editor = None;
+        self.drag_vector = None;
+        self.are_we_moving_nodes = None;
+        self.selection_rectangle = None;
+    }
+
+    fn enter(&mut self, id: i64) {
+        self.open_level(self.maps.get_path_into(id));
+    }
+
+    //f2, escape and ctrl+enter: editing the selected node, going into its map and back out. False for other keys
+    fn handle_map_shortcut(&mut self, key: VirtualKeyCode) -> bool {
+        let selected = self.get_selected_nodes();
+        match key {
+            //f2: edit the selected node
+            VirtualKeyCode::F2 => match selected.as_slice() {
+                [wnode] => self.node_editor = Some(GenericNodeEditor::new(wnode.clone())),
+                _ => self.notify(Err("select the one node to edit".to_string())),
+            },
+            //escape: back to the map above
+            VirtualKeyCode::Escape if self.maps.get_portal().is_some() => {
+                let mut path = self.maps.get_path().clone();
+                path.pop();
+                self.open_level(path);
+            }
+            //ctrl+enter: open the map inside the selected node, making one from its children if it has none
+            VirtualKeyCode::Return if self.is_control_down() => match selected.as_slice() {
+                [wnode] => {
+                    let id = wnode.read().unwrap().get_node_id();
+                    self.enter(id);
+                }
+                _ => self.notify(Err("select the one node to open the map of".to_string())),
+            },
+            _ => return false,
+        }
+        true
+    }
+
+    //selects just the wrappers of that node, if it's on this map
+    fn select_node(&mut self, id: i64) {
+        let mut found = false;
+        for wnode in &self.wrapped_nodes {
+            let mut wnode = wnode.write().unwrap();
+            wnode.selected = wnode.get_node_id() == id;
+            found |= wnode.selected;
+        }
+        if !found {
+            self.notify(Err(format!("node {} is not on this map", id)));
+        }
+        self.deselect_hidden();
+    }
+
+    fn deselect_hidden(&mut self) {
+        let hidden = self.get_hidden_ids();
+        for wnode in &self.wrapped_nodes {
+            let mut wnode = wnode.write().unwrap();
+            if hidden.contains(&wnode.get_node_id()) {
+                wnode.selected = false;
+            }
+        }
+    }
+
+    //mouse position in the window -> position in the layout
+    fn to_layout(&self, position: Vec2) -> Vec2 {
+        let x = (position.x - self.viewport.top_left().x) * self.original_viewport.width() / self.viewport.width() + self.original_viewport.top_left().x;
+        let y = (position.y - self.viewport.top_left().y) * self.original_viewport.height() / self.viewport.height() + self.original_viewport.top_left().y;
+        Vec2::new(x, y)
+    }
+
+    //Err if there was nothing to copy or the clipboard didn't take it
+    fn copy_selection(&self) -> Result<String, String> {
+        let copied: Vec<_> = self.get_selected_nodes().iter().map(|wnode| {
+            let wnode = wnode.read().unwrap();
+            let node = wnode.get_node().read().unwrap().clone();
+            (node, wnode.get_position(), wnode.get_size())
+        }).collect();
+
+        if copied.is_empty() {
+            return Err("select some nodes to copy them".to_string());
+        }
+
+        let count = copied.len();
+        clipboard::set_text(node_clipboard::to_text(copied)).map_err(|error| format!("can't copy to the clipboard: {}", error))?;
+        Ok(format!("copied {} nodes to the clipboard", count))
+    }
+
+    fn paste(&mut self) -> Result<String, String> {
+        let text = clipboard::get_text().map_err(|error| format!("can't paste from the clipboard: {}", error))?;
+
+        //the pasted nodes end up selected, ready to be moved around
+        for wnode in &self.wrapped_nodes {
+            wnode.write().unwrap().selected = false;
+        }
+
+        let origin = self.to_layout(self.pivot);
+        let (pasted, error) = node_clipboard::from_text(&text, &self.get_name());
+        let mut errors: Vec<String> = error.into_iter().collect();
+        let count = pasted.len();
+        for pasted_node in pasted {
+            let (id, parent) = {
+                let node = pasted_node.node.read().unwrap();
+                (node.get_id(), node.get_parent())
+            };
+            //pasted into a node's map, the pasted roots go below that node. The ones that can't stay roots
+            if let (Some(portal), None) = (self.maps.get_portal(), parent) {
+                if let Err(error) = hierarchy::set_parent(id, Some(portal), None) {
+                    errors.push(format!("node {} stays a root: {}", id, error));
+                }
+            }
+            let mut wrapped_node = NodeWrapper::new(pasted_node.node, (origin.x + pasted_node.position.0, origin.y + pasted_node.position.1));
+            wrapped_node.set_size(pasted_node.size);
+            wrapped_node.selected = true;
+            self.wrapped_nodes.push(Arc::new(RwLock::new(wrapped_node)));
+        }
+        match errors.as_slice() {
+            [] => Ok(format!("pasted {} nodes", count)),
+            [error] => Err(error.clone()),
+            [error, others @ ..] => Err(format!("{} (and {} more problems)", error, others.len())),
+        }
+    }
+
+    //file managers copy files as file:// urls, one per line. Plain paths work too, other urls are attached as they are
+    fn attach_from_clipboard(&mut self) -> Result<String, String> {
+        let selected = self.get_selected_nodes();
+        if selected.is_empty() {
+            return Err("select the nodes to attach files to first".to_string());
+        }
+        let text = clipboard::get_text().map_err(|error| format!("can't read the clipboard: {}", error))?;
+
+        let mut attached = 0;
+        let mut errors = Vec::new();
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let attachment = match line.strip_prefix("file://") {
+                Some(path) => attachments::store(&percent_decode(path)),
+                None if line.contains("://") => Ok(Attachment::External(line.to_string())),
+                None if Path::new(line).is_file() => attachments::store(line),
+                None => continue,
+            };
+            match attachment {
+                Ok(attachment) => {
+                    for wnode in &selected {
+                        wnode.read().unwrap().get_node().write().unwrap().add_attachment(attachment.clone());
+                    }
+                    attached += 1;
+                }
+                Err(error) => errors.push(error),
+            }
+        }
+        match (attached, errors.first()) {
+            (_, Some(error)) => Err(error.clone()),
+            (0, None) => Err("the clipboard has no files or urls to attach".to_string()),
+            _ => Ok(format!("attached {} files to {} nodes", attached, selected.len())),
+        }
+    }
+
+    fn open_attachments(&self) -> Result<String, String> {
+        let attachments: Vec<Attachment> = self.get_selected_nodes().iter()
+            .flat_map(|wnode| wnode.read().unwrap().get_node().read().unwrap().get_attachments().clone())
+            .collect();
+        if attachments.is_empty() {
+            return Err("the selected nodes have no attachments".to_string());
+        }
+        //one that can't be opened doesn't keep the others closed
+        let errors: Vec<String> = attachments.iter().filter_map(|attachment| attachments::open(attachment).err()).collect();
+        match errors.first() {
+            Some(error) => Err(error.clone()),
+            None => Ok(format!("opened {} attachments", attachments.len())),
+        }
+    }
+
+    //removes the selected nodes from the workspace, and the links going to or from them. Their children move up.
+    //nodes that other modules show too only leave this one
+    fn delete_selected_nodes(&mut self) {
+        //the nodes whose maps we're inside have to stay
+        let mut kept = Vec::new();
+        for wnode in self.get_selected_nodes() {
+            let mut wnode = wnode.write().unwrap();
+            if self.maps.get_path().contains(&wnode.get_node_id()) {
+                kept.push(format!("node {} can't go while its map is open", wnode.get_node_id()));
+                wnode.selected = false;
+            }
+        }
+        let mut ids = Vec::new();
+        let mut left = 0;
+        for wnode in self.get_selected_nodes() {
+            let (id, node) = {
+                let wnode = wnode.read().unwrap();
+                (wnode.get_node_id(), wnode.get_node())
+            };
+            let mut node = node.write().unwrap();
+            node.remove_module(MODULE_NAME);
+            if node.get_modules().is_empty() {
+                ids.push(id);
+            } else {
+                left += 1;
+                self.remove_node(id);
+            }
+        }
+        if left > 0 {
+            kept.push(format!("{} nodes left this module but stay in others", left));
+        }
+        let mut problems = Vec::new();
+        ids.iter().for_each(|id| {
+            self.remove_node(*id);
+            workspace::unindex_node(*id);
+            problems.extend(hierarchy::remove(*id));
+        });
+        NODES.write().unwrap().retain(|node| !ids.contains(&node.read().unwrap().get_id()));
+        LINKS.write().unwrap().retain(|link| {
+            let link = link.read().unwrap();
+            !ids.contains(&link.get_from_id()) && !ids.contains(&link.get_to_id())
+        });
+        if !problems.is_empty() {
+            problems.extend(kept);
+            self.notify(Err(problems.join(", ")));
+        } else if !kept.is_empty() {
+            self.notify(Ok(kept.join(", ")));
+        }
+    }
+
+    fn copy_as_mermaid(&self, flowchart: bool) -> Result<String, String> {
+        let ids = self.get_selected_ids();
+
+        let (diagram, nodes, edges) = if flowchart {
+            if ids.is_empty() {
+                return Err("select some nodes to copy them as a flowchart".to_string());
+            }
+            mermaid::flowchart(&ids)
+        } else {
+            match ids.as_slice() {
+                [root] => mermaid::mindmap(*root).ok_or(format!("there is no node {}", root))?,
+                _ => return Err("select exactly one node to copy it as a mindmap".to_string()),
+            }
+        };
+
+        clipboard::set_text(format!("```mermaid\n{}```\n", diagram)).map_err(|error| format!("can't copy to the clipboard: {}", error))?;
+        Ok(format!("copied a mermaid {} of {} nodes and {} edges to the clipboard", if flowchart { "flowchart" } else { "mindmap" }, nodes, edges))
+    }
 }
 
 impl Module for GenericNodeContainer {
@@ -102,6 +737,14 @@ impl Module for GenericNodeContainer {
                 }
             }
         }
+
+        //always starts at the root map
+        self.maps = Submaps::load();
+
+        //whatever is on one of the maps is in this module, even if the data says otherwise
+        for wnode in self.maps.get_wrapped_nodes().chain(self.wrapped_nodes.iter()) {
+            wnode.read().unwrap().get_node().write().unwrap().add_module(MODULE_NAME.to_string());
+        }
     }
 
     fn unload(&mut self) {
@@ -112,16 +755,33 @@ impl Module for GenericNodeContainer {
             .truncate(true)
             .open("data/generic_node_container.data")
             .unwrap();
-        let vector: Vec<NodeWrapper> = self
-            .wrapped_nodes
-            .iter_mut()
-            .map(|wnode| wnode.read().unwrap().clone())
-            .collect();
+        let (vector, maps) = self.maps.get_all(&self.wrapped_nodes);
         serde_json::to_writer(file, &vector).unwrap();
+        submaps::save(maps);
     }
 
     fn get_name(&self) -> String {
-        "Generic Node Container".to_string()
+        MODULE_NAME.to_string()
+    }
+
+    fn shows_nodes(&self) -> bool {
+        true
+    }
+
+    fn get_breadcrumbs(&self) -> Vec<String> {
+        let mut breadcrumbs = vec![self.get_name()];
+        for id in self.maps.get_path() {
+            let title = find_node(*id)
+                .map(|node| node.read().unwrap().get_content().lines().next().unwrap_or_default().to_string())
+                .unwrap_or_default();
+            breadcrumbs.push(shorten(&title, BREADCRUMB_LENGTH));
+        }
+        breadcrumbs
+    }
+
+    fn open_breadcrumb(&mut self, index: usize) {
+        let path = self.maps.get_path()[..index.min(self.maps.get_path().len())].to_vec();
+        self.open_level(path);
     }
 
     fn draw(&mut self, graphics: &mut Graphics2D, viewport: Rect, delta_time: f64) {
@@ -176,21 +836,160 @@ impl Module for GenericNodeContainer {
             )
         }*/
 
+        //parents and children, then links, under the nodes
+        let hidden = self.get_hidden_ids();
+        let matching = self.get_matching_ids();
+        let scale = self.viewport.height() / viewport.height();
+        let bounds: HashMap<i64, (Rect, Option<i64>)> = self.wrapped_nodes.iter()
+            .filter(|wnode| !hidden.contains(&wnode.read().unwrap().get_node_id()))
+            .map(|wnode| {
+                let mut wnode = wnode.write().unwrap();
+                let rect = wnode.calculate_bounds(&viewport, &self.viewport);
+                let parent = wnode.get_node().read().unwrap().get_parent();
+                (wnode.get_node_id(), (Rect::new(*rect.top_left(), *rect.bottom_right()), parent))
+            })
+            .collect();
+        for (rect, parent) in bounds.values() {
+            if let Some((parent_rect, _)) = parent.and_then(|parent| bounds.get(&parent)) {
+                graphics.draw_line(get_center(parent_rect), get_center(rect), HIERARCHY_LINE_WIDTH*scale, *HIERARCHY_LINE_COLOR);
+            }
+        }
+        let dimmed = |id: i64| matching.as_ref().map_or(false, |matching| !matching.contains(&id));
+        for shape in FONT.with(|font| get_link_shapes(font, &bounds, scale, dimmed)) {
+            shape.draw(graphics);
+        }
+
+        let recent = self.recent_highlight.map(|index| RECENT_WINDOWS[index].0);
+        let portals = self.maps.get_portals();
+        let hide = self.tag_filter.as_ref().map_or(false, |filter| filter.hide);
         for wrapped_node in &self.wrapped_nodes {
+            let mut wrapped_node = wrapped_node.write().unwrap();
+            let left_out = matching.as_ref().map_or(false, |matching| !matching.contains(&wrapped_node.get_node_id()));
+            if left_out && hide {
+                continue;
+            }
 
-            wrapped_node
-                .write()
-                .unwrap()
-                .draw(graphics, &viewport, &self.viewport);
+            let portal = portals.contains(&wrapped_node.get_node_id());
+            wrapped_node.draw(graphics, &viewport, &self.viewport, recent, left_out, portal);
         }
 
         if let Some(node_editor) = &mut self.node_editor {
             node_editor.draw(graphics);
         }
 
+        let selected = self.get_selected_nodes();
+        if let Some(inspector) = &mut self.inspector {
+            let node = match selected.as_slice() {
+                [wnode] => Some(wnode.read().unwrap().get_node()),
+                _ => None,
+            };
+            inspector.set_node(node);
+            inspector.draw(graphics, viewport.clone());
+        }
+
+        if self.notice.as_ref().map_or(false, |notice| notice.is_over()) {
+            self.notice = None;
+        }
+        if let Some(notice) = &self.notice {
+            notice.draw(graphics, viewport);
+        }
+
+    }
+
+    fn get_node_bounds(&self) -> HashMap<i64, Rect> {
+        self.wrapped_nodes.iter().map(|wnode| {
+            let wnode = wnode.read().unwrap();
+            (wnode.get_node_id(), wnode.get_layout_bounds())
+        }).collect()
+    }
+
+    fn place_node(&mut self, node: Arc<RwLock<Node>>, position: (f32, f32), size: Option<(f32, f32)>) {
+        //wrapped nodes are positioned by their text, which sits inside the border and the padding
+        let frame = WRAPPED_NODE_BORDER_SIZE + WRAPPED_NODE_PADDING;
+        let mut wrapped_node = NodeWrapper::new(node, (position.0 + frame, position.1 + frame));
+        wrapped_node.set_size(size);
+        self.wrapped_nodes.push(Arc::new(RwLock::new(wrapped_node)));
+    }
+
+    fn remove_node(&mut self, id: i64) {
+        //out of its own map first, if we're in it
+        if let Some(index) = self.maps.get_path().iter().position(|portal| *portal == id) {
+            self.open_level(self.maps.get_path()[..index].to_vec());
+        }
+        self.wrapped_nodes.retain(|wnode| wnode.read().unwrap().get_node_id() != id);
+        self.maps.remove_node(id);
+    }
+
+    fn show_notice(&mut self, result: Result<String, String>) {
+        self.notify(result);
     }
 
-    fn close(&mut self) {}
+    fn show_node(&mut self, id: i64) -> bool {
+        //the map it's on: this one, the root one or the map of some node
+        if !self.wrapped_nodes.iter().any(|wnode| wnode.read().unwrap().get_node_id() == id) {
+            match self.maps.find(id) {
+                Some(path) => self.open_level(path),
+                None => return false,
+            }
+        }
+        self.select_node(id);
+
+        //the node in the middle of the view, unless the view isn't there yet (the module was never drawn)
+        let bounds = self.wrapped_nodes.iter()
+            .find(|wnode| wnode.read().unwrap().get_node_id() == id)
+            .map(|wnode| wnode.read().unwrap().get_layout_bounds());
+        if let (Some(bounds), false) = (bounds, self.original_viewport.is_zero_area()) {
+            let (from, to) = (&self.original_viewport, &self.target_viewport);
+            let offset = get_center(&bounds) - *from.top_left();
+            let top_left = get_center(from) - Vec2::new(offset.x * to.width() / from.width(), offset.y * to.height() / from.height());
+            self.target_viewport = Rect::new(top_left, top_left + to.size());
+        }
+        true
+    }
+
+    fn render_headless(&mut self, scale: f32) -> Result<Raster, String> {
+
+        //area covered by all the nodes, in layout coordinates
+        let bounds = self.wrapped_nodes.iter()
+            .map(|wnode| wnode.read().unwrap().get_layout_bounds())
+            .reduce(union_rect)
+            .unwrap_or(Rect::ZERO);
+
+        let origin = *bounds.top_left() - Vec2::new(HEADLESS_MARGIN, HEADLESS_MARGIN);
+        let width = ((bounds.width() + HEADLESS_MARGIN*2.0) * scale).ceil().max(1.0) as u32;
+        let height = ((bounds.height() + HEADLESS_MARGIN*2.0) * scale).ceil().max(1.0) as u32;
+
+        let mut raster = Raster::new(width, height, *BACKGROUND_COLOR)?;
+        let bounds: HashMap<i64, (Rect, Option<i64>)> = self.wrapped_nodes.iter().map(|wnode| {
+            let wnode = wnode.read().unwrap();
+            let rect = wnode.get_layout_bounds();
+            let parent = wnode.get_node().read().unwrap().get_parent();
+            (wnode.get_node_id(), (Rect::new((*rect.top_left() - origin) * scale, (*rect.bottom_right() - origin) * scale), parent))
+        }).collect();
+        for (rect, parent) in bounds.values() {
+            if let Some((parent_rect, _)) = parent.and_then(|parent| bounds.get(&parent)) {
+                raster.draw_line(get_center(parent_rect), get_center(rect), HIERARCHY_LINE_WIDTH*scale, *HIERARCHY_LINE_COLOR);
+            }
+        }
+        for shape in FONT.with(|font| get_link_shapes(font, &bounds, scale, |_| false)) {
+            shape.draw_headless(&mut raster);
+        }
+        let portals = self.maps.get_portals();
+        for wrapped_node in &self.wrapped_nodes {
+            let wrapped_node = wrapped_node.read().unwrap();
+            wrapped_node.draw_headless(&mut raster, origin, scale, portals.contains(&wrapped_node.get_node_id()));
+        }
+
+        Ok(raster)
+    }
+
+    //keys let go of in another module never reach this one
+    fn close(&mut self) {
+        self.pressed_keys.clear();
+        self.drag_vector = None;
+        self.are_we_moving_nodes = None;
+        self.selection_rectangle = None;
+    }
 
     fn get_active_key_bindings(&self) -> HashMap<Vec<VirtualKeyCode>, Box<dyn Fn()>> {
         let mut map = HashMap::new();
@@ -206,8 +1005,23 @@ impl Module for GenericNodeContainer {
 
     fn handle_mouse_down(&mut self, mouse_position: MousePosition, click_count: i32, button: MouseButton) {
 
+        //clicks on the inspector stay there
+        if let Some(inspector) = &mut self.inspector {
+            if inspector.contains(mouse_position.viewport()) {
+                if button == MouseButton::Left {
+                    if let Some(id) = inspector.handle_click(mouse_position.viewport()) {
+                        self.select_node(id);
+                    }
+                }
+                return;
+            }
+        }
+
+        let hidden = self.get_hidden_ids();
         let collisions: Vec<Arc<RwLock<NodeWrapper>>> = self.wrapped_nodes.iter_mut().filter(|wnode| {
-            wnode.write().unwrap().calculate_bounds(&self.original_viewport, &self.viewport).contains(mouse_position.viewport())
+            let mut wnode = wnode.write().unwrap();
+            !hidden.contains(&wnode.get_node_id())
+                && wnode.calculate_bounds(&self.original_viewport, &self.viewport).contains(mouse_position.viewport())
         }).map(|wnode| wnode.clone()).collect();
 
         match button {
@@ -216,6 +1030,16 @@ impl Module for GenericNodeContainer {
             }
             MouseButton::Left => {
 
+                //checkboxes tick tasks off and nothing else
+                let checked = collisions.iter().find(|wnode| {
+                    wnode.read().unwrap().get_checkbox().map_or(false, |checkbox| checkbox.contains(mouse_position.viewport()))
+                });
+                if let Some(wnode) = checked {
+                    let id = wnode.read().unwrap().get_node_id();
+                    self.toggle_task(id);
+                    return;
+                }
+
                 if collisions.is_empty() {
                     //start selection rectangle
                     self.selection_rectangle = Some((mouse_position.viewport(), mouse_position.viewport()));
@@ -235,7 +1059,13 @@ impl Module for GenericNodeContainer {
                     }
                     2 => {
 
-                        if collisions.len() == 1 {
+                        //portals open into their map, f2 edits them
+                        let id = collisions.first().map(|wnode| wnode.read().unwrap().get_node_id());
+                        if collisions.len() == 1 && self.maps.get_portals().contains(&id.unwrap()) {
+                            self.enter(id.unwrap());
+                        }
+
+                        else if collisions.len() == 1 {
                             self.node_editor = Some(GenericNodeEditor::new(collisions[0].clone()));
                             collisions[0].write().unwrap().selected = true;
                         }
@@ -243,18 +1073,25 @@ impl Module for GenericNodeContainer {
                         else {
 
                             let node = Node::create_and_register("new node".to_string(), self.get_name());
+                            let id = node.get_id();
 
                             //retranslate position to the viewport
-                            let x = (mouse_position.viewport().x - self.viewport.top_left().x) * self.original_viewport.width() / self.viewport.width() + self.original_viewport.top_left().x;
-                            let y = (mouse_position.viewport().y - self.viewport.top_left().y) * self.original_viewport.height() / self.viewport.height() + self.original_viewport.top_left().y;
+                            let position = self.to_layout(mouse_position.viewport());
 
                             //self.pivot = Vec2::new(x, y);
 
                             let wrapped_node = NodeWrapper::new(Arc::new(RwLock::new(node)),
-                                                                (x, y));
+                                                                (position.x, position.y));
 
                             NODES.write().unwrap().push(wrapped_node.get_node());
                             self.wrapped_nodes.push(Arc::new(RwLock::new(wrapped_node)));
+
+                            //what's made inside a node's map goes below that node. If it can't, it stays a root
+                            if let Some(portal) = self.maps.get_portal() {
+                                if let Err(error) = hierarchy::set_parent(id, Some(portal), None) {
+                                    self.notify(Err(format!("node {} stays a root: {}", id, error)));
+                                }
+                            }
                         }
                     }
                     _ => {}
@@ -284,7 +1121,11 @@ impl Module for GenericNodeContainer {
         if button == MouseButton::Left {
 
             if let Some((start, end)) = self.selection_rectangle.take() {
+                let hidden = self.get_hidden_ids();
                 for wrapped_node in &mut self.wrapped_nodes {
+                    if hidden.contains(&wrapped_node.read().unwrap().get_node_id()) {
+                        continue;
+                    }
 
                     let selection_rect = Rect::new(start, end);
                     let top = f32::min(selection_rect.top(), selection_rect.bottom());
@@ -325,8 +1166,11 @@ impl Module for GenericNodeContainer {
             if didwe {
 
                 //check collisions and select
+                let hidden = self.get_hidden_ids();
                 let collisions: Vec<Arc<RwLock<NodeWrapper>>> = self.wrapped_nodes.iter_mut().filter(|wnode| {
-                    wnode.write().unwrap().calculate_bounds(&self.original_viewport, &self.viewport).contains(position.viewport())
+                    let mut wnode = wnode.write().unwrap();
+                    !hidden.contains(&wnode.get_node_id())
+                        && wnode.calculate_bounds(&self.original_viewport, &self.viewport).contains(position.viewport())
                 }).map(|wnode| wnode.clone()).collect();
 
                 for wrapped_node in collisions {
@@ -364,15 +1208,38 @@ impl Module for GenericNodeContainer {
     }
 
     fn handle_key_down(&mut self, _key: Option<VirtualKeyCode>, _scancode: KeyScancode) {
-        if let Some(editor) = &mut self.node_editor {
+        if let Some(key) = _key {
+            if !self.pressed_keys.contains(&key) {
+                self.pressed_keys.push(key);
+            }
+        }
+
+        let (control, shift) = (self.is_control_down(), self.is_shift_down());
+        if let Some(inspector) = self.inspector.as_mut().filter(|inspector| inspector.is_editing()) {
             if let Some(key) = _key {
-                editor.handle_key_down(key);
+                inspector.handle_key_down(key, control);
             }
+        } else if let Some(editor) = &mut self.node_editor {
+            if let Some(key) = _key {
+                if control {
+                    if let Err(error) = editor.handle_shortcut(key, shift) {
+                        self.notify(Err(error));
+                    }
+                } else {
+                    editor.handle_key_down(key);
+                }
+            }
+        } else if let Some(key) = _key {
+            self.handle_shortcut(key);
         }
     }
 
     fn handle_key_up(&mut self, key: Option<VirtualKeyCode>, _scancode: KeyScancode) {
 
+        if let Some(key) = key {
+            self.pressed_keys.retain(|pressed| *pressed != key);
+        }
+
         if let Some(editor) = &mut self.node_editor {
             if let Some(key) = key {
                 editor.handle_key_up(key);
@@ -381,21 +1248,15 @@ impl Module for GenericNodeContainer {
     }
 
     fn handle_char(&mut self, character: char) {
-        if let Some(editor) = &mut self.node_editor {
+        if let Some(inspector) = self.inspector.as_mut().filter(|inspector| inspector.is_editing()) {
+            inspector.handle_char(character);
+        }
+        else if let Some(editor) = &mut self.node_editor {
             editor.insert(character);
         }
-        if character as u8 == 127 {
-            self.wrapped_nodes.retain(|wnode| {
-                if wnode.read().unwrap().selected {
-                    let node = wnode.read().unwrap().get_node();
-                    let id = node.read().unwrap().get_id();
-                    NODES.write().unwrap().retain(|node| node.read().unwrap().get_id() != id);
-                    false
-                }
-                else {
-                    true
-                }
-            });
+        //delete removes the selected nodes, unless it's deleting text in the editor
+        else if character as u8 == 127 {
+            self.delete_selected_nodes();
         }
     }
 
@@ -1,98 +1,194 @@
 use speedy2d::color::Color;
 use speedy2d::dimen::Vec2;
-use speedy2d::font::{Font, TextLayout, TextOptions};
 use speedy2d::shape::Rectangle;
 use speedy2d::window::{VirtualKeyCode};
 use speedy2d::Graphics2D;
-use std::cmp::{max, min};
 use std::sync::{Arc, RwLock};
 use std::time::{SystemTime};
 use lazy_static::lazy_static;
-use crate::modules::g_node_container::generic_node_container::{FONT_SIZE, WRAPPED_NODE_PADDING};
-use crate::modules::g_node_container::wrapped_node::NodeWrapper;
+use crate::clipboard;
+use crate::modules::g_node_container::generic_node_container::WRAPPED_NODE_PADDING;
+use crate::modules::g_node_container::wrapped_node::{NodeWrapper, FONT};
+use crate::structs::rich_text::{ListKind, RichText, Style, StyleFlag};
 
 lazy_static! {
     static ref EDITOR_COLOR: Color = Color::from_int_rgba(0, 0, 0, 255);
 }
 
+//edits the rich text of a node. The cursor and the selection are positions in the text (see RichText)
 pub struct GenericNodeEditor {
     wrapped_node: Arc<RwLock<NodeWrapper>>,
-    cursor_index: i32,
-    selection: Option<(usize, usize)>,
+    cursor_index: usize,
+    selection: Option<(usize, usize)>, //(anchor, cursor)
     last_blink: SystemTime,
     selecting: bool,
+    typing_style: Option<Style>, //picked with a shortcut while nothing was selected, used by the next character
 }
 
 impl GenericNodeEditor {
     pub fn new(node: Arc<RwLock<NodeWrapper>>) -> GenericNodeEditor {
-        GenericNodeEditor {
+        let mut editor = GenericNodeEditor {
             wrapped_node: node.clone(),
-            cursor_index: node.read().unwrap().get_node().read().unwrap().get_content().chars().count() as i32,
+            cursor_index: 0,
             selection: None,
             selecting: false,
             last_blink: SystemTime::now(),
-        }
+            typing_style: None,
+        };
+        editor.cursor_index = editor.get_text().len();
+        editor
     }
 
-    pub fn move_cursor(&mut self, positions: i32) {
-        self.cursor_index += positions;
-        self.cursor_index = max(0, min(self.cursor_index, self.wrapped_node.read().unwrap().get_node().read().unwrap().get_content().chars().count() as i32));
+    fn get_text(&self) -> RichText {
+        self.wrapped_node.read().unwrap().get_node().read().unwrap().get_rich_content().clone()
     }
 
-    pub fn insert(&mut self, character: char) {
-
-        let mut characters = self.wrapped_node.read().unwrap().get_node().read().unwrap().get_content().clone().chars().collect::<Vec<char>>();
-
-        if character == '\u{8}' {
+    fn edit(&self, action: impl FnOnce(&mut RichText)) {
+        let node = self.wrapped_node.read().unwrap().get_node();
+        let mut node = node.write().unwrap();
+        node.edit_content(action);
+    }
 
-            //if selecting, then remove selection
-            if let Some(mut selection) = self.selection {
-                let mut buffer = Vec::new();
+    //selected positions from..to, None if nothing is selected
+    fn get_selected_range(&self) -> Option<(usize, usize)> {
+        let (anchor, cursor) = self.selection?;
+        if anchor == cursor {
+            None
+        } else {
+            Some((anchor.min(cursor), anchor.max(cursor)))
+        }
+    }
 
-                //normalize selection
-                if selection.0 > selection.1 {
-                    selection.1 = std::mem::replace(&mut selection.0, selection.1-1)-1;
-                }
+    pub fn move_cursor(&mut self, positions: i32) {
+        let length = self.get_text().len() as i32;
+        self.cursor_index = (self.cursor_index as i32 + positions).clamp(0, length) as usize;
+        self.typing_style = None;
 
-                for i in 0..characters.len() {
-                    if i >= selection.0 && i <= selection.1 {
-                        continue;
-                    }
-                    buffer.push(characters[i]);
-                }
+        if self.selecting {
+            if let Some(selection) = &mut self.selection {
+                selection.1 = self.cursor_index;
+            }
+        } else {
+            self.selection = None;
+        }
+    }
 
-                self.wrapped_node.read().unwrap().get_node().write().unwrap().set_content(buffer.into_iter().collect::<String>());
-                self.cursor_index = selection.0 as i32;
+    //removes the selected text, true if there was any
+    fn remove_selection(&mut self) -> bool {
+        match self.get_selected_range() {
+            Some((start, end)) => {
+                self.edit(|text| text.remove(start, end));
+                self.cursor_index = start;
                 self.selection = None;
-                return;
+                true
             }
-
-            if self.cursor_index == 0 {
-                return;
+            None => {
+                self.selection = None;
+                false
             }
+        }
+    }
 
-            let mut buffer = Vec::new();
+    pub fn insert(&mut self, character: char) {
 
-            for i in 0..characters.len() {
-                if i == self.cursor_index as usize - 1 {
-                    continue;
+        match character {
+            //backspace
+            '\u{8}' => {
+                if !self.remove_selection() && self.cursor_index > 0 {
+                    let cursor = self.cursor_index;
+                    self.edit(|text| text.remove(cursor - 1, cursor));
+                    self.cursor_index -= 1;
                 }
-                buffer.push(characters[i]);
             }
-
-            self.cursor_index -= 1;
-
-            self.wrapped_node.read().unwrap().get_node().write().unwrap().set_content(buffer.into_iter().collect::<String>());
-            return;
-
+            //delete
+            '\u{7f}' => {
+                if !self.remove_selection() {
+                    let cursor = self.cursor_index;
+                    self.edit(|text| text.remove(cursor, cursor + 1));
+                }
+            }
+            //enter starts a new paragraph (a new list item inside lists)
+            '\r' | '\n' => {
+                self.remove_selection();
+                let cursor = self.cursor_index;
+                self.edit(|text| text.insert(cursor, "\n", Style::default(), None));
+                self.cursor_index += 1;
+            }
+            //the rest of the control characters come with ctrl shortcuts
+            character if character.is_control() => return,
+            character => {
+                self.remove_selection();
+                let cursor = self.cursor_index;
+                let (style, link) = self.get_text().get_style_at(cursor);
+                let style = self.typing_style.take().unwrap_or(style);
+                self.edit(|text| text.insert(cursor, &character.to_string(), style, link));
+                self.cursor_index += 1;
+            }
         }
+        self.typing_style = None;
+    }
 
-        characters.insert(self.cursor_index as usize, character);
+    //turns a style on or off for the selection, or for what gets typed next
+    fn toggle_style(&mut self, flag: StyleFlag) {
+        match self.get_selected_range() {
+            Some((start, end)) => self.edit(|text| text.toggle_style(start, end, flag)),
+            None => {
+                let mut style = self.typing_style.unwrap_or(self.get_text().get_style_at(self.cursor_index).0);
+                style.set(flag, !style.has(flag));
+                self.typing_style = Some(style);
+            }
+        }
+    }
 
-        self.wrapped_node.read().unwrap().get_node().write().unwrap().set_content(characters.into_iter().collect::<String>());
+    //paragraphs the selection (or the cursor) is in
+    fn get_paragraph_range(&self) -> (usize, usize) {
+        self.get_selected_range().unwrap_or((self.cursor_index, self.cursor_index))
+    }
 
-        self.cursor_index += 1;
+    //ctrl+key while editing
+    //Err is why the shortcut did nothing
+    pub fn handle_shortcut(&mut self, key: VirtualKeyCode, shift: bool) -> Result<(), String> {
+        self.last_blink = SystemTime::now();
 
+        match key {
+            VirtualKeyCode::A => {
+                let length = self.get_text().len();
+                self.selection = Some((0, length));
+                self.cursor_index = length;
+            }
+            VirtualKeyCode::B => self.toggle_style(StyleFlag::Bold),
+            VirtualKeyCode::I => self.toggle_style(StyleFlag::Italic),
+            VirtualKeyCode::E => self.toggle_style(StyleFlag::Code),
+            //ctrl+k links the selection to the url on the clipboard, ctrl+shift+k removes the link
+            VirtualKeyCode::K => {
+                let (start, end) = self.get_selected_range().ok_or("select some text to link it")?;
+                if shift {
+                    self.edit(|text| text.set_link(start, end, None));
+                    return Ok(());
+                }
+                match clipboard::get_text().map(|link| link.trim().to_string()) {
+                    Ok(link) if !link.is_empty() && !link.contains('\n') => self.edit(|text| text.set_link(start, end, Some(link))),
+                    Ok(_) => return Err("copy the link target first, the clipboard has no single line of text".to_string()),
+                    Err(error) => return Err(format!("can't read the clipboard: {}", error)),
+                }
+            }
+            //ctrl+l bullet list, ctrl+shift+l numbered list, ctrl+] and ctrl+[ nest list items deeper or less deep
+            VirtualKeyCode::L => {
+                let (start, end) = self.get_paragraph_range();
+                let kind = if shift { ListKind::Numbered } else { ListKind::Bullet };
+                self.edit(|text| text.toggle_list(start, end, kind));
+            }
+            VirtualKeyCode::RBracket => {
+                let (start, end) = self.get_paragraph_range();
+                self.edit(|text| text.change_level(start, end, 1));
+            }
+            VirtualKeyCode::LBracket => {
+                let (start, end) = self.get_paragraph_range();
+                self.edit(|text| text.change_level(start, end, -1));
+            }
+            _ => {}
+        }
+        Ok(())
     }
 
     pub fn handle_key_down(&mut self, key: VirtualKeyCode){
@@ -101,34 +197,22 @@ impl GenericNodeEditor {
         self.last_blink = SystemTime::now();
 
         match key {
-            //handle CTRL+A
-            VirtualKeyCode::LControl | VirtualKeyCode::RControl => {
-
+            VirtualKeyCode::Left => self.move_cursor(-1),
+            VirtualKeyCode::Right => self.move_cursor(1),
+            VirtualKeyCode::Home => {
+                let (_, offset) = self.get_text().locate(self.cursor_index);
+                self.move_cursor(-(offset as i32));
+            }
+            VirtualKeyCode::End => {
+                let text = self.get_text();
+                let (paragraph, offset) = text.locate(self.cursor_index);
+                let length = text.get_paragraphs()[paragraph].get_text().chars().count();
+                self.move_cursor((length - offset) as i32);
             }
-            VirtualKeyCode::Left => {
-                if let Some(mut selection) = self.selection {
-                    selection.1  = self.cursor_index as usize;
-                    self.selection = Some(selection);
-                }
-                if !self.selecting {
-                    self.selection = None;
-                }
-                self.move_cursor(-1);
-            },
-            VirtualKeyCode::Right => {
-                if let Some(mut selection) = self.selection {
-                    selection.1 = self.cursor_index as usize;
-                    self.selection = Some(selection);
-                }
-                if !self.selecting {
-                    self.selection = None;
-                }
-                self.move_cursor(1);
-            },
             VirtualKeyCode::LShift | VirtualKeyCode::RShift => {
                 self.selecting = true;
                 if self.selection.is_none() {
-                    self.selection = Some((self.cursor_index as usize, self.cursor_index as usize));
+                    self.selection = Some((self.cursor_index, self.cursor_index));
                 }
             },
             _ => {}
@@ -154,31 +238,18 @@ impl GenericNodeEditor {
 
         graphics.draw_line(rect.bottom_left()+Vec2::new(0.0, -WRAPPED_NODE_PADDING*scale*0.4),
                            rect.bottom_right()+Vec2::new(0.0, -WRAPPED_NODE_PADDING*scale*0.4), 5.0*scale,
-                           Color::from_int_rgba(0, 0, 0, 255));
-
-        //read formatted data to cursor position
-        let font = Font::new(include_bytes!("../../../res/OpenSans-SemiBold.ttf")).unwrap();
-        let formatted_content = font.layout_text(
-            self.wrapped_node.read().unwrap().get_node().read().unwrap().get_content().chars().take(self.cursor_index as usize).collect::<String>().as_str(),
-            FONT_SIZE*scale,
-            TextOptions::new(),
-        );
-
-        //if selection is not none
-        if let Some(selection) = self.selection {
-            let formatted_content_b = font.layout_text(
-                self.wrapped_node.read().unwrap().get_node().read().unwrap().get_content().chars().take(selection.0).collect::<String>().as_str(),
-                FONT_SIZE*scale,
-                TextOptions::new(),
-            );
-            //draw rectangle from selection
-            graphics.draw_rectangle(
-                Rectangle::new(
-                    rect.top_left() + Vec2::new(formatted_content_b.width(), 0.0),
-                    rect.top_left() + Vec2::new(formatted_content.width(), FONT_SIZE*scale),
-                ),
-                Color::from_int_rgba(0, 0, 255, 100),
-            );
+                           *EDITOR_COLOR);
+
+        let text_layout = self.wrapped_node.read().unwrap().get_text_layout(scale);
+
+        //one rectangle for every paragraph in the selection
+        if let Some((start, end)) = self.get_selected_range() {
+            for (top_left, bottom_right) in FONT.with(|font| text_layout.get_selection(font, start, end)) {
+                graphics.draw_rectangle(
+                    Rectangle::new(*rect.top_left() + top_left, *rect.top_left() + bottom_right),
+                    Color::from_int_rgba(0, 0, 255, 100),
+                );
+            }
         }
 
         //if the time delta is odd, then draw the cursor
@@ -186,10 +257,10 @@ impl GenericNodeEditor {
             return;
         }
 
-        //draw line after the formatted content
-        let a = rect.top_left() + Vec2::new(formatted_content.width(), 0.0);
-        let b = rect.bottom_left() + Vec2::new(formatted_content.width(), 0.0);
-        graphics.draw_line(a, b, 1.0, Color::from_int_rgba(0, 0, 0, 255));
+        let (cursor, height) = FONT.with(|font| text_layout.get_cursor(font, self.cursor_index));
+        let a = *rect.top_left() + cursor;
+        let b = a + Vec2::new(0.0, height);
+        graphics.draw_line(a, b, 1.0, *EDITOR_COLOR);
 
     }
 }
@@ -0,0 +1,209 @@
+use crate::headless::Raster;
+use crate::modules::g_node_container::generic_node_container::{BACKGROUND_COLOR, DIMMED_NODE_FADE, FONT_SIZE};
+use crate::modules::g_node_container::wrapped_node::mix;
+use crate::structs::link::{ArrowHead, Link, LinkDirection, LinkStyle};
+use crate::workspace;
+use lazy_static::lazy_static;
+use speedy2d::color::Color;
+use speedy2d::dimen::Vec2;
+use speedy2d::font::{Font, FormattedTextBlock, TextLayout, TextOptions};
+use speedy2d::shape::Rect;
+use speedy2d::Graphics2D;
+use std::rc::Rc;
+
+//a link as it's drawn on a map, from the border of one node to the border of the other. The type its relation
+//names gives the color, the arrowhead and the style (the link's own style wins), the weight gives the thickness.
+//Worked out in window coordinates, then drawn with Graphics2D or into a Raster.
+
+lazy_static! {
+    static ref LINK_COLOR: Color = Color::from_hex_rgb(0x4A4E69); //links without a (known) type
+}
+
+const LINK_WIDTH: f32 = 4.0; //weight 1, at scale 1
+const ARROW_SIZE: f32 = 26.0; //at scale 1, grows with the weight
+const DASH: (f32, f32) = (24.0, 14.0); //(dash, gap) at scale 1
+const DOT_GAP: f32 = 10.0; //dots are as long as the line is thick
+const LABEL_TEXT: f32 = 0.45; //of the node's font size
+const LABEL_OFFSET: f32 = 8.0; //between the line and its label, at scale 1
+
+//an arrowhead broken down into what both Graphics2D and Raster can draw
+#[derive(Default)]
+struct Head {
+    triangles: Vec<[Vec2; 3]>,
+    circle: Option<(Vec2, f32)>, //(center, radius)
+    strokes: Vec<(Vec2, Vec2)>, //as thick as the line
+}
+
+pub struct LinkShape {
+    start: Vec2,
+    end: Vec2,
+    thickness: f32,
+    color: Color,
+    style: LinkStyle,
+    heads: (ArrowHead, ArrowHead), //(start, end)
+    arrow_size: f32,
+    scale: f32,
+    label: Option<(Rc<FormattedTextBlock>, String, f32)>, //(block, text, font size)
+}
+
+//where the line from the center of bounds towards target leaves bounds, as a fraction of the way
+fn get_exit(bounds: &Rect, direction: Vec2) -> f32 {
+    let half_size = (*bounds.bottom_right() - *bounds.top_left()) * 0.5;
+    let x = if direction.x != 0.0 { half_size.x / direction.x.abs() } else { f32::MAX };
+    let y = if direction.y != 0.0 { half_size.y / direction.y.abs() } else { f32::MAX };
+    x.min(y)
+}
+
+impl LinkShape {
+    //from and to are the bounds of the two nodes on screen. None when they overlap and there's no line to draw
+    pub fn new(font: &Font, link: &Link, from: Rect, to: Rect, scale: f32, dimmed: bool) -> Option<LinkShape> {
+        let from_center = (*from.top_left() + *from.bottom_right()) * 0.5;
+        let to_center = (*to.top_left() + *to.bottom_right()) * 0.5;
+        let direction = to_center - from_center;
+        let (from_exit, to_exit) = (get_exit(&from, direction), get_exit(&to, direction));
+        if from_exit + to_exit >= 1.0 {
+            return None;
+        }
+
+        let link_type = workspace::get_link_type(link.get_relation());
+        let color = link_type.as_ref().and_then(|link_type| link_type.get_rgb()).map_or(*LINK_COLOR, Color::from_hex_rgb);
+        let color = if dimmed { mix(color, *BACKGROUND_COLOR, DIMMED_NODE_FADE) } else { color };
+        let arrow = link_type.as_ref().map_or(ArrowHead::Triangle, |link_type| link_type.arrow);
+        let heads = match link.get_direction() {
+            LinkDirection::Directed => (ArrowHead::None, arrow),
+            LinkDirection::Bidirectional => (arrow, arrow),
+            LinkDirection::Undirected => (ArrowHead::None, ArrowHead::None),
+        };
+        let weight = link.get_weight().clamp(0.25, 4.0);
+
+        let label = Some(link.get_label()).filter(|label| !label.is_empty()).map(|label| {
+            let size = FONT_SIZE * LABEL_TEXT * scale;
+            (font.layout_text(label, size, TextOptions::new()), label.clone(), size)
+        });
+
+        Some(LinkShape {
+            start: from_center + direction * from_exit,
+            end: to_center - direction * to_exit,
+            thickness: LINK_WIDTH * weight * scale,
+            color,
+            style: link.get_style().or(link_type.map(|link_type| link_type.style)).unwrap_or_default(),
+            heads,
+            arrow_size: (ARROW_SIZE + LINK_WIDTH * (weight - 1.0) * 2.0) * scale,
+            scale,
+            label,
+        })
+    }
+
+    fn get_unit(&self) -> Vec2 {
+        let direction = self.end - self.start;
+        direction / direction.magnitude().max(f32::EPSILON)
+    }
+
+    //how far back from its end the line stops so that it doesn't poke through the arrowhead
+    fn get_inset(&self, head: ArrowHead) -> f32 {
+        match head {
+            ArrowHead::Triangle | ArrowHead::Diamond => self.arrow_size * 0.9,
+            ArrowHead::Circle => self.arrow_size * 0.6,
+            ArrowHead::Open | ArrowHead::None => 0.0,
+        }
+    }
+
+    //the pieces of the line itself, cut into dashes or dots by the style
+    fn get_segments(&self) -> Vec<(Vec2, Vec2)> {
+        let unit = self.get_unit();
+        let start = self.start + unit * self.get_inset(self.heads.0);
+        let end = self.end - unit * self.get_inset(self.heads.1);
+        let length = (end - start).magnitude();
+        let (dash, gap) = match self.style {
+            LinkStyle::Solid => return vec![(start, end)],
+            LinkStyle::Dashed => (DASH.0 * self.scale, DASH.1 * self.scale),
+            LinkStyle::Dotted => (self.thickness, DOT_GAP * self.scale + self.thickness),
+        };
+        if dash + gap <= 0.0 {
+            return vec![(start, end)];
+        }
+
+        let mut segments = Vec::new();
+        let mut position = 0.0;
+        while position < length {
+            segments.push((start + unit * position, start + unit * (position + dash).min(length)));
+            position += dash + gap;
+        }
+        segments
+    }
+
+    //the arrowhead with its tip at tip, pointing along unit
+    fn get_head(&self, head: ArrowHead, tip: Vec2, unit: Vec2) -> Head {
+        let size = self.arrow_size;
+        let normal = Vec2::new(-unit.y, unit.x);
+        match head {
+            ArrowHead::Triangle => {
+                let base = tip - unit * size;
+                Head { triangles: vec![[tip, base + normal * (size * 0.5), base - normal * (size * 0.5)]], ..Head::default() }
+            }
+            ArrowHead::Diamond => {
+                let middle = tip - unit * (size * 0.5);
+                let back = tip - unit * size;
+                let side = normal * (size * 0.35);
+                Head { triangles: vec![[tip, middle + side, middle - side], [back, middle + side, middle - side]], ..Head::default() }
+            }
+            ArrowHead::Circle => Head { circle: Some((tip - unit * (size * 0.3), size * 0.3)), ..Head::default() },
+            ArrowHead::Open => {
+                let base = tip - unit * size;
+                Head { strokes: vec![(tip, base + normal * (size * 0.45)), (tip, base - normal * (size * 0.45))], ..Head::default() }
+            }
+            ArrowHead::None => Head::default(),
+        }
+    }
+
+    fn get_heads(&self) -> [Head; 2] {
+        let unit = self.get_unit();
+        [self.get_head(self.heads.0, self.start, unit * -1.0), self.get_head(self.heads.1, self.end, unit)]
+    }
+
+    //top left corner of the label: centered above the middle of the line
+    fn get_label_position(&self, size: Vec2) -> Vec2 {
+        let middle = (self.start + self.end) * 0.5;
+        middle - Vec2::new(size.x * 0.5, size.y + LABEL_OFFSET * self.scale)
+    }
+
+    pub fn draw(&self, graphics: &mut Graphics2D) {
+        for (start, end) in self.get_segments() {
+            graphics.draw_line(start, end, self.thickness, self.color);
+        }
+        for head in self.get_heads() {
+            for triangle in head.triangles {
+                graphics.draw_triangle(triangle, self.color);
+            }
+            if let Some((center, radius)) = head.circle {
+                graphics.draw_circle(center, radius, self.color);
+            }
+            for (start, end) in head.strokes {
+                graphics.draw_line(start, end, self.thickness, self.color);
+            }
+        }
+        if let Some((block, _, _)) = &self.label {
+            graphics.draw_text(self.get_label_position(block.size()), self.color, block);
+        }
+    }
+
+    pub fn draw_headless(&self, raster: &mut Raster) {
+        for (start, end) in self.get_segments() {
+            raster.draw_line(start, end, self.thickness, self.color);
+        }
+        for head in self.get_heads() {
+            for triangle in head.triangles {
+                raster.draw_triangle(triangle, self.color);
+            }
+            if let Some((center, radius)) = head.circle {
+                raster.draw_circle(center, radius, self.color);
+            }
+            for (start, end) in head.strokes {
+                raster.draw_line(start, end, self.thickness, self.color);
+            }
+        }
+        if let Some((block, text, size)) = &self.label {
+            raster.draw_text(self.get_label_position(block.size()), *size, self.color, text);
+        }
+    }
+}
@@ -1,4 +1,12 @@
 pub mod generic_node_container;
+mod attachment_layout;
 mod generic_node_editor;
 mod key_bindings;
+mod link_shape;
+mod node_clipboard;
+mod notice;
+mod property_inspector;
+mod rich_text_layout;
+mod submaps;
+mod task_marks;
 mod wrapped_node;
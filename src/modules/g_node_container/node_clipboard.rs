@@ -0,0 +1,231 @@
+use crate::formats::{register_link, register_node};
+use crate::hierarchy;
+use crate::structs::link::Link;
+use crate::structs::node::Node;
+use crate::structs::property::Property;
+use crate::LINKS;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+
+//copied nodes go on the clipboard as text: a markdown list other apps can use, followed by the rmaps payload
+//(nodes, the links between them and where they were relative to each other) in an html comment, which
+//markdown doesn't show. Copied children stay children of their copied parents.
+//text without the payload pastes as one node per line, list indentation making children.
+
+const PAYLOAD_START: &str = "<!-- rmaps:";
+const PAYLOAD_END: &str = " -->";
+
+//plain text has no positions, pasted lines go one below the other
+const LINE_SPACING: f32 = 160.0;
+const INDENT_SPACING: f32 = 300.0;
+
+#[derive(Serialize, Deserialize)]
+struct CopiedNode {
+    node: Node,
+    position: (f32, f32), //relative to the top left copied node
+    #[serde(default)]
+    size: Option<(f32, f32)>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Payload {
+    nodes: Vec<CopiedNode>,
+    #[serde(default)]
+    links: Vec<Link>, //only the ones between copied nodes
+}
+
+pub struct PastedNode {
+    pub node: Arc<RwLock<Node>>,
+    pub position: (f32, f32), //relative to the paste position
+    pub size: Option<(f32, f32)>,
+}
+
+//(node, position, size) of every copied node
+pub fn to_text(copied: Vec<(Node, (f32, f32), Option<(f32, f32)>)>) -> String {
+    let left = copied.iter().map(|(_, position, _)| position.0).fold(f32::INFINITY, f32::min);
+    let top = copied.iter().map(|(_, position, _)| position.1).fold(f32::INFINITY, f32::min);
+
+    let ids: HashSet<i64> = copied.iter().map(|(node, _, _)| node.get_id()).collect();
+    let links: Vec<Link> = LINKS.read().unwrap().iter()
+        .map(|link| link.read().unwrap().clone())
+        .filter(|link| ids.contains(&link.get_from_id()) && ids.contains(&link.get_to_id()))
+        .collect();
+
+    let markdown = to_markdown(&copied);
+    let payload = Payload {
+        nodes: copied.into_iter().map(|(node, position, size)| CopiedNode {
+            node,
+            position: (position.0 - left, position.1 - top),
+            size,
+        }).collect(),
+        links,
+    };
+
+    //">" only shows up inside json strings, escaping it keeps "-->" in some node from closing the comment
+    let json = serde_json::to_string(&payload).unwrap().replace('>', "\\u003e");
+    format!("{}\n{}{}{}\n", markdown, PAYLOAD_START, json, PAYLOAD_END)
+}
+
+//copied nodes as a list, children nested below their parents
+fn to_markdown(copied: &[(Node, (f32, f32), Option<(f32, f32)>)]) -> String {
+    let contents: HashMap<i64, String> = copied.iter().map(|(node, _, _)| (node.get_id(), node.get_rich_content().to_markdown())).collect();
+    let children: HashMap<i64, Vec<i64>> = copied.iter()
+        .map(|(node, _, _)| (node.get_id(), node.get_children().iter().filter(|child| contents.contains_key(child)).cloned().collect()))
+        .collect();
+
+    //nodes whose parent wasn't copied start a list of their own
+    let mut markdown = String::new();
+    let mut visited = HashSet::new();
+    let roots = copied.iter()
+        .map(|(node, _, _)| node)
+        .filter(|node| !node.get_parent().map_or(false, |parent| contents.contains_key(&parent)));
+    for node in roots {
+        write_markdown_item(&mut markdown, node.get_id(), 0, &contents, &children, &mut visited);
+    }
+    markdown
+}
+
+fn write_markdown_item(
+    markdown: &mut String,
+    id: i64,
+    depth: usize,
+    contents: &HashMap<i64, String>,
+    children: &HashMap<i64, Vec<i64>>,
+    visited: &mut HashSet<i64>,
+) {
+    if !visited.insert(id) {
+        return;
+    }
+    let indent = "  ".repeat(depth);
+    let mut lines = contents[&id].lines();
+    markdown.push_str(&format!("{}- {}\n", indent, lines.next().unwrap_or_default()));
+    for line in lines {
+        markdown.push_str(&format!("{}  {}\n", indent, line));
+    }
+    for child in children.get(&id).into_iter().flatten() {
+        write_markdown_item(markdown, *child, depth + 1, contents, children, visited);
+    }
+}
+
+//registers the nodes (and links) in the text with fresh ids, owned by owner. The error is why the rmaps nodes on
+//the clipboard were pasted as plain text instead
+pub fn from_text(text: &str, owner: &str) -> (Vec<PastedNode>, Option<String>) {
+    match read_payload(text) {
+        Some(Ok(payload)) => (paste_payload(payload, owner), None),
+        Some(Err(error)) => {
+            let error = format!("the rmaps nodes on the clipboard are broken, pasted them as text: {}", error);
+            (paste_plain_text(text, owner), Some(error))
+        }
+        None => (paste_plain_text(text, owner), None),
+    }
+}
+
+fn read_payload(text: &str) -> Option<Result<Payload, serde_json::Error>> {
+    let start = text.find(PAYLOAD_START)? + PAYLOAD_START.len();
+    let end = start + text[start..].find(PAYLOAD_END)?;
+    Some(serde_json::from_str(&text[start..end]))
+}
+
+fn paste_payload(payload: Payload, owner: &str) -> Vec<PastedNode> {
+    let mut new_ids = HashMap::new();
+    let mut copied_children = HashMap::new();
+    let mut pasted = Vec::new();
+    for copied in payload.nodes {
+        copied_children.insert(copied.node.get_id(), copied.node.get_children().clone());
+        let mut node = Node::create_and_register(String::new(), owner.to_string());
+        node.set_rich_content(copied.node.get_rich_content().clone());
+        for tag in copied.node.get_tags() {
+            node.add_tag(tag.clone());
+        }
+        for attachment in copied.node.get_attachments() {
+            node.add_attachment(attachment.clone());
+        }
+        for (key, value) in copied.node.get_properties() {
+            node.set_typed_property(key.clone(), value.clone());
+        }
+        new_ids.insert(copied.node.get_id(), node.get_id());
+        pasted.push(PastedNode {
+            node: register_node(node),
+            position: copied.position,
+            size: copied.size,
+        });
+    }
+
+    for (old_parent, new_parent) in &new_ids {
+        let children: Vec<i64> = copied_children.get(old_parent).into_iter().flatten().filter_map(|child| new_ids.get(child)).cloned().collect();
+        for child in children {
+            hierarchy::set_parent(child, Some(*new_parent), None).unwrap();
+        }
+    }
+
+    //references between copied nodes point to the new copies
+    for pasted in &pasted {
+        let mut node = pasted.node.write().unwrap();
+        let references: Vec<(String, i64)> = node.get_properties().iter()
+            .filter_map(|(key, value)| Some((key.clone(), *new_ids.get(&value.as_node()?)?)))
+            .collect();
+        for (key, id) in references {
+            node.set_typed_property(key, Property::node(id));
+        }
+    }
+
+    for copied in payload.links {
+        if let (Some(from), Some(to)) = (new_ids.get(&copied.get_from_id()), new_ids.get(&copied.get_to_id())) {
+            let mut link = Link::create_and_register(*from, *to, owner.to_string());
+            link.set_label(copied.get_label().clone());
+            link.set_relation(copied.get_relation().clone());
+            link.set_sides(copied.get_sides());
+            link.set_direction(copied.get_direction());
+            link.set_weight(copied.get_weight());
+            link.set_style(copied.get_style());
+            register_link(link);
+        }
+    }
+
+    pasted
+}
+
+//"- item", "* item", "+ item" and "1. item" lose their marker, deeper indented items become children
+fn paste_plain_text(text: &str, owner: &str) -> Vec<PastedNode> {
+    let mut pasted = Vec::new();
+    let mut parents: Vec<(usize, i64)> = Vec::new(); //(indentation, id) of the items above
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        let trimmed = line.trim_start();
+        let indentation = line.len() - trimmed.len();
+        let content = strip_list_marker(trimmed).trim_end().to_string();
+
+        while parents.last().map_or(false, |(parent_indentation, _)| *parent_indentation >= indentation) {
+            parents.pop();
+        }
+
+        let node = register_node(Node::create_and_register(content, owner.to_string()));
+        let id = node.read().unwrap().get_id();
+        if let Some((_, parent)) = parents.last() {
+            hierarchy::set_parent(id, Some(*parent), None).unwrap();
+        }
+
+        pasted.push(PastedNode {
+            node,
+            position: (parents.len() as f32 * INDENT_SPACING, pasted.len() as f32 * LINE_SPACING),
+            size: None,
+        });
+        parents.push((indentation, id));
+    }
+    pasted
+}
+
+fn strip_list_marker(line: &str) -> &str {
+    for marker in ["- ", "* ", "+ "] {
+        if let Some(rest) = line.strip_prefix(marker) {
+            return rest;
+        }
+    }
+    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 {
+        if let Some(rest) = line[digits..].strip_prefix(". ").or(line[digits..].strip_prefix(") ")) {
+            return rest;
+        }
+    }
+    line
+}
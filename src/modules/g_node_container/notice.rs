@@ -0,0 +1,58 @@
+use crate::modules::g_node_container::generic_node_container::BACKGROUND_COLOR;
+use crate::modules::g_node_container::wrapped_node::mix;
+use lazy_static::lazy_static;
+use speedy2d::color::Color;
+use speedy2d::dimen::Vec2;
+use speedy2d::font::{Font, TextLayout, TextOptions};
+use speedy2d::shape::Rect;
+use speedy2d::Graphics2D;
+use std::time::{Duration, Instant};
+
+//one line at the bottom left of the node container saying what the last shortcut did, or why it did nothing.
+//it fades into the background after a few seconds, a newer one takes its place right away
+
+lazy_static! {
+    static ref PANEL_COLOR: Color = Color::from_hex_rgb(0xF5F3FF);
+    static ref TEXT_COLOR: Color = Color::from_hex_rgb(0x1B1B1B);
+    static ref ERROR_COLOR: Color = Color::from_hex_rgb(0xC0392B);
+}
+
+const FONT_SIZE: f32 = 26.0;
+const PADDING: f32 = 12.0;
+const MARGIN: f32 = 16.0; //from the corner of the node container
+const SHOWN_FOR: Duration = Duration::from_secs(4);
+const FADE_FOR: Duration = Duration::from_millis(800); //the end of SHOWN_FOR
+
+pub struct Notice {
+    text: String,
+    error: bool, //why a shortcut did nothing, in red
+    since: Instant,
+    font: Font,
+}
+
+impl Notice {
+    pub fn new(text: String, error: bool) -> Notice {
+        Notice {
+            text,
+            error,
+            since: Instant::now(),
+            font: Font::new(include_bytes!("../../../res/OpenSans-SemiBold.ttf")).unwrap(),
+        }
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.since.elapsed() >= SHOWN_FOR
+    }
+
+    pub fn draw(&self, graphics: &mut Graphics2D, area: Rect) {
+        let left = SHOWN_FOR.saturating_sub(self.since.elapsed());
+        let fade = 1.0 - (left.as_secs_f32() / FADE_FOR.as_secs_f32()).min(1.0);
+
+        let block = self.font.layout_text(&self.text, FONT_SIZE, TextOptions::new());
+        let top_left = Vec2::new(area.left() + MARGIN, area.bottom() - MARGIN - block.height() - PADDING * 2.0);
+        let size = Vec2::new(block.width() + PADDING * 2.0, block.height() + PADDING * 2.0);
+        graphics.draw_rectangle(Rect::new(top_left, top_left + size), mix(*PANEL_COLOR, *BACKGROUND_COLOR, fade));
+        let color = if self.error { *ERROR_COLOR } else { *TEXT_COLOR };
+        graphics.draw_text(top_left + Vec2::new(PADDING, PADDING), mix(color, *BACKGROUND_COLOR, fade), &block);
+    }
+}
@@ -0,0 +1,294 @@
+use crate::clipboard;
+use crate::formats::find_node;
+use crate::structs::node::Node;
+use crate::structs::property::{Property, PropertyKind, PROPERTY_KINDS};
+use crate::utils::shorten;
+use lazy_static::lazy_static;
+use speedy2d::color::Color;
+use speedy2d::dimen::Vec2;
+use speedy2d::font::{Font, TextLayout, TextOptions};
+use speedy2d::shape::Rect;
+use speedy2d::window::VirtualKeyCode;
+use speedy2d::Graphics2D;
+use std::sync::{Arc, RwLock};
+
+//the properties of the selected node, over the right side of the node container (ctrl+p).
+//clicking a value edits it (enter keeps it, escape forgets it), booleans just flip. Clicking the kind switches to
+//the next kind the value fits, "x" removes the property and "->" selects the node a node property points to.
+//new properties are typed as "name = value", their kind is guessed from the value.
+
+lazy_static! {
+    static ref PANEL_COLOR: Color = Color::from_hex_rgb(0xF5F3FF);
+    static ref BORDER_COLOR: Color = Color::from_hex_rgb(0x8EA7E9);
+    static ref TEXT_COLOR: Color = Color::from_hex_rgb(0x1B1B1B);
+    static ref KIND_COLOR: Color = Color::from_hex_rgb(0x6B7BB0);
+    static ref EDITING_COLOR: Color = Color::from_hex_rgb(0xE5E0FF);
+    static ref ERROR_COLOR: Color = Color::from_hex_rgb(0xC0392B);
+}
+
+const WIDTH_RATIO: f32 = 0.3; //of the node container
+const FONT_SIZE: f32 = 26.0;
+const PADDING: f32 = 16.0;
+const COLUMN_GAP: f32 = 12.0;
+const ROW_SPACING: f32 = 8.0;
+const MAX_VALUE_LENGTH: usize = 40; //longer values are cut when not being edited
+
+//what a click on a part of the panel does
+#[derive(Clone)]
+enum Target {
+    Value(String),
+    Kind(String),
+    Remove(String),
+    Follow(i64),
+    Add,
+}
+
+enum Field {
+    Value(String),
+    New,
+}
+
+pub struct PropertyInspector {
+    node: Option<Arc<RwLock<Node>>>,
+    bounds: Rect,
+    hitboxes: Vec<(Rect, Target)>,
+    editing: Option<(Field, String)>,
+    error: Option<String>,
+    font: Font,
+}
+
+impl PropertyInspector {
+    pub fn new() -> PropertyInspector {
+        PropertyInspector {
+            node: None,
+            bounds: Rect::ZERO,
+            hitboxes: Vec::new(),
+            editing: None,
+            error: None,
+            font: Font::new(include_bytes!("../../../res/OpenSans-SemiBold.ttf")).unwrap(),
+        }
+    }
+
+    //the node to show, None when there isn't exactly one selected. Switching nodes drops an unfinished edit
+    pub fn set_node(&mut self, node: Option<Arc<RwLock<Node>>>) {
+        let same = match (&self.node, &node) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        };
+        if !same {
+            self.editing = None;
+            self.error = None;
+        }
+        self.node = node;
+    }
+
+    pub fn contains(&self, position: Vec2) -> bool {
+        self.bounds.contains(position)
+    }
+
+    pub fn is_editing(&self) -> bool {
+        self.editing.is_some()
+    }
+
+    fn draw_text(&mut self, graphics: &mut Graphics2D, position: Vec2, color: Color, text: &str, target: Option<Target>) -> f32 {
+        let block = self.font.layout_text(text, FONT_SIZE, TextOptions::new());
+        graphics.draw_text(position, color, &block);
+        if let Some(target) = target {
+            self.hitboxes.push((Rect::new(position, position + Vec2::new(block.width(), block.height())), target));
+        }
+        block.width()
+    }
+
+    fn line_height(&self) -> f32 {
+        self.font.layout_text("Ag", FONT_SIZE, TextOptions::new()).height()
+    }
+
+    //area is the whole node container, the panel takes its right side
+    pub fn draw(&mut self, graphics: &mut Graphics2D, area: Rect) {
+        self.bounds = Rect::from_tuples((area.right() - area.width() * WIDTH_RATIO, area.top()), (area.right(), area.bottom()));
+        self.hitboxes.clear();
+        graphics.draw_rectangle(&self.bounds, *PANEL_COLOR);
+        graphics.draw_line(*self.bounds.top_left(), self.bounds.bottom_left(), 2.0, *BORDER_COLOR);
+
+        let line_height = self.line_height();
+        let left = self.bounds.left() + PADDING;
+        let mut y = self.bounds.top() + PADDING;
+        self.draw_text(graphics, Vec2::new(left, y), *TEXT_COLOR, "Properties", None);
+        y += line_height + ROW_SPACING;
+
+        let node = match &self.node {
+            Some(node) => node.clone(),
+            None => {
+                self.draw_text(graphics, Vec2::new(left, y), *KIND_COLOR, "select one node to see its properties", None);
+                return;
+            }
+        };
+        let (title, properties) = {
+            let node = node.read().unwrap();
+            (node.get_content().lines().next().unwrap_or_default().to_string(), node.get_properties().clone())
+        };
+        self.draw_text(graphics, Vec2::new(left, y), *KIND_COLOR, &shorten(&title, MAX_VALUE_LENGTH), None);
+        y += line_height + ROW_SPACING * 2.0;
+
+        for (key, property) in properties {
+            let mut x = left;
+            x += self.draw_text(graphics, Vec2::new(x, y), *TEXT_COLOR, &key, None) + COLUMN_GAP;
+            let kind = format!("[{}]", property.get_kind().get_name());
+            x += self.draw_text(graphics, Vec2::new(x, y), *KIND_COLOR, &kind, Some(Target::Kind(key.clone()))) + COLUMN_GAP;
+
+            let editing = match &self.editing {
+                Some((Field::Value(editing), text)) if *editing == key => Some(format!("{}|", text)),
+                _ => None,
+            };
+            match editing {
+                Some(text) => {
+                    let width = self.font.layout_text(&text, FONT_SIZE, TextOptions::new()).width();
+                    graphics.draw_rectangle(Rect::new(Vec2::new(x, y), Vec2::new(x + width, y + line_height)), *EDITING_COLOR);
+                    self.draw_text(graphics, Vec2::new(x, y), *TEXT_COLOR, &text, None);
+                }
+                None => {
+                    x += self.draw_text(graphics, Vec2::new(x, y), *TEXT_COLOR, &describe(&property), Some(Target::Value(key.clone()))) + COLUMN_GAP;
+                    if let Some(id) = property.as_node() {
+                        self.draw_text(graphics, Vec2::new(x, y), *KIND_COLOR, "->", Some(Target::Follow(id)));
+                    }
+                }
+            }
+
+            let remove_x = self.bounds.right() - PADDING - self.font.layout_text("x", FONT_SIZE, TextOptions::new()).width();
+            self.draw_text(graphics, Vec2::new(remove_x, y), *KIND_COLOR, "x", Some(Target::Remove(key.clone())));
+            y += line_height + ROW_SPACING;
+        }
+
+        y += ROW_SPACING;
+        match &self.editing {
+            Some((Field::New, text)) => {
+                let text = format!("{}|", text);
+                self.draw_text(graphics, Vec2::new(left, y), *TEXT_COLOR, &text, None);
+            }
+            _ => {
+                self.draw_text(graphics, Vec2::new(left, y), *KIND_COLOR, "+ add property (name = value)", Some(Target::Add));
+            }
+        }
+        y += line_height + ROW_SPACING;
+
+        if let Some(error) = self.error.clone() {
+            self.draw_text(graphics, Vec2::new(left, y), *ERROR_COLOR, &error, None);
+        }
+    }
+
+    //returns the node to select, if the click asked for one
+    pub fn handle_click(&mut self, position: Vec2) -> Option<i64> {
+        let node = self.node.clone()?;
+        let target = self.hitboxes.iter().find(|(hitbox, _)| hitbox.contains(position)).map(|(_, target)| target.clone());
+        self.error = None;
+
+        match target {
+            Some(Target::Value(key)) => {
+                let property = node.read().unwrap().get_typed_property(&key).cloned()?;
+                match property.as_bool() {
+                    Some(value) => node.write().unwrap().set_typed_property(key, Property::parse(&PropertyKind::Boolean, &(!value).to_string()).unwrap()),
+                    None => self.editing = Some((Field::Value(key), property.get_text().clone())),
+                }
+            }
+            Some(Target::Kind(key)) => {
+                self.editing = None;
+                let mut node = node.write().unwrap();
+                let property = node.get_typed_property(&key).cloned()?;
+                //the kinds after this one, round and round, until one fits the value. Text always does
+                let start = PROPERTY_KINDS.iter().position(|kind| kind == property.get_kind()).map_or(0, |index| index + 1);
+                let next = (0..PROPERTY_KINDS.len())
+                    .map(|offset| &PROPERTY_KINDS[(start + offset) % PROPERTY_KINDS.len()])
+                    .find_map(|kind| Property::parse(kind, property.get_text()).ok());
+                if let Some(next) = next {
+                    node.set_typed_property(key, next);
+                }
+            }
+            Some(Target::Remove(key)) => {
+                self.editing = None;
+                node.write().unwrap().remove_property(&key);
+            }
+            Some(Target::Follow(id)) => {
+                if find_node(id).is_none() {
+                    self.error = Some(format!("there is no node {} anymore", id));
+                }
+                return Some(id);
+            }
+            Some(Target::Add) => self.editing = Some((Field::New, String::new())),
+            None => self.editing = None,
+        }
+        None
+    }
+
+    pub fn handle_char(&mut self, character: char) {
+        if matches!(character, '\r' | '\n') {
+            self.commit();
+            return;
+        }
+        if let Some((_, text)) = &mut self.editing {
+            match character {
+                //backspace
+                '\u{8}' => {
+                    text.pop();
+                }
+                character if character.is_control() => {}
+                character => text.push(character),
+            }
+        }
+    }
+
+    pub fn handle_key_down(&mut self, key: VirtualKeyCode, control: bool) {
+        match key {
+            VirtualKeyCode::Escape => {
+                self.editing = None;
+                self.error = None;
+            }
+            VirtualKeyCode::V if control => {
+                if let (Some((_, text)), Ok(pasted)) = (&mut self.editing, clipboard::get_text()) {
+                    text.push_str(pasted.lines().next().unwrap_or_default());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn commit(&mut self) {
+        let (node, (field, text)) = match (&self.node, self.editing.take()) {
+            (Some(node), Some(editing)) => (node.clone(), editing),
+            _ => return,
+        };
+        let mut node = node.write().unwrap();
+
+        let result = match &field {
+            Field::Value(key) => {
+                let kind = node.get_typed_property(key).map(|property| property.get_kind().clone()).unwrap_or(PropertyKind::Text);
+                Property::parse(&kind, &text).map(|property| node.set_typed_property(key.clone(), property))
+            }
+            Field::New => match text.split_once('=') {
+                Some((key, value)) if !key.trim().is_empty() => {
+                    node.set_typed_property(key.trim().to_string(), Property::guess(value.trim()));
+                    Ok(())
+                }
+                _ => Err("new properties are written as name = value".to_string()),
+            },
+        };
+
+        //keep editing what was wrong
+        if let Err(error) = result {
+            self.error = Some(error);
+            self.editing = Some((field, text));
+        }
+    }
+}
+
+//a property's value as the panel shows it
+fn describe(property: &Property) -> String {
+    match property.as_node() {
+        Some(id) => {
+            let title = find_node(id).map(|node| node.read().unwrap().get_content().lines().next().unwrap_or_default().to_string());
+            format!("#{} {}", id, shorten(&title.unwrap_or("(missing)".to_string()), MAX_VALUE_LENGTH))
+        }
+        None if property.get_text().is_empty() => "(empty)".to_string(),
+        None => shorten(property.get_text().lines().next().unwrap_or_default(), MAX_VALUE_LENGTH),
+    }
+}
@@ -0,0 +1,245 @@
+use crate::headless::Raster;
+use crate::structs::rich_text::{RichText, Style};
+use lazy_static::lazy_static;
+use speedy2d::color::Color;
+use speedy2d::dimen::Vec2;
+use speedy2d::font::{Font, FormattedTextBlock, TextLayout, TextOptions};
+use speedy2d::shape::{Rectangle, RoundRect};
+use speedy2d::Graphics2D;
+use std::rc::Rc;
+
+//where every span of a node's rich text goes, relative to the top left corner of the text. Both the window and
+//the headless renderer draw from this, and the editor uses it to find the cursor.
+//there's only the one font, so bold is the text drawn twice a bit apart and italic is sheared by drawing it
+//in thin horizontal strips, each one shifted a little more than the one below.
+
+lazy_static! {
+    static ref LINK_COLOR: Color = Color::from_hex_rgb(0x3D5AA8);
+    static ref CODE_BACKGROUND_COLOR: Color = Color::from_hex_rgb(0xD2CBF5);
+}
+
+const LIST_INDENT: f32 = 1.2; //in font sizes, per list level
+const BOLD_OFFSET: f32 = 0.03; //in font sizes
+const ITALIC_SHEAR: f32 = 0.2; //horizontal shift per vertical pixel
+const ITALIC_STRIP_HEIGHT: f32 = 2.0; //in pixels
+const CODE_PADDING: f32 = 0.1; //in font sizes
+
+pub struct LaidOutSpan {
+    pub block: Rc<FormattedTextBlock>,
+    pub text: String,
+    pub position: Vec2,
+    pub width: f32,
+    pub style: Style,
+    pub link: bool,
+}
+
+//a list marker ("•", "1.") in front of a paragraph
+pub struct LaidOutMarker {
+    pub block: Rc<FormattedTextBlock>,
+    pub text: String,
+    pub position: Vec2,
+}
+
+//one paragraph
+struct LaidOutLine {
+    start: usize, //position of its first character, see RichText
+    text: String,
+    x: f32,
+    y: f32,
+}
+
+pub struct RichTextLayout {
+    pub spans: Vec<LaidOutSpan>,
+    pub markers: Vec<LaidOutMarker>,
+    pub size: Vec2,
+    lines: Vec<LaidOutLine>,
+    line_height: f32,
+    font_size: f32,
+}
+
+//text widths without trailing spaces being dropped: "a " measures as "a |" minus "|"
+fn measure(font: &Font, text: &str, font_size: f32) -> f32 {
+    if text.ends_with(char::is_whitespace) {
+        let with_end = font.layout_text(&format!("{}|", text), font_size, TextOptions::new()).width();
+        with_end - font.layout_text("|", font_size, TextOptions::new()).width()
+    } else {
+        font.layout_text(text, font_size, TextOptions::new()).width()
+    }
+}
+
+pub fn layout(font: &Font, text: &RichText, font_size: f32) -> RichTextLayout {
+    let line_height = font.layout_text("Ag", font_size, TextOptions::new()).height();
+    let mut spans = Vec::new();
+    let mut markers = Vec::new();
+    let mut lines = Vec::new();
+    let mut width: f32 = 0.0;
+
+    let mut start = 0;
+    for (index, (paragraph, marker)) in text.get_paragraphs().iter().zip(text.get_list_markers()).enumerate() {
+        let y = index as f32 * line_height;
+        let mut x = 0.0;
+        if let Some(marker) = marker {
+            let marker = if marker == "-" { "•".to_string() } else { marker };
+            x = paragraph.level as f32 * LIST_INDENT * font_size;
+            markers.push(LaidOutMarker {
+                block: font.layout_text(&marker, font_size, TextOptions::new()),
+                text: marker,
+                position: Vec2::new(x, y),
+            });
+            x += LIST_INDENT * font_size;
+        }
+
+        let line_x = x;
+        for span in &paragraph.spans {
+            let span_width = measure(font, &span.text, font_size);
+            spans.push(LaidOutSpan {
+                block: font.layout_text(&span.text, font_size, TextOptions::new()),
+                text: span.text.clone(),
+                position: Vec2::new(x, y),
+                width: span_width,
+                style: span.style,
+                link: span.link.is_some(),
+            });
+            x += span_width;
+        }
+        width = width.max(x);
+
+        let text = paragraph.get_text();
+        let length = text.chars().count();
+        lines.push(LaidOutLine { start, text, x: line_x, y });
+        start += length + 1;
+    }
+
+    RichTextLayout {
+        spans,
+        markers,
+        size: Vec2::new(width, lines.len() as f32 * line_height),
+        lines,
+        line_height,
+        font_size,
+    }
+}
+
+impl RichTextLayout {
+    //top of the cursor standing before the character at that position, and its height
+    pub fn get_cursor(&self, font: &Font, position: usize) -> (Vec2, f32) {
+        let line = self.lines.iter().rev().find(|line| line.start <= position).unwrap_or(&self.lines[0]);
+        let prefix: String = line.text.chars().take(position - line.start).collect();
+        (Vec2::new(line.x + measure(font, &prefix, self.font_size), line.y), self.line_height)
+    }
+
+    //one (top left, bottom right) rectangle per paragraph touched by the positions from start to end
+    pub fn get_selection(&self, font: &Font, start: usize, end: usize) -> Vec<(Vec2, Vec2)> {
+        self.lines.iter().filter_map(|line| {
+            let length = line.text.chars().count();
+            let from = start.max(line.start);
+            let to = end.min(line.start + length);
+            if from > to || (from == to && start != end) {
+                return None;
+            }
+            let (top_left, height) = self.get_cursor(font, from);
+            let (right, _) = self.get_cursor(font, to);
+            Some((top_left, Vec2::new(right.x, top_left.y + height)))
+        }).collect()
+    }
+
+    fn get_color(span: &LaidOutSpan, color: Color) -> Color {
+        if span.link {
+            *LINK_COLOR
+        } else {
+            color
+        }
+    }
+
+    fn get_code_background(&self, span: &LaidOutSpan, position: Vec2) -> RoundRect {
+        let padding = CODE_PADDING * self.font_size;
+        RoundRect::from_tuples(
+            (position.x + span.position.x - padding, position.y + span.position.y),
+            (position.x + span.position.x + span.width + padding, position.y + span.position.y + self.line_height),
+            padding * 2.0,
+        )
+    }
+
+    //position is where the top left corner of the text goes
+    pub fn draw(&self, graphics: &mut Graphics2D, position: Vec2, color: Color) {
+        for marker in &self.markers {
+            graphics.draw_text(position + marker.position, color, &marker.block);
+        }
+        for span in &self.spans {
+            let top_left = position + span.position;
+            let span_color = Self::get_color(span, color);
+
+            if span.style.code {
+                graphics.draw_rounded_rectangle(self.get_code_background(span, position), *CODE_BACKGROUND_COLOR);
+            }
+
+            let mut offsets = vec![0.0];
+            if span.style.bold {
+                offsets.push(BOLD_OFFSET * self.font_size);
+            }
+            for offset in offsets {
+                let top_left = top_left + Vec2::new(offset, 0.0);
+                if span.style.italic {
+                    self.draw_sheared(graphics, top_left, span_color, &span.block);
+                } else {
+                    graphics.draw_text(top_left, span_color, &span.block);
+                }
+            }
+
+            if span.link {
+                let baseline = top_left.y + self.line_height * 0.9;
+                graphics.draw_line(
+                    Vec2::new(top_left.x, baseline),
+                    Vec2::new(top_left.x + span.width, baseline),
+                    (self.font_size / 20.0).max(1.0),
+                    span_color,
+                );
+            }
+        }
+    }
+
+    //the strips lean right going up, the bottom one stays where the text would be
+    fn draw_sheared(&self, graphics: &mut Graphics2D, top_left: Vec2, color: Color, block: &Rc<FormattedTextBlock>) {
+        let strips = (self.line_height / ITALIC_STRIP_HEIGHT).ceil().max(1.0) as i32;
+        for strip in 0..strips {
+            let top = top_left.y + strip as f32 * ITALIC_STRIP_HEIGHT;
+            let shift = (top_left.y + self.line_height - top) * ITALIC_SHEAR;
+            graphics.set_clip(Some(Rectangle::from_tuples(
+                ((top_left.x - 1.0).floor() as i32, top.floor() as i32),
+                ((top_left.x + block.width() + shift + 1.0).ceil() as i32, (top + ITALIC_STRIP_HEIGHT).ceil() as i32),
+            )));
+            graphics.draw_text(top_left + Vec2::new(shift, 0.0), color, block);
+        }
+        graphics.set_clip(None);
+    }
+
+    pub fn draw_headless(&self, raster: &mut Raster, position: Vec2, color: Color) {
+        for marker in &self.markers {
+            raster.draw_text(position + marker.position, self.font_size, color, &marker.text);
+        }
+        for span in &self.spans {
+            let top_left = position + span.position;
+            let span_color = Self::get_color(span, color);
+
+            if span.style.code {
+                raster.draw_rounded_rectangle(&self.get_code_background(span, position), *CODE_BACKGROUND_COLOR);
+            }
+
+            let slant = if span.style.italic { ITALIC_SHEAR } else { 0.0 };
+            raster.draw_slanted_text(top_left, self.font_size, span_color, &span.text, slant);
+            if span.style.bold {
+                raster.draw_slanted_text(top_left + Vec2::new(BOLD_OFFSET * self.font_size, 0.0), self.font_size, span_color, &span.text, slant);
+            }
+
+            if span.link {
+                let baseline = top_left.y + self.line_height * 0.9;
+                raster.draw_line(
+                    Vec2::new(top_left.x, baseline),
+                    Vec2::new(top_left.x + span.width, baseline),
+                    (self.font_size / 20.0).max(1.0),
+                    span_color,
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use crate::formats::{find_node, LAYOUT_SPACING};
+use crate::hierarchy;
+use crate::layout;
+use crate::modules::g_node_container::generic_node_container::MODULE_NAME;
+use crate::modules::g_node_container::wrapped_node::NodeWrapper;
+use crate::LINKS;
+use speedy2d::dimen::Vec2;
+use speedy2d::shape::Rect;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::OpenOptions;
+use std::io::Read;
+use std::sync::{Arc, RwLock};
+
+//maps inside nodes. Any node can have its own layout, scoped to it: that makes it a portal, which opens into
+//that layout instead of the editor when double-clicked. The root map stays in generic_node_container.data,
+//the others go into generic_node_container_maps.data by the id of the node they're in.
+
+const MAPS_FILE: &str = "data/generic_node_container_maps.data";
+
+//a map that isn't on screen right now
+pub struct Level {
+    pub wrapped_nodes: Vec<Arc<RwLock<NodeWrapper>>>,
+    pub viewport: Option<Rect>, //where the view was when we left it, only kept while the window is open
+}
+
+impl Level {
+    pub fn new(wrapped_nodes: Vec<Arc<RwLock<NodeWrapper>>>) -> Level {
+        Level { wrapped_nodes, viewport: None }
+    }
+}
+
+//points the wrappers at their nodes, dropping the ones whose node is gone
+pub fn link_nodes(wrapped_nodes: Vec<NodeWrapper>) -> Vec<Arc<RwLock<NodeWrapper>>> {
+    wrapped_nodes.into_iter().filter_map(|mut wnode| {
+        wnode.set_linked_node(&find_node(wnode.get_node_id())?);
+        Some(Arc::new(RwLock::new(wnode)))
+    }).collect()
+}
+
+//the maps of a node container: the one on screen is at the end of path (the root one when it's empty), every
+//other map waits in levels, the root one under None
+pub struct Submaps {
+    path: Vec<i64>,
+    levels: HashMap<Option<i64>, Level>,
+}
+
+impl Submaps {
+    pub fn new() -> Submaps {
+        Submaps { path: Vec::new(), levels: HashMap::new() }
+    }
+
+    //the maps of nodes from MAPS_FILE, with the root one on screen
+    pub fn load() -> Submaps {
+        let mut data = String::new();
+        if let Ok(mut file) = OpenOptions::new().read(true).open(MAPS_FILE) {
+            file.read_to_string(&mut data).unwrap();
+        }
+        if data.is_empty() {
+            return Submaps::new();
+        }
+
+        let maps: BTreeMap<i64, Vec<NodeWrapper>> = serde_json::from_str(&data).unwrap();
+        let levels = maps.into_iter()
+            .filter(|(id, _)| find_node(*id).is_some())
+            .map(|(id, wrapped_nodes)| (Some(id), Level::new(link_nodes(wrapped_nodes))))
+            .collect();
+        Submaps { path: Vec::new(), levels }
+    }
+
+    pub fn get_path(&self) -> &Vec<i64> {
+        &self.path
+    }
+
+    //the node whose map is on screen, None on the root map
+    pub fn get_portal(&self) -> Option<i64> {
+        self.path.last().copied()
+    }
+
+    //nodes with a map of their own
+    pub fn get_portals(&self) -> HashSet<i64> {
+        self.levels.keys().flatten().chain(self.path.iter()).copied().collect()
+    }
+
+    //the path after going into the map of that node. A portal already on the way is a jump back up to it
+    pub fn get_path_into(&self, id: i64) -> Vec<i64> {
+        let mut path = self.path.clone();
+        match path.iter().position(|portal| *portal == id) {
+            Some(index) => path.truncate(index + 1),
+            None => path.push(id),
+        }
+        path
+    }
+
+    //the path to the map that shows the node, when it's one of those not on screen
+    pub fn find(&self, id: i64) -> Option<Vec<i64>> {
+        self.levels.iter()
+            .find(|(_, level)| level.wrapped_nodes.iter().any(|wnode| wnode.read().unwrap().get_node_id() == id))
+            .map(|(key, _)| key.iter().copied().collect())
+    }
+
+    //puts the map on screen (current) away and hands over the one at the end of path, seeded at origin if that
+    //node had none. A map left empty goes away, its node is a plain node again
+    pub fn switch(&mut self, current: Level, path: Vec<i64>, origin: Vec2) -> Level {
+        if self.path.is_empty() || !current.wrapped_nodes.is_empty() {
+            self.levels.insert(self.get_portal(), current);
+        }
+        let key = path.last().copied();
+        self.path = path;
+        self.levels.remove(&key).unwrap_or_else(|| seed(key.unwrap(), origin))
+    }
+
+    //the node leaves every map that isn't on screen, and its own map goes
+    pub fn remove_node(&mut self, id: i64) {
+        for level in self.levels.values_mut() {
+            level.wrapped_nodes.retain(|wnode| wnode.read().unwrap().get_node_id() != id);
+        }
+        self.levels.remove(&Some(id));
+    }
+
+    //every wrapper on the maps that aren't on screen
+    pub fn get_wrapped_nodes(&self) -> impl Iterator<Item = &Arc<RwLock<NodeWrapper>>> {
+        self.levels.values().flat_map(|level| level.wrapped_nodes.iter())
+    }
+
+    //the wrappers of the root map and of every node's map, with on_screen as the one at the end of the path
+    pub fn get_all(&self, on_screen: &[Arc<RwLock<NodeWrapper>>]) -> (Vec<NodeWrapper>, BTreeMap<i64, Vec<NodeWrapper>>) {
+        let unwrap = |wrapped_nodes: &[Arc<RwLock<NodeWrapper>>]| -> Vec<NodeWrapper> {
+            wrapped_nodes.iter().map(|wnode| wnode.read().unwrap().clone()).collect()
+        };
+        let mut root = Vec::new();
+        let mut maps = BTreeMap::new();
+        let levels = self.levels.iter()
+            .map(|(key, level)| (*key, level.wrapped_nodes.as_slice()))
+            .chain(std::iter::once((self.get_portal(), on_screen)));
+        for (key, wrapped_nodes) in levels {
+            match key {
+                None => root = unwrap(wrapped_nodes),
+                Some(id) => {
+                    maps.insert(id, unwrap(wrapped_nodes));
+                }
+            }
+        }
+        (root, maps)
+    }
+}
+
+pub fn save(maps: BTreeMap<i64, Vec<NodeWrapper>>) {
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(MAPS_FILE)
+        .unwrap();
+    serde_json::to_writer(file, &maps).unwrap();
+}
+
+//what a node's map starts with: its children, laid out by their links, with the top left one at origin
+pub fn seed(id: i64, origin: Vec2) -> Level {
+    let children: Vec<i64> = hierarchy::get_children(id);
+    let index = |id: i64| children.iter().position(|child| *child == id);
+    let edges: Vec<(usize, usize)> = LINKS.read().unwrap().iter()
+        .filter_map(|link| {
+            let link = link.read().unwrap();
+            Some((index(link.get_from_id())?, index(link.get_to_id())?))
+        })
+        .collect();
+
+    let positions = layout::force_directed(children.len(), &edges, LAYOUT_SPACING);
+    let left = positions.iter().map(|position| position.0).fold(f32::MAX, f32::min);
+    let top = positions.iter().map(|position| position.1).fold(f32::MAX, f32::min);
+    let wrapped_nodes = children.iter().zip(positions)
+        .filter_map(|(child, position)| {
+            let position = (origin.x + position.0 - left, origin.y + position.1 - top);
+            let node = find_node(*child)?;
+            node.write().unwrap().add_module(MODULE_NAME.to_string());
+            Some(Arc::new(RwLock::new(NodeWrapper::new(node, position))))
+        })
+        .collect();
+    Level::new(wrapped_nodes)
+}
@@ -0,0 +1,142 @@
+use crate::headless::Raster;
+use crate::modules::g_node_container::generic_node_container::{BACKGROUND_COLOR, DIMMED_NODE_FADE, FONT_SIZE};
+use crate::modules::g_node_container::wrapped_node::mix;
+use crate::structs::task::{Task, TaskPriority, TaskStatus};
+use crate::utils::now;
+use lazy_static::lazy_static;
+use speedy2d::color::Color;
+use speedy2d::dimen::Vec2;
+use speedy2d::font::{Font, FormattedTextBlock, TextLayout, TextOptions};
+use speedy2d::shape::{Rect, RoundRect};
+use speedy2d::Graphics2D;
+use std::rc::Rc;
+
+//what a task node has on top of a plain one: a checkbox left of the text (its border colored by the priority,
+//ticked when done, crossed out when cancelled, half filled while in progress) and a badge with the due date on
+//the top right corner, red once it's late. Worked out in window coordinates like link shapes.
+
+lazy_static! {
+    static ref CHECKBOX_BORDER_COLOR: Color = Color::from_hex_rgb(0x6B7BB0); //no priority
+    static ref HIGH_PRIORITY_COLOR: Color = Color::from_hex_rgb(0xE53935);
+    static ref MEDIUM_PRIORITY_COLOR: Color = Color::from_hex_rgb(0xFB8C00);
+    static ref LOW_PRIORITY_COLOR: Color = Color::from_hex_rgb(0x43A047);
+    static ref CHECK_COLOR: Color = Color::from_hex_rgb(0x2F3E46);
+    static ref BADGE_COLOR: Color = Color::from_hex_rgb(0x607D8B);
+    static ref OVERDUE_BADGE_COLOR: Color = Color::from_hex_rgb(0xE53935);
+}
+
+pub const CHECKBOX_SIZE: f32 = 44.0; //at scale 1
+pub const CHECKBOX_GAP: f32 = 20.0; //between the checkbox and the text
+const CHECKBOX_BORDER: f32 = 5.0;
+const CHECKBOX_RADIUS: f32 = 8.0;
+const CHECK_WIDTH: f32 = 7.0;
+const BADGE_TEXT: f32 = 0.45; //of the node's font size
+const BADGE_PADDING: f32 = 8.0;
+
+pub struct TaskMarks {
+    checkbox: Rect,
+    boxes: Vec<(RoundRect, Color)>, //border, inside, and the filling of tasks in progress
+    strokes: Vec<(Vec2, Vec2)>, //the tick or the cross
+    check_width: f32,
+    check_color: Color,
+    scale: f32,
+    badge: Option<(RoundRect, Color, Rc<FormattedTextBlock>, String, f32)>, //(background, color, block, text, font size)
+}
+
+//where the checkbox of a node whose text starts at position goes: on the left, centered on the first line
+pub fn get_checkbox_rect(position: Vec2, scale: f32) -> Rect {
+    let top = position.y + ((FONT_SIZE - CHECKBOX_SIZE) * 0.5).max(0.0) * scale;
+    let top_left = Vec2::new(position.x - (CHECKBOX_SIZE + CHECKBOX_GAP) * scale, top);
+    Rect::new(top_left, top_left + Vec2::new(CHECKBOX_SIZE, CHECKBOX_SIZE) * scale)
+}
+
+impl TaskMarks {
+    //position is where the node's text starts, outer_rect its border
+    pub fn new(font: &Font, task: &Task, position: Vec2, outer_rect: &RoundRect, scale: f32, dimmed: bool) -> TaskMarks {
+        let fade = |color: Color| if dimmed { mix(color, *BACKGROUND_COLOR, DIMMED_NODE_FADE) } else { color };
+        let checkbox = get_checkbox_rect(position, scale);
+        let border = match task.priority {
+            Some(TaskPriority::High) => *HIGH_PRIORITY_COLOR,
+            Some(TaskPriority::Medium) => *MEDIUM_PRIORITY_COLOR,
+            Some(TaskPriority::Low) => *LOW_PRIORITY_COLOR,
+            None => *CHECKBOX_BORDER_COLOR,
+        };
+        let inset = Vec2::new(CHECKBOX_BORDER, CHECKBOX_BORDER) * scale;
+        let mut boxes = vec![
+            (RoundRect::new(*checkbox.top_left(), *checkbox.bottom_right(), CHECKBOX_RADIUS * scale), fade(border)),
+            (RoundRect::new(*checkbox.top_left() + inset, *checkbox.bottom_right() - inset, (CHECKBOX_RADIUS - CHECKBOX_BORDER).max(0.0) * scale), fade(Color::WHITE)),
+        ];
+
+        let (left, top, size) = (checkbox.left(), checkbox.top(), checkbox.width());
+        let at = |x: f32, y: f32| Vec2::new(left + x * size, top + y * size);
+        let strokes = match task.status {
+            TaskStatus::Open => Vec::new(),
+            TaskStatus::InProgress => {
+                boxes.push((RoundRect::new(at(0.3, 0.3), at(0.7, 0.7), 0.0), fade(border)));
+                Vec::new()
+            }
+            TaskStatus::Done => vec![(at(0.22, 0.52), at(0.42, 0.72)), (at(0.42, 0.72), at(0.8, 0.28))],
+            TaskStatus::Cancelled => vec![(at(0.25, 0.25), at(0.75, 0.75)), (at(0.75, 0.25), at(0.25, 0.75))],
+        };
+
+        //closed tasks have nothing left to be late for
+        let badge = task.get_due().filter(|_| !task.status.is_closed()).map(|due| {
+            let text = match task.recurrence {
+                Some(recurrence) => format!("due {}, {}", due, recurrence.get_name()),
+                None => format!("due {}", due),
+            };
+            let color = if task.is_overdue(now()) { *OVERDUE_BADGE_COLOR } else { *BADGE_COLOR };
+            let font_size = FONT_SIZE * BADGE_TEXT * scale;
+            let block = font.layout_text(&text, font_size, TextOptions::new());
+            let padding = BADGE_PADDING * scale;
+            let size = block.size() + Vec2::new(padding, padding) * 2.0;
+            let top_left = Vec2::new(outer_rect.bottom_right().x - size.x, outer_rect.top_left().y - size.y * 0.5);
+            (RoundRect::new(top_left, top_left + size, size.y * 0.5), fade(color), block, text, font_size)
+        });
+
+        TaskMarks {
+            checkbox,
+            boxes,
+            strokes,
+            check_width: CHECK_WIDTH * scale,
+            check_color: fade(*CHECK_COLOR),
+            scale,
+            badge,
+        }
+    }
+
+    //on screen, for clicks
+    pub fn get_checkbox(&self) -> Rect {
+        self.checkbox.clone()
+    }
+
+    fn get_badge_text_position(&self, background: &RoundRect) -> Vec2 {
+        *background.top_left() + Vec2::new(BADGE_PADDING, BADGE_PADDING) * self.scale
+    }
+
+    pub fn draw(&self, graphics: &mut Graphics2D) {
+        for (rect, color) in &self.boxes {
+            graphics.draw_rounded_rectangle(rect.clone(), *color);
+        }
+        for (start, end) in &self.strokes {
+            graphics.draw_line(*start, *end, self.check_width, self.check_color);
+        }
+        if let Some((background, color, block, _, _)) = &self.badge {
+            graphics.draw_rounded_rectangle(background.clone(), *color);
+            graphics.draw_text(self.get_badge_text_position(background), Color::WHITE, block);
+        }
+    }
+
+    pub fn draw_headless(&self, raster: &mut Raster) {
+        for (rect, color) in &self.boxes {
+            raster.draw_rounded_rectangle(rect, *color);
+        }
+        for (start, end) in &self.strokes {
+            raster.draw_line(*start, *end, self.check_width, self.check_color);
+        }
+        if let Some((background, color, _, text, font_size)) = &self.badge {
+            raster.draw_rounded_rectangle(background, *color);
+            raster.draw_text(self.get_badge_text_position(background), *font_size, Color::WHITE, text);
+        }
+    }
+}
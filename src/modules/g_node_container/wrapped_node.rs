@@ -1,26 +1,58 @@
-use std::rc::Rc;
 use std::sync::{Arc, RwLock};
-use speedy2d::font::{Font, FormattedTextBlock, TextLayout, TextOptions};
+use speedy2d::font::Font;
 use speedy2d::shape::{Rect, RoundedRectangle, RoundRect};
-use crate::modules::g_node_container::generic_node_container::{FONT_SIZE, ROUNDED_RECT_BORDER_RADIUS, ROUNDED_RECT_RADIUS, WRAPPED_NODE_BORDER_COLOR, WRAPPED_NODE_BORDER_SIZE, WRAPPED_NODE_COLOR, WRAPPED_NODE_PADDING, WRAPPED_NODE_SELECTED_COLOR};
+use crate::modules::g_node_container::generic_node_container::{FONT_SIZE, ROUNDED_RECT_BORDER_RADIUS, ROUNDED_RECT_RADIUS, WRAPPED_NODE_BORDER_COLOR, WRAPPED_NODE_BORDER_SIZE, WRAPPED_NODE_COLOR, WRAPPED_NODE_PADDING, WRAPPED_NODE_SELECTED_COLOR, RECENTLY_EDITED_COLOR, BACKGROUND_COLOR, DIMMED_NODE_FADE, MODULE_NAME};
+use crate::headless::Raster;
+use crate::modules::g_node_container::attachment_layout::{self, AttachmentLayout, ATTACHMENTS_GAP};
+use crate::modules::g_node_container::rich_text_layout::{layout, RichTextLayout};
+use crate::modules::g_node_container::task_marks::{TaskMarks, CHECKBOX_GAP, CHECKBOX_SIZE};
 use crate::structs::node::Node;
+use crate::structs::rich_text::RichText;
+use crate::structs::task::Task;
+use crate::utils::now;
+use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
 use speedy2d::color::Color;
 use speedy2d::dimen::Vec2;
 use speedy2d::Graphics2D;
 
+lazy_static! {
+    static ref PORTAL_STACK_COLOR: Color = Color::from_hex_rgb(0x6B7BB0);
+    static ref ELSEWHERE_COLOR: Color = Color::from_hex_rgb(0x52796F);
+    static ref CLOSED_TASK_TEXT_COLOR: Color = Color::from_hex_rgb(0x6C757D); //done or cancelled
+}
+
+thread_local! {
+    //parsed once for the window's thread (or the command line's, for render), not for every node every frame.
+    //everything in the node container that lays out text uses this one
+    pub static FONT: Font = Font::new(include_bytes!("../../../res/OpenSans-SemiBold.ttf")).unwrap();
+}
+
+const PORTAL_STACK_OFFSET: f32 = 14.0; //at scale 1
+const ELSEWHERE_FONT_RATIO: f32 = 0.5; //of FONT_SIZE, for the "also in" line
+const ELSEWHERE_GAP: f32 = 6.0; //between that line and the node, at scale 1
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct NodeWrapper {
     #[serde(skip)]
     node: Arc<RwLock<Node>>,
     node_id: i64,
     position: (f32, f32),
+    #[serde(default)]
+    size: Option<(f32, f32)>, //minimum size of the whole node, border included. None means "fit the text"
     #[serde(skip)]
     pub selected: bool,
     #[serde(skip, default = "default_cached_bounds")]
     cached_bounds: (Rect, f32),
     #[serde(skip, default = "default_vec2")]
     offset: Vec2,
+    #[serde(skip)]
+    checkbox: Option<Rect>, //on screen, where draw last put it. None for plain nodes
+}
+
+pub(crate) fn mix(a: Color, b: Color, amount: f32) -> Color {
+    let channel = |a: f32, b: f32| a + (b - a) * amount;
+    Color::from_rgb(channel(a.r(), b.r()), channel(a.g(), b.g()), channel(a.b(), b.b()))
 }
 
 fn default_cached_bounds() -> (Rect, f32) {
@@ -44,6 +76,14 @@ impl NodeWrapper {
         self.position
     }
 
+    pub fn get_size(&self) -> Option<(f32, f32)> {
+        self.size
+    }
+
+    pub fn set_size(&mut self, size: Option<(f32, f32)>) {
+        self.size = size;
+    }
+
     pub fn merge_offset(&mut self) {
         self.position.0 += self.offset.x;
         self.position.1 += self.offset.y;
@@ -78,9 +118,62 @@ impl NodeWrapper {
 
         let (position, scale) = self.translation(from, to);
 
-        let formatted_content = self.get_formatted_content(scale);
-        let text_size = formatted_content.size();
+        let text_layout = self.get_text_layout(scale);
+        let attachment_layout = self.get_attachment_layout(scale);
+        let (outer_rect, _) = Self::get_frame(position, self.get_content_size(self.get_body_size(&text_layout, &attachment_layout, scale), scale), scale);
+
+        outer_rect
+    }
+
+    //the text with the attachments below it, and the checkbox of tasks left of both
+    fn get_body_size(&self, text_layout: &RichTextLayout, attachment_layout: &AttachmentLayout, scale: f32) -> Vec2 {
+        let column = if attachment_layout.is_empty() {
+            text_layout.size
+        } else {
+            Vec2::new(
+                f32::max(text_layout.size.x, attachment_layout.size.x),
+                text_layout.size.y + ATTACHMENTS_GAP*scale + attachment_layout.size.y,
+            )
+        };
+        match self.get_task() {
+            Some(_) => Vec2::new(self.get_checkbox_room(scale) + column.x, f32::max(column.y, CHECKBOX_SIZE*scale)),
+            None => column,
+        }
+    }
+
+    fn get_task(&self) -> Option<Task> {
+        self.node.read().unwrap().get_task().cloned()
+    }
+
+    //how far right of the body the text starts
+    fn get_checkbox_room(&self, scale: f32) -> f32 {
+        match self.get_task() {
+            Some(_) => (CHECKBOX_SIZE + CHECKBOX_GAP)*scale,
+            None => 0.0,
+        }
+    }
+
+    //where draw last put the checkbox, None for plain nodes
+    pub fn get_checkbox(&self) -> Option<Rect> {
+        self.checkbox.clone()
+    }
 
+    //size of the area inside the padding: the text and attachments, grown to the node's minimum size if it has one
+    fn get_content_size(&self, body_size: Vec2, scale: f32) -> Vec2 {
+        match self.size {
+            Some((width, height)) => {
+                let frame = (WRAPPED_NODE_BORDER_SIZE + WRAPPED_NODE_PADDING)*2.0;
+                Vec2::new(
+                    f32::max(body_size.x, (width - frame)*scale),
+                    f32::max(body_size.y, (height - frame)*scale),
+                )
+            }
+            None => body_size,
+        }
+    }
+
+    //outer (border) and inner rounded rectangles around text placed at position
+    fn get_frame(position: Vec2, text_size: Vec2, scale: f32) -> (RoundRect, RoundRect) {
         let outer_rect = RoundRect::from_tuples(
             (
                 position.x - (WRAPPED_NODE_BORDER_SIZE + WRAPPED_NODE_PADDING)*scale,
@@ -90,10 +183,30 @@ impl NodeWrapper {
                 position.x + text_size.x + (WRAPPED_NODE_BORDER_SIZE + WRAPPED_NODE_PADDING)*scale,
                 position.y + text_size.y + (WRAPPED_NODE_BORDER_SIZE + WRAPPED_NODE_PADDING)*scale,
             ),
-            ROUNDED_RECT_BORDER_RADIUS,
+            ROUNDED_RECT_BORDER_RADIUS*scale,
         );
 
-        outer_rect
+        let inner_rect = RoundRect::from_tuples(
+            (
+                position.x - WRAPPED_NODE_PADDING*scale,
+                position.y - WRAPPED_NODE_PADDING*scale,
+            ),
+            (
+                position.x + text_size.x + WRAPPED_NODE_PADDING*scale,
+                position.y + text_size.y + WRAPPED_NODE_PADDING*scale,
+            ),
+            ROUNDED_RECT_RADIUS*scale,
+        );
+
+        (outer_rect, inner_rect)
+    }
+
+    //bounds of the whole node (border included) in layout coordinates, ignoring any viewport
+    pub fn get_layout_bounds(&self) -> Rect {
+        let position = Vec2::new(self.position.0, self.position.1);
+        let body_size = self.get_body_size(&self.get_text_layout(1.0), &self.get_attachment_layout(1.0), 1.0);
+        let (outer_rect, _) = Self::get_frame(position, self.get_content_size(body_size, 1.0), 1.0);
+        Rect::new(*outer_rect.top_left(), *outer_rect.bottom_right())
     }
 
     pub fn get_cached_bounds(&self) -> &(Rect, f32) {
@@ -105,9 +218,11 @@ impl NodeWrapper {
             node: Arc::clone(&node),
             node_id: node.read().unwrap().get_id(),
             position,
+            size: None,
             selected: false,
             cached_bounds: default_cached_bounds(),
             offset: default_vec2(),
+            checkbox: None,
         };
         let (rect, _) = nw.translation(from, to);
         nw.set_position((rect.x, rect.y));
@@ -119,66 +234,136 @@ impl NodeWrapper {
             node: Arc::clone(&node),
             node_id: node.read().unwrap().get_id(),
             position,
+            size: None,
             selected: false,
             cached_bounds: default_cached_bounds(),
             offset: default_vec2(),
+            checkbox: None,
         }
     }
 
-    pub fn get_formatted_content(&self, scale: f32) -> Rc<FormattedTextBlock> {
-        let font = Font::new(include_bytes!("../../../res/OpenSans-SemiBold.ttf")).unwrap();
-        let formatted_content = font.layout_text(
-            self.node.read().unwrap().get_content().as_str(),
-            FONT_SIZE*scale,
-            TextOptions::new(),
-        );
-        formatted_content
+    pub fn get_text_layout(&self, scale: f32) -> RichTextLayout {
+        FONT.with(|font| layout(font, self.node.read().unwrap().get_rich_content(), FONT_SIZE*scale))
     }
 
-    pub fn draw(&mut self, graphics: &mut Graphics2D, from: &Rect, to: &Rect) {
+    pub fn get_attachment_layout(&self, scale: f32) -> AttachmentLayout {
+        FONT.with(|font| attachment_layout::layout(font, self.node.read().unwrap().get_attachments(), FONT_SIZE*scale, scale))
+    }
 
-        let (position, scale) = self.translation(from, to);
-        let formatted_content = self.get_formatted_content(scale);
-        let text_size = formatted_content.size();
+    //"also in ..." for nodes that other modules show too, it sits above the node
+    fn get_elsewhere_layout(&self, scale: f32) -> Option<RichTextLayout> {
+        let elsewhere: Vec<String> = self.node.read().unwrap().get_modules().iter()
+            .filter(|module| *module != MODULE_NAME)
+            .cloned()
+            .collect();
+        if elsewhere.is_empty() {
+            return None;
+        }
+        let text = RichText::plain(&format!("also in {}", elsewhere.join(", ")));
+        Some(FONT.with(|font| layout(font, &text, FONT_SIZE*ELSEWHERE_FONT_RATIO*scale)))
+    }
 
-        let outer_rect = RoundRect::from_tuples(
-            (
-                position.x - (WRAPPED_NODE_BORDER_SIZE + WRAPPED_NODE_PADDING)*scale,
-                position.y - (WRAPPED_NODE_BORDER_SIZE + WRAPPED_NODE_PADDING)*scale,
-            ),
-            (
-                position.x + text_size.x + (WRAPPED_NODE_BORDER_SIZE + WRAPPED_NODE_PADDING)*scale,
-                position.y + text_size.y + (WRAPPED_NODE_BORDER_SIZE + WRAPPED_NODE_PADDING)*scale,
-            ),
-            ROUNDED_RECT_BORDER_RADIUS*scale,
-        );
-        self.cached_bounds = (Rect::new(position, position+formatted_content.size()), scale);
+    fn get_elsewhere_position(outer_rect: &RoundRect, elsewhere: &RichTextLayout, scale: f32) -> Vec2 {
+        *outer_rect.top_left() - Vec2::new(0.0, elsewhere.size.y + ELSEWHERE_GAP*scale)
+    }
 
+    //recent: nodes changed less than that many seconds ago get tinted, the more the more recent the change.
+    //dimmed nodes fade into the background (the ones left out by the tag filter). Portals (nodes with a map
+    //inside) look like a stack of nodes
+    pub fn draw(&mut self, graphics: &mut Graphics2D, from: &Rect, to: &Rect, recent: Option<i64>, dimmed: bool, portal: bool) {
 
-        graphics.draw_rounded_rectangle(outer_rect, *WRAPPED_NODE_BORDER_COLOR);
+        let (position, scale) = self.translation(from, to);
+        let text_layout = self.get_text_layout(scale);
+        let attachment_layout = self.get_attachment_layout(scale);
+        let body_size = self.get_body_size(&text_layout, &attachment_layout, scale);
+        let (outer_rect, inner_rect) = Self::get_frame(position, self.get_content_size(body_size, scale), scale);
+        let text_position = position + Vec2::new(self.get_checkbox_room(scale), 0.0);
+        self.cached_bounds = (Rect::new(text_position, text_position+text_layout.size), scale);
 
-        let inner_rect = RoundRect::from_tuples(
-            (
-                position.x - WRAPPED_NODE_PADDING*scale,
-                position.y - WRAPPED_NODE_PADDING*scale,
-            ),
-            (
-                position.x + text_size.x + WRAPPED_NODE_PADDING*scale,
-                position.y + text_size.y + WRAPPED_NODE_PADDING*scale,
-            ),
-            ROUNDED_RECT_RADIUS*scale,
-        );
 
-        graphics.draw_rounded_rectangle(inner_rect, {
-            if self.selected {
-                *WRAPPED_NODE_SELECTED_COLOR
-            } else {
-                *WRAPPED_NODE_COLOR
+        let fade = |color: Color| if dimmed { mix(color, *BACKGROUND_COLOR, DIMMED_NODE_FADE) } else { color };
+
+        if portal {
+            graphics.draw_rounded_rectangle(Self::get_stacked(&outer_rect, scale), fade(*PORTAL_STACK_COLOR));
+        }
+        graphics.draw_rounded_rectangle(outer_rect.clone(), fade(*WRAPPED_NODE_BORDER_COLOR));
+
+        let mut fill_color = self.get_fill_color();
+        if let (Some(window), Some(modified), false) = (recent, self.node.read().unwrap().get_modified(), self.selected) {
+            let age = (now() - modified).max(0);
+            if age < window {
+                fill_color = mix(fill_color, *RECENTLY_EDITED_COLOR, 1.0 - age as f32 / window as f32);
             }
-        });
+        }
+        graphics.draw_rounded_rectangle(inner_rect, fade(fill_color));
 
         //draw the contents
-        graphics.draw_text(position, Color::BLACK, &formatted_content);
+        let task = self.get_task();
+        text_layout.draw(graphics, text_position, fade(Self::get_text_color(&task)));
+        attachment_layout.draw(graphics, text_position + Vec2::new(0.0, text_layout.size.y + ATTACHMENTS_GAP*scale));
+
+        self.checkbox = None;
+        if let Some(task) = &task {
+            let marks = FONT.with(|font| TaskMarks::new(font, task, text_position, &outer_rect, scale, dimmed));
+            marks.draw(graphics);
+            self.checkbox = Some(marks.get_checkbox());
+        }
+
+        if let Some(elsewhere) = self.get_elsewhere_layout(scale) {
+            elsewhere.draw(graphics, Self::get_elsewhere_position(&outer_rect, &elsewhere, scale), fade(*ELSEWHERE_COLOR));
+        }
+    }
+
+    //same as draw, but on the cpu. origin is the layout point that ends up in the top left corner of the raster
+    pub fn draw_headless(&self, raster: &mut Raster, origin: Vec2, scale: f32, portal: bool) {
+
+        let position = (Vec2::new(self.position.0, self.position.1) - origin) * scale;
+        let text_layout = self.get_text_layout(scale);
+        let attachment_layout = self.get_attachment_layout(scale);
+        let body_size = self.get_body_size(&text_layout, &attachment_layout, scale);
+        let (outer_rect, inner_rect) = Self::get_frame(position, self.get_content_size(body_size, scale), scale);
+
+        if portal {
+            raster.draw_rounded_rectangle(&Self::get_stacked(&outer_rect, scale), *PORTAL_STACK_COLOR);
+        }
+        raster.draw_rounded_rectangle(&outer_rect, *WRAPPED_NODE_BORDER_COLOR);
+
+        raster.draw_rounded_rectangle(&inner_rect, self.get_fill_color());
+
+        let text_position = position + Vec2::new(self.get_checkbox_room(scale), 0.0);
+        let task = self.get_task();
+        text_layout.draw_headless(raster, text_position, Self::get_text_color(&task));
+        attachment_layout.draw_headless(raster, text_position + Vec2::new(0.0, text_layout.size.y + ATTACHMENTS_GAP*scale));
+
+        if let Some(task) = &task {
+            FONT.with(|font| TaskMarks::new(font, task, text_position, &outer_rect, scale, false)).draw_headless(raster);
+        }
+
+        if let Some(elsewhere) = self.get_elsewhere_layout(scale) {
+            elsewhere.draw_headless(raster, Self::get_elsewhere_position(&outer_rect, &elsewhere, scale), *ELSEWHERE_COLOR);
+        }
+    }
+
+    //the node below the node, peeking out at the bottom right
+    fn get_stacked(outer_rect: &RoundRect, scale: f32) -> RoundRect {
+        let offset = Vec2::new(PORTAL_STACK_OFFSET, PORTAL_STACK_OFFSET) * scale;
+        RoundRect::new(*outer_rect.top_left() + offset, *outer_rect.bottom_right() + offset, ROUNDED_RECT_BORDER_RADIUS*scale)
+    }
+
+    //tasks that are done or cancelled are greyed out
+    fn get_text_color(task: &Option<Task>) -> Color {
+        match task {
+            Some(task) if task.status.is_closed() => *CLOSED_TASK_TEXT_COLOR,
+            _ => Color::BLACK,
+        }
+    }
+
+    fn get_fill_color(&self) -> Color {
+        if self.selected {
+            *WRAPPED_NODE_SELECTED_COLOR
+        } else {
+            *WRAPPED_NODE_COLOR
+        }
     }
 
     //first vector: position, second: scale, but only for the height. Screw the width
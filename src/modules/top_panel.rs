@@ -1,36 +1,69 @@
 use speedy2d::color::Color;
 use speedy2d::dimen::Vec2;
+use speedy2d::font::{Font, TextLayout, TextOptions};
 use speedy2d::shape::Rectangle;
 use speedy2d::window::MouseButton;
 use speedy2d::Graphics2D;
 
 const BACKGROUND_COLOR: Color = Color::from_rgb(116.0 / 255.0, 140.0 / 255.0, 171.0 / 255.0);
+const BREADCRUMB_COLOR: Color = Color::from_rgb(0.85, 0.89, 0.95);
+const CURRENT_BREADCRUMB_COLOR: Color = Color::WHITE;
 
 pub const DEFAULT_HEIGHT_RATIO: f32 = 0.05;
+const TEXT_SIZE: f32 = 0.45; //of the panel's height
+const MARGIN: f32 = 0.4; //left of the first breadcrumb, of the panel's height
+const SEPARATOR: &str = "  >  ";
 
 pub struct TopPanel {
     bounds: Rectangle,
+    font: Font,
+    breadcrumbs: Vec<Rectangle>, //where each breadcrumb was drawn, for clicks
 }
 
 impl TopPanel {
     pub fn new() -> TopPanel {
         TopPanel {
             bounds: Rectangle::from_tuples((0.0, 0.0), (0.0, 0.0)),
+            font: Font::new(include_bytes!("../../res/OpenSans-SemiBold.ttf")).unwrap(),
+            breadcrumbs: Vec::new(),
         }
     }
 
-    pub fn draw(&mut self, size: (f32, f32), offset: (f32, f32), graphics: &mut Graphics2D) {
+    //breadcrumbs come from the active module, the last one is where it is now
+    pub fn draw(&mut self, size: (f32, f32), offset: (f32, f32), graphics: &mut Graphics2D, breadcrumbs: &[String]) {
         self.bounds = Rectangle::from_tuples(offset, (size.0 + offset.0, size.1 + offset.1));
 
         //draw background rectangle
         graphics.draw_rectangle(self.bounds.clone(), BACKGROUND_COLOR);
+
+        let text_size = size.1 * TEXT_SIZE;
+        let mut x = offset.0 + size.1 * MARGIN;
+        self.breadcrumbs.clear();
+        for (index, breadcrumb) in breadcrumbs.iter().enumerate() {
+            let current = index + 1 == breadcrumbs.len();
+            let block = self.font.layout_text(breadcrumb, text_size, TextOptions::new());
+            let position = Vec2::new(x, offset.1 + (size.1 - block.height()) / 2.0);
+            graphics.draw_text(position, if current { CURRENT_BREADCRUMB_COLOR } else { BREADCRUMB_COLOR }, &block);
+            self.breadcrumbs.push(Rectangle::new(Vec2::new(x, offset.1), Vec2::new(x + block.width(), offset.1 + size.1)));
+            x += block.width();
+
+            if !current {
+                let separator = self.font.layout_text(SEPARATOR, text_size, TextOptions::new());
+                graphics.draw_text(Vec2::new(x, position.y), BREADCRUMB_COLOR, &separator);
+                x += separator.width();
+            }
+        }
     }
 
     pub fn get_bounds(&self) -> Rectangle {
         self.bounds.clone()
     }
 
-    pub fn handle_click(&mut self, position: Vec2, click_count: i32, button: MouseButton) {
-        //println!("clicked top panel at {position:?}")
+    //the index of the breadcrumb that was clicked, if any
+    pub fn handle_click(&mut self, position: Vec2, _click_count: i32, button: MouseButton) -> Option<usize> {
+        if button != MouseButton::Left {
+            return None;
+        }
+        self.breadcrumbs.iter().position(|bounds| bounds.contains(position))
     }
 }
@@ -0,0 +1,424 @@
+use crate::formats::{escape_xml, write_file};
+use crate::modules::g_node_container::generic_node_container::{
+    BACKGROUND_COLOR, FONT_SIZE, ROUNDED_RECT_BORDER_RADIUS, ROUNDED_RECT_RADIUS, WRAPPED_NODE_BORDER_COLOR, WRAPPED_NODE_BORDER_SIZE,
+    WRAPPED_NODE_COLOR, WRAPPED_NODE_PADDING,
+};
+use crate::attachments::Attachment;
+use crate::hierarchy;
+use crate::structs::link::{Link, LinkStyle};
+use crate::structs::node::Node;
+use crate::structs::property::Property;
+use crate::utils::union_rect;
+use crate::workspace;
+use crate::{LINKS, MODULES, NODES};
+use speedy2d::color::Color;
+use speedy2d::shape::Rect;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+//a static website of the whole workspace: an index, one page per node (content, tags, properties, its links
+//and backlinks) and one page per module with its layout drawn as an svg, nodes clickable. Plain files with
+//relative links, so the folder can go on any static host or be opened straight from the disk.
+
+const STYLE: &str = include_str!("../res/site.css");
+const FONT: &[u8] = include_bytes!("../res/OpenSans-SemiBold.ttf");
+const FONT_FILE: &str = "OpenSans-SemiBold.ttf";
+
+const MAP_MARGIN: f32 = 40.0;
+const LINK_WIDTH: f32 = 6.0; //links of weight 1
+const LINE_HEIGHT: f32 = 1.36; //of open sans, in font sizes
+const TITLE_LENGTH: usize = 80;
+
+struct Map {
+    name: String,
+    file: String, //inside maps/
+    bounds: HashMap<i64, Rect>,
+}
+
+fn node_file(id: i64) -> String {
+    format!("{}.html", id)
+}
+
+//"Generic Node Container" -> "generic-node-container"
+fn slug(name: &str) -> String {
+    let slug: Vec<String> = name
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .map(|part| part.to_lowercase())
+        .collect();
+    if slug.is_empty() {
+        "map".to_string()
+    } else {
+        slug.join("-")
+    }
+}
+
+//first line of the content, shortened
+fn node_title(node: &Node) -> String {
+    let line = node.get_content().lines().map(str::trim).find(|line| !line.is_empty()).unwrap_or_default();
+    let title: String = line.chars().take(TITLE_LENGTH).collect();
+    if title.is_empty() {
+        format!("node {}", node.get_id())
+    } else if title.len() < line.len() {
+        format!("{}…", title)
+    } else {
+        title
+    }
+}
+
+fn css_color(color: &Color) -> String {
+    let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", channel(color.r()), channel(color.g()), channel(color.b()))
+}
+
+//root is the way back to the site's folder from the page ("" or "../")
+fn page(title: &str, root: &str, maps: &[Map], body: &str) -> String {
+    let mut navigation = format!("<a href=\"{}index.html\">Index</a>", root);
+    for map in maps {
+        navigation.push_str(&format!(" · <a href=\"{}maps/{}\">{}</a>", root, map.file, escape_xml(&map.name)));
+    }
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{}</title>\n<link rel=\"stylesheet\" href=\"{}style.css\">\n</head>\n<body>\n<nav>{}</nav>\n<main>\n{}</main>\n</body>\n</html>\n",
+        escape_xml(title),
+        root,
+        navigation,
+        body
+    )
+}
+
+//paragraphs on empty lines, line breaks kept inside them
+fn content_html(content: &str) -> String {
+    content
+        .replace("\r\n", "\n")
+        .split("\n\n")
+        .map(str::trim)
+        .filter(|paragraph| !paragraph.is_empty())
+        .map(|paragraph| format!("<p>{}</p>\n", escape_xml(paragraph).replace('\n', "<br>\n")))
+        .collect()
+}
+
+//urls and other nodes become links, for node pages (which are next to each other)
+fn property_html(property: &Property, titles: &HashMap<i64, String>) -> String {
+    if let Some(url) = property.as_url() {
+        return format!("<a href=\"{0}\">{0}</a>", escape_xml(url));
+    }
+    match property.as_node().and_then(|id| Some((id, titles.get(&id)?))) {
+        Some((id, title)) => format!("<a href=\"{}\">{}</a>", node_file(id), escape_xml(title)),
+        None => escape_xml(property.get_text()).replace('\n', "<br>"),
+    }
+}
+
+//stored files go along with the site, images shown. Other local files stay on the machine the site was made on,
+//only urls are worth a link
+fn attachment_html(folder: &Path, attachment: &Attachment) -> Result<String, String> {
+    let name = escape_xml(attachment.get_name());
+    match (attachment, attachment.get_path()) {
+        (Attachment::Stored { .. }, Some(path)) => {
+            let file = path.file_name().unwrap().to_string_lossy().to_string();
+            let copy = folder.join("attachments").join(&file);
+            if !copy.exists() {
+                std::fs::create_dir_all(folder.join("attachments")).map_err(|e| format!("can't create the attachments folder: {}", e))?;
+                std::fs::copy(&path, &copy).map_err(|e| format!("can't copy {}: {}", path.display(), e))?;
+            }
+            let href = format!("../attachments/{}", escape_xml(&file));
+            if attachment.is_image() {
+                Ok(format!("<a href=\"{0}\"><img src=\"{0}\" alt=\"{1}\"></a>", href, name))
+            } else {
+                Ok(format!("<a href=\"{}\">{}</a>", href, name))
+            }
+        }
+        (_, None) => Ok(format!("<a href=\"{0}\">{0}</a>", name)),
+        (_, Some(_)) => Ok(format!("<code>{}</code>", name)),
+    }
+}
+
+fn tags_html(node: &Node) -> String {
+    node.get_all_tags().iter().map(|tag| format!("<span class=\"tag\">{}</span>", escape_xml(tag))).collect::<Vec<_>>().join(" ")
+}
+
+//"label", "relation" or both, for showing next to a link
+fn link_description(link: &Link) -> String {
+    let mut parts: Vec<&str> = [link.get_relation().as_str(), link.get_label().as_str()]
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .collect();
+    parts.dedup();
+    if parts.is_empty() {
+        String::new()
+    } else {
+        format!(" <span class=\"relation\">{}</span>", escape_xml(&parts.join(": ")))
+    }
+}
+
+fn write_node_page(folder: &Path, node: &Node, titles: &HashMap<i64, String>, links: &[Link], maps: &[Map]) -> Result<(), String> {
+    let id = node.get_id();
+    let title = &titles[&id];
+    let mut body = String::new();
+    let ancestors: Vec<String> = hierarchy::get_ancestors(id).into_iter().rev()
+        .filter_map(|ancestor| Some(format!("<a href=\"{}\">{}</a>", node_file(ancestor), escape_xml(titles.get(&ancestor)?))))
+        .collect();
+    if !ancestors.is_empty() {
+        body.push_str(&format!("<p class=\"ancestors\">{}</p>\n", ancestors.join(" / ")));
+    }
+    body.push_str(&format!("<h1>{}</h1>\n", escape_xml(title)));
+
+    if !node.get_all_tags().is_empty() {
+        body.push_str(&format!("<p class=\"tags\">{}</p>\n", tags_html(node)));
+    }
+    body.push_str(&format!("<div class=\"content\">\n{}</div>\n", content_html(node.get_content())));
+
+    if !node.get_properties().is_empty() {
+        body.push_str("<h2>Properties</h2>\n<table>\n");
+        for (key, value) in node.get_properties() {
+            body.push_str(&format!("<tr><th>{}</th><td>{}</td></tr>\n", escape_xml(key), property_html(value, titles)));
+        }
+        body.push_str("</table>\n");
+    }
+
+    if !node.get_attachments().is_empty() {
+        body.push_str("<h2>Attachments</h2>\n<ul>\n");
+        for attachment in node.get_attachments() {
+            body.push_str(&format!("<li>{}</li>\n", attachment_html(folder, attachment)?));
+        }
+        body.push_str("</ul>\n");
+    }
+
+    let children: String = node.get_children().iter()
+        .filter_map(|child| Some(format!("<li><a href=\"{}\">{}</a></li>\n", node_file(*child), escape_xml(titles.get(child)?))))
+        .collect();
+    if !children.is_empty() {
+        body.push_str(&format!("<h2>Children</h2>\n<ol>\n{}</ol>\n", children));
+    }
+
+    let mut sections = [("Links", String::new()), ("Backlinks", String::new())];
+    for link in links {
+        let (section, other) = if link.get_from_id() == id {
+            (0, link.get_to_id())
+        } else if link.get_to_id() == id {
+            (1, link.get_from_id())
+        } else {
+            continue;
+        };
+        if let Some(other_title) = titles.get(&other) {
+            sections[section].1.push_str(&format!(
+                "<li><a href=\"{}\">{}</a>{}</li>\n",
+                node_file(other),
+                escape_xml(other_title),
+                link_description(link)
+            ));
+        }
+    }
+    for (name, items) in sections {
+        if !items.is_empty() {
+            body.push_str(&format!("<h2>{}</h2>\n<ul>\n{}</ul>\n", name, items));
+        }
+    }
+
+    let on_maps: Vec<String> = maps
+        .iter()
+        .filter(|map| map.bounds.contains_key(&id))
+        .map(|map| format!("<a href=\"../maps/{}#node-{}\">{}</a>", map.file, id, escape_xml(&map.name)))
+        .collect();
+    if !on_maps.is_empty() {
+        body.push_str(&format!("<p class=\"maps\">On {}</p>\n", on_maps.join(", ")));
+    }
+
+    write_file(&folder.join("nodes").join(node_file(id)).to_string_lossy(), &page(title, "../", maps, &body))
+}
+
+//what the link's type (and its own style and weight) change about the css of .link lines. The lines go from
+//center to center under the nodes, so there's no point in arrowheads
+fn link_style(link: &Link) -> String {
+    let link_type = workspace::get_link_type(link.get_relation());
+    let mut style = format!("stroke-width: {}", LINK_WIDTH * link.get_weight());
+    if let Some(link_type) = &link_type {
+        style.push_str(&format!("; stroke: {}", link_type.color));
+    }
+    match link.get_style().or(link_type.map(|link_type| link_type.style)).unwrap_or_default() {
+        LinkStyle::Solid => {}
+        LinkStyle::Dashed => style.push_str("; stroke-dasharray: 24 14"),
+        LinkStyle::Dotted => style.push_str("; stroke-dasharray: 6 10"),
+    }
+    style
+}
+
+//the module's layout, drawn the way the generic node container draws it
+fn map_svg(map: &Map, nodes: &HashMap<i64, &Node>, links: &[Link]) -> String {
+    let area = map.bounds.values().cloned().reduce(union_rect).unwrap_or(Rect::ZERO);
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\" style=\"background: {}\">\n",
+        area.left() - MAP_MARGIN,
+        area.top() - MAP_MARGIN,
+        area.width() + MAP_MARGIN * 2.0,
+        area.height() + MAP_MARGIN * 2.0,
+        css_color(&BACKGROUND_COLOR)
+    );
+
+    //lines first, so that nodes cover their ends
+    let mut ids: Vec<&i64> = map.bounds.keys().collect();
+    ids.sort();
+    for id in &ids {
+        let parent = nodes.get(id).and_then(|node| node.get_parent());
+        if let (Some(from), Some(to)) = (parent.and_then(|parent| map.bounds.get(&parent)), map.bounds.get(id)) {
+            let (from, to) = (from.top_left() + from.size() / 2.0, to.top_left() + to.size() / 2.0);
+            svg.push_str(&format!(
+                "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" class=\"child\"/>\n",
+                from.x, from.y, to.x, to.y
+            ));
+        }
+    }
+    for link in links {
+        if let (Some(from), Some(to)) = (map.bounds.get(&link.get_from_id()), map.bounds.get(&link.get_to_id())) {
+            let (from, to) = (from.top_left() + from.size() / 2.0, to.top_left() + to.size() / 2.0);
+            svg.push_str(&format!(
+                "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" class=\"link\" style=\"{}\"/>\n",
+                from.x, from.y, to.x, to.y, link_style(link)
+            ));
+            let description = [link.get_label().as_str(), link.get_relation().as_str()].into_iter().find(|text| !text.is_empty());
+            if let Some(description) = description {
+                svg.push_str(&format!(
+                    "<text x=\"{}\" y=\"{}\" class=\"link-label\" font-size=\"{}\">{}</text>\n",
+                    (from.x + to.x) / 2.0,
+                    (from.y + to.y) / 2.0,
+                    FONT_SIZE / 2.0,
+                    escape_xml(description)
+                ));
+            }
+        }
+    }
+
+    for id in ids {
+        let node = match nodes.get(id) {
+            Some(node) => node,
+            None => continue,
+        };
+        let rect = &map.bounds[id];
+        svg.push_str(&format!("<a href=\"../nodes/{}\" id=\"node-{}\">\n", node_file(*id), id));
+        svg.push_str(&format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"{}\" fill=\"{}\"/>\n",
+            rect.left(),
+            rect.top(),
+            rect.width(),
+            rect.height(),
+            ROUNDED_RECT_BORDER_RADIUS,
+            css_color(&WRAPPED_NODE_BORDER_COLOR)
+        ));
+        svg.push_str(&format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"{}\" fill=\"{}\"/>\n",
+            rect.left() + WRAPPED_NODE_BORDER_SIZE,
+            rect.top() + WRAPPED_NODE_BORDER_SIZE,
+            (rect.width() - WRAPPED_NODE_BORDER_SIZE * 2.0).max(0.0),
+            (rect.height() - WRAPPED_NODE_BORDER_SIZE * 2.0).max(0.0),
+            ROUNDED_RECT_RADIUS,
+            css_color(&WRAPPED_NODE_COLOR)
+        ));
+
+        //one tspan per line, the first baseline about one font size below the top of the text
+        let frame = WRAPPED_NODE_BORDER_SIZE + WRAPPED_NODE_PADDING;
+        let x = rect.left() + frame;
+        svg.push_str(&format!("<text x=\"{}\" y=\"{}\" font-size=\"{}\">", x, rect.top() + frame + FONT_SIZE, FONT_SIZE));
+        for (index, line) in node.get_content().lines().enumerate() {
+            let dy = if index == 0 { 0.0 } else { FONT_SIZE * LINE_HEIGHT };
+            svg.push_str(&format!("<tspan x=\"{}\" dy=\"{}\">{}</tspan>", x, dy, escape_xml(line)));
+        }
+        svg.push_str("</text>\n</a>\n");
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn write_map_page(folder: &Path, map: &Map, nodes: &HashMap<i64, &Node>, links: &[Link], maps: &[Map]) -> Result<(), String> {
+    let body = format!("<h1>{}</h1>\n<div class=\"map\">\n{}</div>\n", escape_xml(&map.name), map_svg(map, nodes, links));
+    write_file(&folder.join("maps").join(&map.file).to_string_lossy(), &page(&map.name, "../", maps, &body))
+}
+
+fn write_index(folder: &Path, title: &str, nodes: &[Node], titles: &HashMap<i64, String>, maps: &[Map]) -> Result<(), String> {
+    let mut body = format!("<h1>{}</h1>\n", escape_xml(title));
+
+    if !maps.is_empty() {
+        body.push_str("<h2>Maps</h2>\n<ul>\n");
+        for map in maps {
+            body.push_str(&format!("<li><a href=\"maps/{}\">{}</a> ({} nodes)</li>\n", map.file, escape_xml(&map.name), map.bounds.len()));
+        }
+        body.push_str("</ul>\n");
+    }
+
+    //alphabetical, ties by id so that the order doesn't change between runs
+    let mut sorted: Vec<&Node> = nodes.iter().collect();
+    sorted.sort_by_key(|node| (titles[&node.get_id()].to_lowercase(), node.get_id()));
+
+    body.push_str("<h2>Nodes</h2>\n<ul class=\"nodes\">\n");
+    for node in sorted {
+        body.push_str(&format!(
+            "<li><a href=\"nodes/{}\">{}</a> {}</li>\n",
+            node_file(node.get_id()),
+            escape_xml(&titles[&node.get_id()]),
+            tags_html(node)
+        ));
+    }
+    body.push_str("</ul>\n");
+
+    //tag -> nodes, #hashtags included
+    let mut tags: BTreeMap<String, Vec<i64>> = BTreeMap::new();
+    for node in nodes {
+        for tag in node.get_all_tags() {
+            tags.entry(tag).or_default().push(node.get_id());
+        }
+    }
+    if !tags.is_empty() {
+        body.push_str("<h2>Tags</h2>\n<dl>\n");
+        for (tag, ids) in tags {
+            let items: Vec<String> = ids
+                .iter()
+                .map(|id| format!("<a href=\"nodes/{}\">{}</a>", node_file(*id), escape_xml(&titles[id])))
+                .collect();
+            body.push_str(&format!("<dt>{}</dt><dd>{}</dd>\n", escape_xml(&tag), items.join(", ")));
+        }
+        body.push_str("</dl>\n");
+    }
+
+    write_file(&folder.join("index.html").to_string_lossy(), &page(title, "", maps, &body))
+}
+
+//returns (node pages, map pages) written
+pub fn generate(folder: &str, title: &str) -> Result<(usize, usize), String> {
+    let folder = Path::new(folder);
+    for subfolder in [folder.join("nodes"), folder.join("maps")] {
+        std::fs::create_dir_all(&subfolder).map_err(|e| format!("can't create {}: {}", subfolder.display(), e))?;
+    }
+
+    let nodes: Vec<Node> = NODES.read().unwrap().iter().map(|node| node.read().unwrap().clone()).collect();
+    let links: Vec<Link> = LINKS.read().unwrap().iter().map(|link| link.read().unwrap().clone()).collect();
+    let titles: HashMap<i64, String> = nodes.iter().map(|node| (node.get_id(), node_title(node))).collect();
+    let by_id: HashMap<i64, &Node> = nodes.iter().map(|node| (node.get_id(), node)).collect();
+
+    //modules without a layout (or with nothing placed in it) get no map
+    let mut maps: Vec<Map> = Vec::new();
+    for module in MODULES.read().unwrap().iter() {
+        let module = module.read().unwrap();
+        let bounds = module.get_node_bounds();
+        if bounds.is_empty() {
+            continue;
+        }
+        let mut file = format!("{}.html", slug(&module.get_name()));
+        if maps.iter().any(|map| map.file == file) {
+            file = format!("{}-{}.html", slug(&module.get_name()), maps.len());
+        }
+        maps.push(Map { name: module.get_name(), file, bounds });
+    }
+
+    write_file(&folder.join("style.css").to_string_lossy(), STYLE)?;
+    std::fs::write(folder.join(FONT_FILE), FONT).map_err(|e| format!("can't write {}: {}", FONT_FILE, e))?;
+
+    write_index(folder, title, &nodes, &titles, &maps)?;
+    for node in &nodes {
+        write_node_page(folder, node, &titles, &links, &maps)?;
+    }
+    for map in &maps {
+        write_map_page(folder, map, &by_id, &links, &maps)?;
+    }
+
+    Ok((nodes.len(), maps.len()))
+}
@@ -1,9 +1,156 @@
 use crate::structs::node::Node;
+use core::sync::atomic::{AtomicI64, Ordering};
 use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 
+pub static LINK_COUNTER: AtomicI64 = AtomicI64::new(0);
+
+//which side of a node a link is attached to. Only a hint for layouts that care, like canvases
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkSide {
+    Top,
+    Right,
+    Bottom,
+    Left,
+}
+
+//whether a link goes one way, both ways or no particular way. It only changes the arrowheads
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkDirection {
+    #[default]
+    Directed,
+    Undirected,
+    Bidirectional,
+}
+
+impl LinkDirection {
+    pub const ALL: [LinkDirection; 3] = [LinkDirection::Directed, LinkDirection::Bidirectional, LinkDirection::Undirected];
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            LinkDirection::Directed => "directed",
+            LinkDirection::Undirected => "undirected",
+            LinkDirection::Bidirectional => "bidirectional",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<LinkDirection, String> {
+        LinkDirection::ALL.into_iter()
+            .find(|direction| direction.get_name() == name.trim().to_lowercase())
+            .ok_or(format!("unknown direction \"{}\" (directed, undirected, bidirectional)", name))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkStyle {
+    #[default]
+    Solid,
+    Dashed,
+    Dotted,
+}
+
+impl LinkStyle {
+    pub const ALL: [LinkStyle; 3] = [LinkStyle::Solid, LinkStyle::Dashed, LinkStyle::Dotted];
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            LinkStyle::Solid => "solid",
+            LinkStyle::Dashed => "dashed",
+            LinkStyle::Dotted => "dotted",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<LinkStyle, String> {
+        LinkStyle::ALL.into_iter()
+            .find(|style| style.get_name() == name.trim().to_lowercase())
+            .ok_or(format!("unknown style \"{}\" (solid, dashed, dotted)", name))
+    }
+}
+
+//what the "to" end of a link (and the "from" end of bidirectional ones) looks like
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArrowHead {
+    #[default]
+    Triangle,
+    Open, //two strokes, like ">"
+    Diamond,
+    Circle,
+    None,
+}
+
+impl ArrowHead {
+    pub const ALL: [ArrowHead; 5] = [ArrowHead::Triangle, ArrowHead::Open, ArrowHead::Diamond, ArrowHead::Circle, ArrowHead::None];
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            ArrowHead::Triangle => "triangle",
+            ArrowHead::Open => "open",
+            ArrowHead::Diamond => "diamond",
+            ArrowHead::Circle => "circle",
+            ArrowHead::None => "none",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<ArrowHead, String> {
+        ArrowHead::ALL.into_iter()
+            .find(|arrow| arrow.get_name() == name.trim().to_lowercase())
+            .ok_or(format!("unknown arrowhead \"{}\" (triangle, open, diamond, circle, none)", name))
+    }
+}
+
+//a kind of link, defined per workspace (in the settings). Links name it in their relation; relations that
+//aren't defined here (like the ones bibtex imports make) are drawn like plain links
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LinkType {
+    pub name: String,
+    pub color: String, //"#rrggbb"
+    #[serde(default)]
+    pub arrow: ArrowHead,
+    #[serde(default)]
+    pub style: LinkStyle,
+}
+
+impl LinkType {
+    pub fn new(name: &str, color: &str, arrow: ArrowHead, style: LinkStyle) -> LinkType {
+        LinkType { name: name.to_string(), color: color.to_string(), arrow, style }
+    }
+
+    //the ones every workspace starts with
+    pub fn get_defaults() -> Vec<LinkType> {
+        vec![
+            LinkType::new("supports", "#43a047", ArrowHead::Triangle, LinkStyle::Solid),
+            LinkType::new("contradicts", "#e53935", ArrowHead::Diamond, LinkStyle::Dashed),
+            LinkType::new("depends-on", "#fb8c00", ArrowHead::Open, LinkStyle::Solid),
+            LinkType::new("references", "#607d8b", ArrowHead::Circle, LinkStyle::Dotted),
+        ]
+    }
+
+    //0xRRGGBB, None if the color isn't "#rrggbb" (or "#rgb")
+    pub fn get_rgb(&self) -> Option<u32> {
+        parse_color(&self.color)
+    }
+}
+
+pub fn parse_color(color: &str) -> Option<u32> {
+    let hex = color.trim().trim_start_matches('#');
+    let hex = match hex.len() {
+        3 => hex.chars().flat_map(|c| [c, c]).collect(),
+        6 => hex.to_string(),
+        _ => return None,
+    };
+    u32::from_str_radix(&hex, 16).ok()
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Link {
     id: i64,
@@ -14,4 +161,109 @@ pub struct Link {
     from_id: i64,
     to_id: i64,
     owner: String,
+    #[serde(default)]
+    label: String,
+    #[serde(default)]
+    relation: String, //what kind of link this is ("supports", "cites"...), free text
+    #[serde(default)]
+    sides: (Option<LinkSide>, Option<LinkSide>), //(from, to)
+    #[serde(default)]
+    direction: LinkDirection,
+    #[serde(default = "default_weight")]
+    weight: f32, //how strong the link is, drawn as its thickness
+    #[serde(default)]
+    style: Option<LinkStyle>, //None: whatever its type says
+}
+
+impl Link {
+    pub(crate) fn create_and_register(from_id: i64, to_id: i64, owner: String) -> Link {
+        let id = LINK_COUNTER.fetch_add(1, Ordering::SeqCst);
+        Link {
+            id,
+            from: Arc::new(Mutex::new(Node::null())),
+            to: Arc::new(Mutex::new(Node::null())),
+            from_id,
+            to_id,
+            owner,
+            label: String::new(),
+            relation: String::new(),
+            sides: (None, None),
+            direction: LinkDirection::Directed,
+            weight: 1.0,
+            style: None,
+        }
+    }
+
+    //see Node::create_with_id
+    pub(crate) fn create_with_id(id: i64, from_id: i64, to_id: i64, owner: String) -> Link {
+        LINK_COUNTER.fetch_max(id + 1, Ordering::SeqCst);
+        Link {
+            id,
+            ..Link::create_and_register(from_id, to_id, owner)
+        }
+    }
+
+    pub fn get_id(&self) -> i64 {
+        self.id
+    }
+
+    pub fn get_from_id(&self) -> i64 {
+        self.from_id
+    }
+
+    pub fn get_to_id(&self) -> i64 {
+        self.to_id
+    }
+
+    pub fn get_owner(&self) -> &String {
+        &self.owner
+    }
+
+    pub fn get_label(&self) -> &String {
+        &self.label
+    }
+
+    pub fn set_label(&mut self, label: String) {
+        self.label = label;
+    }
+
+    pub fn get_relation(&self) -> &String {
+        &self.relation
+    }
+
+    pub fn set_relation(&mut self, relation: String) {
+        self.relation = relation;
+    }
+
+    pub fn get_sides(&self) -> (Option<LinkSide>, Option<LinkSide>) {
+        self.sides
+    }
+
+    pub fn set_sides(&mut self, sides: (Option<LinkSide>, Option<LinkSide>)) {
+        self.sides = sides;
+    }
+
+    pub fn get_direction(&self) -> LinkDirection {
+        self.direction
+    }
+
+    pub fn set_direction(&mut self, direction: LinkDirection) {
+        self.direction = direction;
+    }
+
+    pub fn get_weight(&self) -> f32 {
+        self.weight
+    }
+
+    pub fn set_weight(&mut self, weight: f32) {
+        self.weight = weight;
+    }
+
+    pub fn get_style(&self) -> Option<LinkStyle> {
+        self.style
+    }
+
+    pub fn set_style(&mut self, style: Option<LinkStyle>) {
+        self.style = style;
+    }
 }
@@ -2,4 +2,7 @@ pub mod link;
 pub mod module;
 pub mod mouse_position;
 pub mod node;
+pub mod property;
+pub mod rich_text;
+pub mod task;
 mod keyboard_shortcut;
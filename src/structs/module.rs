@@ -1,4 +1,6 @@
+use crate::headless::Raster;
 use crate::structs::mouse_position::MousePosition;
+use crate::structs::node::Node;
 use speedy2d::dimen::Vec2;
 use speedy2d::shape::Rect;
 use speedy2d::window::{KeyScancode, MouseButton, MouseScrollDistance, VirtualKeyCode};
@@ -6,6 +8,7 @@ use speedy2d::Graphics2D;
 use std::collections::HashMap;
 use std::fs::File;
 use std::iter::Map;
+use std::sync::{Arc, RwLock};
 
 pub trait Module {
 
@@ -18,8 +21,57 @@ pub trait Module {
 
     fn get_name(&self) -> String;
 
+    //where the module is, for the top panel: its name, then whatever it has opened inside itself
+    fn get_breadcrumbs(&self) -> Vec<String> {
+        vec![self.get_name()]
+    }
+
+    //the breadcrumb at index was clicked, 0 being the module itself
+    fn open_breadcrumb(&mut self, _index: usize) {
+        // do nothing
+    }
+
     fn draw(&mut self, graphics: &mut Graphics2D, viewport: Rect, delta_time: f64);
 
+    //draws the module's current layout into an image without a window. Err if the module can't do that
+    //(or the image would be too big, see Raster::new)
+    fn render_headless(&mut self, _scale: f32) -> Result<Raster, String> {
+        Err(format!("module \"{}\" has no layout to render", self.get_name()))
+    }
+
+    //whether the module shows workspace nodes at all. The ones that don't can't be sent nodes, the node
+    //functions below do nothing in them
+    fn shows_nodes(&self) -> bool {
+        false
+    }
+
+    //bounds (in the module's own layout coordinates) of every node the module shows, by node id.
+    //importers and exporters use this to carry positions around
+    fn get_node_bounds(&self) -> HashMap<i64, Rect> {
+        HashMap::new()
+    }
+
+    //shows a node in the module with the top left corner at position. size is a hint, modules are free to ignore it
+    fn place_node(&mut self, _node: Arc<RwLock<Node>>, _position: (f32, f32), _size: Option<(f32, f32)>) {
+        // do nothing
+    }
+
+    //stops showing a node, wherever in the module it is
+    fn remove_node(&mut self, _id: i64) {
+        // do nothing
+    }
+
+    //brings a node into view (and selects it) once the module is open. false if the module doesn't show it
+    fn show_node(&mut self, _id: i64) -> bool {
+        false
+    }
+
+    //something for the user from outside the module (the window), shown like the module's own messages.
+    //modules with nowhere to show it drop it
+    fn show_notice(&mut self, _result: Result<String, String>) {
+        // do nothing
+    }
+
     fn open(&mut self) {}
     fn close(&mut self) {}
 
@@ -1,60 +1,298 @@
 use core::sync::atomic::{AtomicI64, Ordering};
-use serde::{Deserialize, Serialize};
-use std::sync::{Arc, Mutex};
+use crate::attachments::Attachment;
+use crate::structs::property::Property;
+use crate::structs::rich_text::RichText;
+use crate::structs::task::Task;
+use crate::utils::{find_hashtags, now};
+use crate::workspace;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
 
 pub static NODE_COUNTER: AtomicI64 = AtomicI64::new(0);
 
+//older data files have a single "owner" instead, the module that made the node
+fn read_modules<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BTreeSet<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Modules {
+        Owner(String),
+        Set(BTreeSet<String>),
+    }
+    Ok(match Modules::deserialize(deserializer)? {
+        Modules::Owner(owner) => BTreeSet::from([owner]),
+        Modules::Set(modules) => modules,
+    })
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Node {
     id: i64,
-    content: String,
-    owner: String,
-    #[serde(skip)]
-    links: Vec<Arc<Mutex<Node>>>,
+    content: RichText, //plain strings in older data files load as unformatted text
+    #[serde(default, alias = "owner", deserialize_with = "read_modules")]
+    modules: BTreeSet<String>, //names of the modules showing the node, see membership.rs
+    #[serde(default)]
+    tags: BTreeSet<String>, //the ones given explicitly, #hashtags in the content come on top (see get_all_tags)
+    #[serde(default)]
+    attachments: Vec<Attachment>, //files in the workspace's store, or paths and urls of files elsewhere
+    #[serde(default)]
+    properties: BTreeMap<String, Property>, //text ones are plain strings in the data files, like they used to be
+    #[serde(default)]
+    created: Option<i64>, //unix time. None for nodes from before these were kept
+    #[serde(default)]
+    modified: Option<i64>, //unix time of the last change to the content, tags, attachments or properties
+    #[serde(default)]
+    author: String, //from the settings of the workspace the node was made in
+    #[serde(default)]
+    parent: Option<i64>, //see hierarchy.rs, the tree is kept apart from links
+    #[serde(default)]
+    children: Vec<i64>, //in order
+    #[serde(default)]
+    task: Option<Task>, //None for nodes that aren't todos
 }
 
 impl Default for Node {
     fn default() -> Self {
         Node {
             id: -1,
-            content: "null".to_string(),
-            owner: "null".to_string(),
-            links: Vec::new(),
+            content: RichText::plain("null"),
+            modules: BTreeSet::new(),
+            tags: BTreeSet::new(),
+            attachments: Vec::new(),
+            properties: BTreeMap::new(),
+            created: None,
+            modified: None,
+            author: String::new(),
+            parent: None,
+            children: Vec::new(),
+            task: None,
         }
     }
 }
 
 impl Node {
+    //the content as plain text, formatting dropped
     pub fn get_content(&self) -> &String {
-        &self.content
+        self.content.get_plain_text()
     }
 
+    //replaces the content with unformatted text
     pub fn set_content(&mut self, string: String) {
-        self.content = string;
+        self.content = RichText::plain(&string);
+        self.touch();
+    }
+
+    pub fn get_rich_content(&self) -> &RichText {
+        &self.content
+    }
+
+    //changes the content in place. Touched after the change, so the tag index sees the new text
+    pub fn edit_content(&mut self, action: impl FnOnce(&mut RichText)) {
+        action(&mut self.content);
+        self.touch();
+    }
+
+    pub fn set_rich_content(&mut self, content: RichText) {
+        self.content = content;
+        self.touch();
+    }
+
+    pub fn get_created(&self) -> Option<i64> {
+        self.created
+    }
+
+    pub fn get_modified(&self) -> Option<i64> {
+        self.modified
+    }
+
+    //for importers that know better than "now"
+    pub fn set_timestamps(&mut self, created: Option<i64>, modified: Option<i64>) {
+        self.created = created;
+        self.modified = modified;
+    }
+
+    pub fn get_author(&self) -> &String {
+        &self.author
+    }
+
+    pub fn set_author(&mut self, author: String) {
+        self.author = author;
+    }
+
+    //every change goes through here, which keeps the workspace tag index up to date
+    fn touch(&mut self) {
+        self.modified = Some(now());
+        workspace::index_node(self);
+    }
+
+    pub fn get_modules(&self) -> &BTreeSet<String> {
+        &self.modules
+    }
+
+    pub fn is_in(&self, module: &str) -> bool {
+        self.modules.contains(module)
+    }
+
+    //where a node is shown isn't a change to it. Only membership.rs and the modules themselves call these
+    pub(crate) fn add_module(&mut self, module: String) -> bool {
+        self.modules.insert(module)
+    }
+
+    pub(crate) fn remove_module(&mut self, module: &str) -> bool {
+        self.modules.remove(module)
+    }
+
+    pub fn get_tags(&self) -> &BTreeSet<String> {
+        &self.tags
+    }
+
+    //#hashtags in the content, code left out
+    pub fn get_hashtags(&self) -> Vec<String> {
+        find_hashtags(&self.content.to_markdown())
     }
 
-    pub fn get_owner(&self) -> &String {
-        &self.owner
+    pub fn get_all_tags(&self) -> BTreeSet<String> {
+        let mut tags = self.tags.clone();
+        tags.extend(self.get_hashtags());
+        tags
     }
 
-    pub fn set_owner(&mut self, string: String) {
-        self.owner = string;
+    //ignores case and a leading "#"
+    pub fn has_tag(&self, tag: &str) -> bool {
+        let tag = workspace::normalize_tag(tag);
+        self.get_all_tags().iter().any(|own| workspace::normalize_tag(own) == tag)
     }
 
-    pub fn get_links(&self) -> &Vec<Arc<Mutex<Node>>> {
-        &self.links
+    pub fn add_tag(&mut self, tag: String) {
+        self.tags.insert(tag);
+        self.touch();
     }
 
+    pub fn remove_tag(&mut self, tag: &str) {
+        self.tags.remove(tag);
+        self.touch();
+    }
+
+    pub fn get_attachments(&self) -> &Vec<Attachment> {
+        &self.attachments
+    }
 
-    pub(crate) fn create_and_register(content: String, owner: String) -> Node {
-        let id = NODE_COUNTER.fetch_add(1, Ordering::SeqCst);
-        let node = Node {
+    //attaching the same file (or url) again does nothing
+    pub fn add_attachment(&mut self, attachment: Attachment) {
+        if self.attachments.iter().any(|attached| attached.get_key() == attachment.get_key()) {
+            return;
+        }
+        self.attachments.push(attachment);
+        self.touch();
+    }
+
+    pub fn remove_attachment(&mut self, index: usize) {
+        if index < self.attachments.len() {
+            self.attachments.remove(index);
+            self.touch();
+        }
+    }
+
+    pub fn get_properties(&self) -> &BTreeMap<String, Property> {
+        &self.properties
+    }
+
+    //the value as text, whatever its kind
+    pub fn get_property(&self, key: &str) -> Option<&String> {
+        self.properties.get(key).map(|property| property.get_text())
+    }
+
+    pub fn get_typed_property(&self, key: &str) -> Option<&Property> {
+        self.properties.get(key)
+    }
+
+    //keeps the kind the property already has if the value fits it, otherwise it becomes text
+    pub fn set_property(&mut self, key: String, value: String) {
+        let property = self.properties.get(&key)
+            .and_then(|old| Property::parse(old.get_kind(), &value).ok())
+            .unwrap_or(Property::text(value));
+        self.set_typed_property(key, property);
+    }
+
+    pub fn set_typed_property(&mut self, key: String, property: Property) {
+        self.properties.insert(key, property);
+        self.touch();
+    }
+
+    pub fn remove_property(&mut self, key: &str) {
+        self.properties.remove(key);
+        self.touch();
+    }
+
+    pub fn get_task(&self) -> Option<&Task> {
+        self.task.as_ref()
+    }
+
+    //None makes the node a plain note again
+    pub fn set_task(&mut self, task: Option<Task>) {
+        self.task = task;
+        self.touch();
+    }
+
+    pub fn get_parent(&self) -> Option<i64> {
+        self.parent
+    }
+
+    pub fn get_children(&self) -> &Vec<i64> {
+        &self.children
+    }
+
+    //only hierarchy.rs changes these, so that parents and children always agree. Moving a node around in the
+    //tree doesn't count as a change to it
+    pub(crate) fn set_parent(&mut self, parent: Option<i64>) {
+        self.parent = parent;
+    }
+
+    pub(crate) fn get_children_mut(&mut self) -> &mut Vec<i64> {
+        &mut self.children
+    }
+
+
+    //module is the one the node is made in, the first to show it
+    pub(crate) fn create_and_register(content: String, module: String) -> Node {
+        Node::new(NODE_COUNTER.fetch_add(1, Ordering::SeqCst), content, module)
+    }
+
+    fn new(id: i64, content: String, module: String) -> Node {
+        Node {
             id,
-            content,
-            owner,
-            links: Vec::new(),
-        };
-        node
+            content: RichText::plain(&content),
+            modules: BTreeSet::from([module]),
+            tags: BTreeSet::new(),
+            attachments: Vec::new(),
+            properties: BTreeMap::new(),
+            created: Some(now()),
+            modified: Some(now()),
+            author: workspace::get_settings().author,
+            parent: None,
+            children: Vec::new(),
+            task: None,
+        }
+    }
+
+    //same as create_and_register but with a given id, for importers that want to keep the ids they read.
+    //the caller makes sure that the id is not taken
+    pub(crate) fn create_with_id(id: i64, content: String, module: String) -> Node {
+        NODE_COUNTER.fetch_max(id + 1, Ordering::SeqCst);
+        Node::new(id, content, module)
+    }
+
+    //a new node with the same content, tags, attachments, properties and task, in the same modules but outside
+    //the hierarchy. Like create_and_register, the caller registers it
+    pub(crate) fn duplicate(&self) -> Node {
+        Node {
+            id: NODE_COUNTER.fetch_add(1, Ordering::SeqCst),
+            created: Some(now()),
+            modified: Some(now()),
+            author: workspace::get_settings().author,
+            parent: None,
+            children: Vec::new(),
+            ..self.clone()
+        }
     }
 
     pub fn null() -> Node {
@@ -0,0 +1,275 @@
+use crate::utils::{civil_to_timestamp, timestamp_to_civil};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+//node properties are text unless they say otherwise. Text is saved as a plain string like before, the other
+//kinds as {"type": "number", "value": 3}. Kinds this version doesn't know (written by a newer one) are kept
+//the way they were read and saved back untouched, they can only be replaced, not edited.
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PropertyKind {
+    Text,
+    Number,
+    Date, //"2024-05-01", optionally with a time: "2024-05-01 10:00"
+    Boolean,
+    Url,
+    Node, //id of another node
+    Unknown(String),
+}
+
+//the kinds that can be picked, in the order the inspector goes through them
+pub const PROPERTY_KINDS: [PropertyKind; 6] = [
+    PropertyKind::Text,
+    PropertyKind::Number,
+    PropertyKind::Date,
+    PropertyKind::Boolean,
+    PropertyKind::Url,
+    PropertyKind::Node,
+];
+
+impl PropertyKind {
+    pub fn get_name(&self) -> &str {
+        match self {
+            PropertyKind::Text => "text",
+            PropertyKind::Number => "number",
+            PropertyKind::Date => "date",
+            PropertyKind::Boolean => "boolean",
+            PropertyKind::Url => "url",
+            PropertyKind::Node => "node",
+            PropertyKind::Unknown(name) => name,
+        }
+    }
+
+    pub fn parse(name: &str) -> PropertyKind {
+        PROPERTY_KINDS.iter()
+            .find(|kind| kind.get_name() == name)
+            .cloned()
+            .unwrap_or(PropertyKind::Unknown(name.to_string()))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(from = "PropertyData", into = "PropertyData")]
+pub struct Property {
+    kind: PropertyKind,
+    text: String, //the value as text, in one canonical form for every known kind
+    raw: Option<Value>, //unknown kinds only
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum PropertyData {
+    Plain(String),
+    Typed {
+        #[serde(rename = "type")]
+        kind: String,
+        value: Value,
+    },
+    Other(Value), //not something rmaps writes, read as text
+}
+
+impl From<PropertyData> for Property {
+    fn from(data: PropertyData) -> Self {
+        match data {
+            PropertyData::Plain(text) => Property::text(text),
+            PropertyData::Typed { kind, value } => {
+                let kind = PropertyKind::parse(&kind);
+                //a known kind with a value that doesn't fit it is as good as unknown, keep it as it is
+                let text = match (&kind, &value) {
+                    (PropertyKind::Number, Value::Number(number)) => number.as_f64().map(format_number),
+                    (PropertyKind::Boolean, Value::Bool(value)) => Some(value.to_string()),
+                    (PropertyKind::Node, Value::Number(number)) => number.as_i64().map(|id| id.to_string()),
+                    (PropertyKind::Text | PropertyKind::Date | PropertyKind::Url, Value::String(text)) => {
+                        Property::parse(&kind, text).ok().map(|property| property.text)
+                    }
+                    _ => None,
+                };
+                match text {
+                    Some(text) => Property { kind, text, raw: None },
+                    None => Property {
+                        kind: PropertyKind::Unknown(kind.get_name().to_string()),
+                        text: value_text(&value),
+                        raw: Some(value),
+                    },
+                }
+            }
+            PropertyData::Other(value) => Property::text(value_text(&value)),
+        }
+    }
+}
+
+impl From<Property> for PropertyData {
+    fn from(property: Property) -> Self {
+        let value = match property.kind {
+            PropertyKind::Text => return PropertyData::Plain(property.text),
+            PropertyKind::Number => {
+                let number = property.as_number().unwrap_or_default();
+                if number.fract() == 0.0 && number.abs() < 9e15 {
+                    Value::from(number as i64)
+                } else {
+                    Value::from(number)
+                }
+            }
+            PropertyKind::Boolean => Value::Bool(property.as_bool().unwrap_or_default()),
+            PropertyKind::Node => Value::from(property.as_node().unwrap_or_default()),
+            PropertyKind::Date | PropertyKind::Url => Value::String(property.text),
+            PropertyKind::Unknown(_) => property.raw.unwrap_or(Value::String(property.text)),
+        };
+        PropertyData::Typed { kind: property.kind.get_name().to_string(), value }
+    }
+}
+
+fn value_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        value => value.to_string(),
+    }
+}
+
+//"3" rather than "3.0"
+fn format_number(number: f64) -> String {
+    if number.fract() == 0.0 && number.abs() < 9e15 {
+        (number as i64).to_string()
+    } else {
+        number.to_string()
+    }
+}
+
+//"2024-05-01" or "2024-05-01 10:00" -> (midnight of the day, minutes into it if there was a time)
+fn parse_date(text: &str) -> Option<(i64, Option<i64>)> {
+    let (date, time) = match text.split_once(|c: char| c == ' ' || c == 'T') {
+        Some((date, time)) => (date, Some(time)),
+        None => (text, None),
+    };
+    let mut parts = date.splitn(3, '-');
+    let year: i32 = parts.next()?.parse().ok()?;
+    let month: u32 = parts.next()?.parse().ok()?;
+    let day: u32 = parts.next()?.parse().ok()?;
+    let midnight = civil_to_timestamp(year, month, day);
+    //the 31st of february comes back as some day in march
+    if timestamp_to_civil(midnight).0 != (year, month, day) {
+        return None;
+    }
+
+    let minutes = match time {
+        Some(time) => {
+            let (hour, minute) = time.split_once(':')?;
+            let (hour, minute): (i64, i64) = (hour.parse().ok()?, minute.get(..2).unwrap_or(minute).parse().ok()?);
+            if !(0..24).contains(&hour) || !(0..60).contains(&minute) {
+                return None;
+            }
+            Some(hour * 60 + minute)
+        }
+        None => None,
+    };
+    Some((midnight, minutes))
+}
+
+impl Property {
+    pub fn text(text: String) -> Property {
+        Property { kind: PropertyKind::Text, text, raw: None }
+    }
+
+    pub fn number(number: f64) -> Property {
+        Property { kind: PropertyKind::Number, text: format_number(number), raw: None }
+    }
+
+    pub fn node(id: i64) -> Property {
+        Property { kind: PropertyKind::Node, text: id.to_string(), raw: None }
+    }
+
+    //checks that the text is a value of that kind and writes it the one way it's kept
+    pub fn parse(kind: &PropertyKind, text: &str) -> Result<Property, String> {
+        let trimmed = text.trim();
+        let text = match kind {
+            PropertyKind::Text => text.to_string(),
+            PropertyKind::Number => match trimmed.parse::<f64>() {
+                Ok(number) if number.is_finite() => format_number(number),
+                _ => return Err(format!("\"{}\" is not a number", trimmed)),
+            },
+            PropertyKind::Date => match parse_date(trimmed) {
+                Some((midnight, minutes)) => {
+                    let (year, month, day) = timestamp_to_civil(midnight).0;
+                    match minutes {
+                        Some(minutes) => format!("{:04}-{:02}-{:02} {:02}:{:02}", year, month, day, minutes / 60, minutes % 60),
+                        None => format!("{:04}-{:02}-{:02}", year, month, day),
+                    }
+                }
+                None => return Err(format!("\"{}\" is not a date like 2024-05-01 or 2024-05-01 10:00", trimmed)),
+            },
+            PropertyKind::Boolean => match trimmed.to_lowercase().as_str() {
+                "true" | "yes" | "on" | "1" => "true".to_string(),
+                "false" | "no" | "off" | "0" => "false".to_string(),
+                _ => return Err(format!("\"{}\" is not true or false", trimmed)),
+            },
+            PropertyKind::Url => {
+                if trimmed.is_empty() || trimmed.contains(char::is_whitespace) || !(trimmed.contains("://") || trimmed.starts_with("mailto:")) {
+                    return Err(format!("\"{}\" is not a url", trimmed));
+                }
+                trimmed.to_string()
+            }
+            PropertyKind::Node => match trimmed.trim_start_matches('#').parse::<i64>() {
+                Ok(id) if id >= 0 => id.to_string(),
+                _ => return Err(format!("\"{}\" is not a node id", trimmed)),
+            },
+            PropertyKind::Unknown(name) => return Err(format!("this version doesn't know how to write {} properties", name)),
+        };
+        Ok(Property { kind: kind.clone(), text, raw: None })
+    }
+
+    //the most specific kind the text fits, text if nothing else does. Node ids look like numbers, so never those
+    pub fn guess(text: &str) -> Property {
+        let kinds = [PropertyKind::Number, PropertyKind::Date, PropertyKind::Url];
+        let boolean = matches!(text.trim(), "true" | "false");
+        if boolean {
+            return Property::parse(&PropertyKind::Boolean, text).unwrap();
+        }
+        kinds.iter()
+            .find_map(|kind| Property::parse(kind, text).ok())
+            .unwrap_or(Property::text(text.to_string()))
+    }
+
+    pub fn get_kind(&self) -> &PropertyKind {
+        &self.kind
+    }
+
+    pub fn get_text(&self) -> &String {
+        &self.text
+    }
+
+    pub fn as_number(&self) -> Option<f64> {
+        match self.kind {
+            PropertyKind::Number => self.text.parse().ok(),
+            _ => None,
+        }
+    }
+
+    //unix time (UTC)
+    pub fn as_date(&self) -> Option<i64> {
+        match self.kind {
+            PropertyKind::Date => parse_date(&self.text).map(|(midnight, minutes)| midnight + minutes.unwrap_or(0) * 60),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self.kind {
+            PropertyKind::Boolean => Some(self.text == "true"),
+            _ => None,
+        }
+    }
+
+    pub fn as_url(&self) -> Option<&str> {
+        match self.kind {
+            PropertyKind::Url => Some(&self.text),
+            _ => None,
+        }
+    }
+
+    pub fn as_node(&self) -> Option<i64> {
+        match self.kind {
+            PropertyKind::Node => self.text.parse().ok(),
+            _ => None,
+        }
+    }
+}
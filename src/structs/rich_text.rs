@@ -0,0 +1,458 @@
+use serde::{Deserialize, Serialize};
+
+//node content with some formatting: paragraphs (optionally list items) made of spans of styled text, spans can
+//link somewhere. Content without any formatting is saved as a plain string like before, and plain strings
+//load as unformatted paragraphs, one per line.
+//
+//editing works on "positions": the characters of every paragraph with one extra position between paragraphs
+//for the line break, list markers not included.
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Style {
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub bold: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub italic: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub code: bool,
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StyleFlag {
+    Bold,
+    Italic,
+    Code,
+}
+
+impl Style {
+    pub fn has(&self, flag: StyleFlag) -> bool {
+        match flag {
+            StyleFlag::Bold => self.bold,
+            StyleFlag::Italic => self.italic,
+            StyleFlag::Code => self.code,
+        }
+    }
+
+    pub fn set(&mut self, flag: StyleFlag, value: bool) {
+        match flag {
+            StyleFlag::Bold => self.bold = value,
+            StyleFlag::Italic => self.italic = value,
+            StyleFlag::Code => self.code = value,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Span {
+    pub text: String,
+    #[serde(flatten)]
+    pub style: Style,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link: Option<String>, //url, or anything else the text points to
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ListKind {
+    Bullet,
+    Numbered,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Paragraph {
+    #[serde(default)]
+    pub spans: Vec<Span>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub list: Option<ListKind>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub level: usize, //how deep a list item is nested
+}
+
+fn is_zero(value: &usize) -> bool {
+    *value == 0
+}
+
+impl Paragraph {
+    pub fn get_text(&self) -> String {
+        self.spans.iter().map(|span| span.text.as_str()).collect()
+    }
+
+    fn len(&self) -> usize {
+        self.spans.iter().map(|span| span.text.chars().count()).sum()
+    }
+
+    fn is_plain(&self) -> bool {
+        self.list.is_none() && self.level == 0 && self.spans.iter().all(|span| span.style == Style::default() && span.link.is_none())
+    }
+}
+
+//one character and its formatting, for editing
+#[derive(Clone, Debug, PartialEq)]
+struct StyledChar {
+    character: char,
+    style: Style,
+    link: Option<String>,
+}
+
+fn explode(paragraph: &Paragraph) -> Vec<StyledChar> {
+    paragraph.spans.iter()
+        .flat_map(|span| span.text.chars().map(move |character| StyledChar { character, style: span.style, link: span.link.clone() }))
+        .collect()
+}
+
+//neighbouring characters with the same formatting end up in the same span
+fn implode(characters: Vec<StyledChar>) -> Vec<Span> {
+    let mut spans: Vec<Span> = Vec::new();
+    for character in characters {
+        match spans.last_mut() {
+            Some(span) if span.style == character.style && span.link == character.link => span.text.push(character.character),
+            _ => spans.push(Span { text: character.character.to_string(), style: character.style, link: character.link }),
+        }
+    }
+    spans
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(from = "RichTextData", into = "RichTextData")]
+pub struct RichText {
+    paragraphs: Vec<Paragraph>,
+    plain_text: String, //kept up to date by every change, so that get_plain_text can hand out a reference
+}
+
+//what goes into the data files: a string when there's no formatting, so that old workspaces load and
+//unformatted content keeps looking like it always did
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum RichTextData {
+    Plain(String),
+    Rich { paragraphs: Vec<Paragraph> },
+}
+
+impl Default for RichText {
+    fn default() -> Self {
+        RichText::plain("")
+    }
+}
+
+impl From<RichTextData> for RichText {
+    fn from(data: RichTextData) -> Self {
+        match data {
+            RichTextData::Plain(text) => RichText::plain(&text),
+            RichTextData::Rich { paragraphs } => RichText::new(paragraphs),
+        }
+    }
+}
+
+impl From<RichText> for RichTextData {
+    fn from(text: RichText) -> Self {
+        if text.is_plain() {
+            RichTextData::Plain(text.plain_text)
+        } else {
+            RichTextData::Rich { paragraphs: text.paragraphs }
+        }
+    }
+}
+
+impl RichText {
+    pub fn new(paragraphs: Vec<Paragraph>) -> RichText {
+        let mut text = RichText { paragraphs, plain_text: String::new() };
+        text.update();
+        text
+    }
+
+    //one unformatted paragraph per line
+    pub fn plain(text: &str) -> RichText {
+        RichText::new(text.split('\n').map(|line| Paragraph {
+            spans: implode(line.chars().map(|character| StyledChar { character, style: Style::default(), link: None }).collect()),
+            list: None,
+            level: 0,
+        }).collect())
+    }
+
+    pub fn get_paragraphs(&self) -> &Vec<Paragraph> {
+        &self.paragraphs
+    }
+
+    //paragraphs on their own lines, list items with "- " or "1. " in front
+    pub fn get_plain_text(&self) -> &String {
+        &self.plain_text
+    }
+
+    pub fn is_plain(&self) -> bool {
+        self.paragraphs.iter().all(Paragraph::is_plain)
+    }
+
+    //"-" or "1.", "2."... for every paragraph that is a list item. Numbering restarts after anything that
+    //isn't a deeper list item
+    pub fn get_list_markers(&self) -> Vec<Option<String>> {
+        let mut numbers: Vec<usize> = Vec::new();
+        self.paragraphs.iter().map(|paragraph| {
+            let kind = match paragraph.list {
+                Some(kind) => kind,
+                None => {
+                    numbers.clear();
+                    return None;
+                }
+            };
+            numbers.resize(paragraph.level + 1, 0);
+            match kind {
+                ListKind::Bullet => {
+                    numbers[paragraph.level] = 0;
+                    Some("-".to_string())
+                }
+                ListKind::Numbered => {
+                    numbers[paragraph.level] += 1;
+                    Some(format!("{}.", numbers[paragraph.level]))
+                }
+            }
+        }).collect()
+    }
+
+    //**bold**, *italic*, `code`, [text](link) and lists
+    pub fn to_markdown(&self) -> String {
+        let markers = self.get_list_markers();
+        let lines: Vec<String> = self.paragraphs.iter().zip(markers).map(|(paragraph, marker)| {
+            let mut line = String::new();
+            if let Some(marker) = marker {
+                line.push_str(&format!("{}{} ", "  ".repeat(paragraph.level), marker));
+            }
+            for span in &paragraph.spans {
+                let mut text = if span.style.code {
+                    format!("`{}`", span.text)
+                } else {
+                    span.text.clone()
+                };
+                if span.style.italic {
+                    text = format!("*{}*", text);
+                }
+                if span.style.bold {
+                    text = format!("**{}**", text);
+                }
+                if let Some(link) = &span.link {
+                    text = format!("[{}]({})", text, link);
+                }
+                line.push_str(&text);
+            }
+            line
+        }).collect();
+        lines.join("\n")
+    }
+
+    fn update(&mut self) {
+        if self.paragraphs.is_empty() {
+            self.paragraphs.push(Paragraph::default());
+        }
+        for paragraph in &mut self.paragraphs {
+            paragraph.spans = implode(explode(paragraph));
+        }
+
+        let markers = self.get_list_markers();
+        let lines: Vec<String> = self.paragraphs.iter().zip(markers).map(|(paragraph, marker)| match marker {
+            Some(marker) => format!("{}{} {}", "  ".repeat(paragraph.level), marker, paragraph.get_text()),
+            None => paragraph.get_text(),
+        }).collect();
+        self.plain_text = lines.join("\n");
+    }
+
+    //number of positions
+    pub fn len(&self) -> usize {
+        self.paragraphs.iter().map(Paragraph::len).sum::<usize>() + self.paragraphs.len() - 1
+    }
+
+    //position -> (paragraph, character in the paragraph)
+    pub fn locate(&self, position: usize) -> (usize, usize) {
+        let mut start = 0;
+        for (index, paragraph) in self.paragraphs.iter().enumerate() {
+            let length = paragraph.len();
+            if position <= start + length {
+                return (index, position - start);
+            }
+            start += length + 1;
+        }
+        let last = self.paragraphs.len() - 1;
+        (last, self.paragraphs[last].len())
+    }
+
+    //position where a paragraph starts
+    pub fn get_paragraph_start(&self, index: usize) -> usize {
+        self.paragraphs.iter().take(index).map(|paragraph| paragraph.len() + 1).sum()
+    }
+
+    //formatting of the character before the position (the first one at the start of a paragraph). Links only
+    //carry on when the character after the position is part of the same link
+    pub fn get_style_at(&self, position: usize) -> (Style, Option<String>) {
+        let (index, offset) = self.locate(position);
+        let characters = explode(&self.paragraphs[index]);
+        let before = characters.get(offset.max(1) - 1);
+        let after = characters.get(offset);
+        match before {
+            Some(before) => {
+                let link = before.link.clone().filter(|_| after.map_or(false, |after| after.link == before.link));
+                (before.style, link)
+            }
+            None => (Style::default(), None),
+        }
+    }
+
+    //"\n" starts a new paragraph, which keeps the list kind of the one it was split from
+    pub fn insert(&mut self, position: usize, text: &str, style: Style, link: Option<String>) {
+        let (mut index, mut offset) = self.locate(position);
+        for character in text.chars() {
+            let mut characters = explode(&self.paragraphs[index]);
+            if character == '\n' {
+                let rest = characters.split_off(offset);
+                self.paragraphs[index].spans = implode(characters);
+                let new_paragraph = Paragraph {
+                    spans: implode(rest),
+                    list: self.paragraphs[index].list,
+                    level: self.paragraphs[index].level,
+                };
+                self.paragraphs.insert(index + 1, new_paragraph);
+                index += 1;
+                offset = 0;
+            } else {
+                characters.insert(offset, StyledChar { character, style, link: link.clone() });
+                self.paragraphs[index].spans = implode(characters);
+                offset += 1;
+            }
+        }
+        self.update();
+    }
+
+    //removes the positions from start to end (not included). Removing a line break joins two paragraphs
+    pub fn remove(&mut self, start: usize, end: usize) {
+        let end = end.min(self.len());
+        if start >= end {
+            return;
+        }
+        let (first, first_offset) = self.locate(start);
+        let (last, last_offset) = self.locate(end);
+
+        let mut characters = explode(&self.paragraphs[first]);
+        characters.truncate(first_offset);
+        characters.extend(explode(&self.paragraphs[last]).into_iter().skip(last_offset));
+        self.paragraphs[first].spans = implode(characters);
+        self.paragraphs.drain(first + 1..=last);
+        self.update();
+    }
+
+    fn all_characters(&self, start: usize, end: usize, predicate: impl Fn(&StyledChar) -> bool) -> bool {
+        let (first, _) = self.locate(start);
+        let (last, _) = self.locate(end);
+        (first..=last).all(|index| {
+            let paragraph_start = self.get_paragraph_start(index);
+            explode(&self.paragraphs[index])
+                .iter()
+                .enumerate()
+                .filter(|(offset, _)| (start..end).contains(&(paragraph_start + offset)))
+                .all(|(_, character)| predicate(character))
+        })
+    }
+
+    fn for_each_character(&mut self, start: usize, end: usize, mut action: impl FnMut(&mut StyledChar)) {
+        let (first, _) = self.locate(start);
+        let (last, _) = self.locate(end);
+        for index in first..=last {
+            let paragraph_start = self.get_paragraph_start(index);
+            let mut characters = explode(&self.paragraphs[index]);
+            for (offset, character) in characters.iter_mut().enumerate() {
+                if (start..end).contains(&(paragraph_start + offset)) {
+                    action(character);
+                }
+            }
+            self.paragraphs[index].spans = implode(characters);
+        }
+        self.update();
+    }
+
+    //turns the style off if every character in the range has it, on otherwise
+    pub fn toggle_style(&mut self, start: usize, end: usize, flag: StyleFlag) {
+        let all = self.all_characters(start, end, |character| character.style.has(flag));
+        self.for_each_character(start, end, |character| character.style.set(flag, !all));
+    }
+
+    pub fn set_link(&mut self, start: usize, end: usize, link: Option<String>) {
+        self.for_each_character(start, end, |character| character.link = link.clone());
+    }
+
+    //makes the paragraphs from start to end list items of that kind, or normal paragraphs if they already are
+    pub fn toggle_list(&mut self, start: usize, end: usize, kind: ListKind) {
+        let (first, _) = self.locate(start);
+        let (last, _) = self.locate(end);
+        let all = self.paragraphs[first..=last].iter().all(|paragraph| paragraph.list == Some(kind));
+        for paragraph in &mut self.paragraphs[first..=last] {
+            paragraph.list = if all { None } else { Some(kind) };
+            paragraph.level = 0;
+        }
+        self.update();
+    }
+
+    //nests list items one level deeper, or less deep with a negative change
+    pub fn change_level(&mut self, start: usize, end: usize, change: i32) {
+        let (first, _) = self.locate(start);
+        let (last, _) = self.locate(end);
+        for paragraph in &mut self.paragraphs[first..=last] {
+            if paragraph.list.is_some() {
+                paragraph.level = (paragraph.level as i32 + change).max(0) as usize;
+            }
+        }
+        self.update();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_strings_load_and_save_like_before() {
+        let text: RichText = serde_json::from_str("\"first\\nsecond\"").unwrap();
+        assert!(text.is_plain());
+        assert_eq!(text.get_paragraphs().len(), 2);
+        assert_eq!(text.get_plain_text(), "first\nsecond");
+        assert_eq!(serde_json::to_string(&text).unwrap(), "\"first\\nsecond\"");
+        assert_eq!(serde_json::to_string(&RichText::default()).unwrap(), "\"\"");
+    }
+
+    #[test]
+    fn formatting_saves_as_paragraphs() {
+        let mut text = RichText::plain("bold words");
+        text.toggle_style(0, 4, StyleFlag::Bold);
+        assert!(!text.is_plain());
+        let saved = serde_json::to_string(&text).unwrap();
+        assert_eq!(saved, r#"{"paragraphs":[{"spans":[{"text":"bold","bold":true},{"text":" words"}]}]}"#);
+        let loaded: RichText = serde_json::from_str(&saved).unwrap();
+        assert_eq!(loaded, text);
+        assert_eq!(loaded.get_plain_text(), "bold words");
+
+        //taking the formatting away makes it a plain string again
+        text.toggle_style(0, 4, StyleFlag::Bold);
+        assert_eq!(serde_json::to_string(&text).unwrap(), "\"bold words\"");
+    }
+
+    #[test]
+    fn lists_and_links_survive() {
+        let data = r#"{"paragraphs":[{"spans":[{"text":"item","link":"https://example.org"}],"list":"numbered","level":1},{}]}"#;
+        let text: RichText = serde_json::from_str(data).unwrap();
+        assert_eq!(text.get_paragraphs()[0].list, Some(ListKind::Numbered));
+        assert_eq!(text.get_paragraphs()[0].level, 1);
+        assert_eq!(text.get_style_at(2).1.as_deref(), Some("https://example.org"));
+        assert_eq!(serde_json::from_str::<RichText>(&serde_json::to_string(&text).unwrap()).unwrap(), text);
+    }
+
+    #[test]
+    fn positions_span_paragraphs() {
+        let mut text = RichText::plain("ab\ncd");
+        assert_eq!(text.len(), 5);
+        assert_eq!(text.locate(3), (1, 0));
+        text.insert(2, "!", Style { italic: true, ..Style::default() }, None);
+        assert_eq!(text.get_plain_text(), "ab!\ncd");
+        text.remove(2, 5);
+        assert_eq!(text.get_plain_text(), "abd");
+        assert_eq!(text.get_paragraphs().len(), 1);
+    }
+}
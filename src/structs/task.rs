@@ -0,0 +1,308 @@
+use crate::structs::property::{Property, PropertyKind};
+use crate::utils::{civil_to_timestamp, timestamp_to_civil};
+use serde::{Deserialize, Serialize};
+
+//the todo side of a node. Nodes without one are plain notes, nodes with one get a checkbox. The due date is
+//written like date properties ("2024-05-01" or "2024-05-01 10:00", UTC), completion is unix time like
+//created/modified. Status changes go through tasks.rs, which also makes the next occurrence of recurring tasks
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TaskStatus {
+    #[default]
+    Open,
+    InProgress,
+    Done,
+    Cancelled,
+}
+
+impl TaskStatus {
+    pub const ALL: [TaskStatus; 4] = [TaskStatus::Open, TaskStatus::InProgress, TaskStatus::Done, TaskStatus::Cancelled];
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            TaskStatus::Open => "open",
+            TaskStatus::InProgress => "in-progress",
+            TaskStatus::Done => "done",
+            TaskStatus::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<TaskStatus, String> {
+        TaskStatus::ALL.into_iter()
+            .find(|status| status.get_name() == name.trim().to_lowercase())
+            .ok_or(format!("unknown status \"{}\" (open, in-progress, done, cancelled)", name))
+    }
+
+    //nothing left to do, one way or the other
+    pub fn is_closed(&self) -> bool {
+        matches!(self, TaskStatus::Done | TaskStatus::Cancelled)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskPriority {
+    High,
+    Medium,
+    Low,
+}
+
+impl TaskPriority {
+    pub const ALL: [TaskPriority; 3] = [TaskPriority::High, TaskPriority::Medium, TaskPriority::Low];
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            TaskPriority::High => "high",
+            TaskPriority::Medium => "medium",
+            TaskPriority::Low => "low",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<TaskPriority, String> {
+        TaskPriority::ALL.into_iter()
+            .find(|priority| priority.get_name() == name.trim().to_lowercase())
+            .ok_or(format!("unknown priority \"{}\" (high, medium, low)", name))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecurrenceUnit {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl RecurrenceUnit {
+    pub const ALL: [RecurrenceUnit; 4] = [RecurrenceUnit::Day, RecurrenceUnit::Week, RecurrenceUnit::Month, RecurrenceUnit::Year];
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            RecurrenceUnit::Day => "day",
+            RecurrenceUnit::Week => "week",
+            RecurrenceUnit::Month => "month",
+            RecurrenceUnit::Year => "year",
+        }
+    }
+
+    //"daily", "weekly"...
+    fn get_adverb(&self) -> &'static str {
+        match self {
+            RecurrenceUnit::Day => "daily",
+            RecurrenceUnit::Week => "weekly",
+            RecurrenceUnit::Month => "monthly",
+            RecurrenceUnit::Year => "yearly",
+        }
+    }
+
+    //Org's repeater letters
+    fn get_letter(&self) -> char {
+        self.get_name().chars().next().unwrap()
+    }
+}
+
+//a task that comes back every so many days, weeks, months or years
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Recurrence {
+    pub every: u32,
+    pub unit: RecurrenceUnit,
+}
+
+impl Recurrence {
+    //"daily", "weekly", "monthly", "yearly", "every 3 days", "every week", or Org's "+3d", "+2w", "+1m", "+1y"
+    pub fn parse(text: &str) -> Result<Recurrence, String> {
+        let text = text.trim().to_lowercase();
+        let error = || format!("\"{}\" is not a recurrence (daily, weekly, monthly, yearly, every 3 days, +2w)", text);
+        let parse_every = |every: &str| every.parse::<u32>().ok().filter(|every| *every > 0).ok_or_else(error);
+
+        if let Some(unit) = RecurrenceUnit::ALL.into_iter().find(|unit| unit.get_adverb() == text) {
+            return Ok(Recurrence { every: 1, unit });
+        }
+        if let Some(repeater) = text.strip_prefix('+') {
+            let letter = repeater.chars().last().ok_or_else(error)?;
+            let unit = RecurrenceUnit::ALL.into_iter().find(|unit| unit.get_letter() == letter).ok_or_else(error)?;
+            return Ok(Recurrence { every: parse_every(&repeater[..repeater.len() - letter.len_utf8()])?, unit });
+        }
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let (every, unit) = match words.as_slice() {
+            ["every", unit] => (1, *unit),
+            ["every", every, unit] => (parse_every(every)?, *unit),
+            _ => return Err(error()),
+        };
+        let unit = RecurrenceUnit::ALL.into_iter()
+            .find(|known| known.get_name() == unit.trim_end_matches('s'))
+            .ok_or_else(error)?;
+        Ok(Recurrence { every, unit })
+    }
+
+    pub fn get_name(&self) -> String {
+        match self.every {
+            1 => self.unit.get_adverb().to_string(),
+            every => format!("every {} {}s", every, self.unit.get_name()),
+        }
+    }
+
+    //Org's repeater, "+1m"
+    pub fn get_repeater(&self) -> String {
+        format!("+{}{}", self.every, self.unit.get_letter())
+    }
+
+    //the same time of day one period later. Days past the end of a shorter month become its last day
+    pub fn next(&self, timestamp: i64) -> i64 {
+        let ((year, month, day), (hour, minute, second)) = timestamp_to_civil(timestamp);
+        let time_of_day = (hour * 3600 + minute * 60 + second) as i64;
+        let every = self.every as i64;
+        let (year, month) = match self.unit {
+            RecurrenceUnit::Day => return timestamp + every * 86400,
+            RecurrenceUnit::Week => return timestamp + every * 7 * 86400,
+            RecurrenceUnit::Month => {
+                let months = year as i64 * 12 + (month as i64 - 1) + every;
+                (months.div_euclid(12) as i32, months.rem_euclid(12) as u32 + 1)
+            }
+            RecurrenceUnit::Year => (year + self.every as i32, month),
+        };
+        civil_to_timestamp(year, month, day.min(days_in_month(year, month))) + time_of_day
+    }
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    let (next_year, next_month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+    ((civil_to_timestamp(next_year, next_month, 1) - civil_to_timestamp(year, month, 1)) / 86400) as u32
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Task {
+    #[serde(default)]
+    pub status: TaskStatus,
+    #[serde(default)]
+    due: Option<String>, //always in the form date properties are kept in, see set_due
+    #[serde(default)]
+    pub priority: Option<TaskPriority>,
+    #[serde(default)]
+    pub recurrence: Option<Recurrence>,
+    #[serde(default)]
+    pub completed: Option<i64>, //unix time, while it's done
+}
+
+impl Task {
+    pub fn new() -> Task {
+        Task::default()
+    }
+
+    pub fn get_due(&self) -> Option<&String> {
+        self.due.as_ref()
+    }
+
+    //"2024-05-01" or "2024-05-01 10:00", None to take the due date away
+    pub fn set_due(&mut self, due: Option<&str>) -> Result<(), String> {
+        self.due = match due {
+            Some(due) => Some(Property::parse(&PropertyKind::Date, due)?.get_text().clone()),
+            None => None,
+        };
+        Ok(())
+    }
+
+    //unix time of the due date, midnight for dates without a time
+    pub fn get_due_time(&self) -> Option<i64> {
+        Property::parse(&PropertyKind::Date, self.due.as_ref()?).ok()?.as_date()
+    }
+
+    //past the due time, or past the due day for dates without a time. Closed tasks are never late
+    pub fn is_overdue(&self, now: i64) -> bool {
+        let due = match (self.get_due_time(), self.status.is_closed()) {
+            (Some(due), false) => due,
+            _ => return false,
+        };
+        let has_time = self.due.as_ref().map_or(false, |due| due.len() > 10);
+        now >= if has_time { due } else { due + 86400 }
+    }
+
+    //the due date of the next occurrence: one period after this one, or after today for tasks without a due date.
+    //None if the task doesn't recur
+    pub fn get_next_due(&self, now: i64) -> Option<String> {
+        let recurrence = self.recurrence?;
+        let (due, has_time) = match self.get_due_time() {
+            Some(due) => (due, self.due.as_ref().map_or(false, |due| due.len() > 10)),
+            None => (now - now.rem_euclid(86400), false),
+        };
+        let ((year, month, day), (hour, minute, _)) = timestamp_to_civil(recurrence.next(due));
+        Some(match has_time {
+            true => format!("{:04}-{:02}-{:02} {:02}:{:02}", year, month, day, hour, minute),
+            false => format!("{:04}-{:02}-{:02}", year, month, day),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(year: i32, month: u32, day: u32, hour: i64) -> i64 {
+        civil_to_timestamp(year, month, day) + hour * 3600
+    }
+
+    #[test]
+    fn parse_recurrences() {
+        let parsed = |text: &str| Recurrence::parse(text).map(|recurrence| (recurrence.every, recurrence.unit));
+        assert_eq!(parsed("Weekly"), Ok((1, RecurrenceUnit::Week)));
+        assert_eq!(parsed("+2w"), Ok((2, RecurrenceUnit::Week)));
+        assert_eq!(parsed("+10d"), Ok((10, RecurrenceUnit::Day)));
+        assert_eq!(parsed("every 3 days"), Ok((3, RecurrenceUnit::Day)));
+        assert_eq!(parsed("every month"), Ok((1, RecurrenceUnit::Month)));
+        for bad in ["", "+", "+w", "+0m", "+2h", "every 0 weeks", "every -1 days", "every 2 fortnights", "sometimes"] {
+            assert!(Recurrence::parse(bad).is_err(), "{}", bad);
+        }
+
+        assert_eq!(Recurrence::parse("+1y").unwrap().get_name(), "yearly");
+        assert_eq!(Recurrence::parse("every 2 weeks").unwrap().get_repeater(), "+2w");
+    }
+
+    #[test]
+    fn next_occurrences() {
+        let monthly = Recurrence { every: 1, unit: RecurrenceUnit::Month };
+        //month ends are clamped, leap years included, and the time of day stays
+        assert_eq!(monthly.next(at(2024, 1, 31, 10)), at(2024, 2, 29, 10));
+        assert_eq!(monthly.next(at(2023, 1, 31, 0)), at(2023, 2, 28, 0));
+        assert_eq!(Recurrence { every: 2, unit: RecurrenceUnit::Month }.next(at(2024, 12, 31, 0)), at(2025, 2, 28, 0));
+        assert_eq!(Recurrence::parse("+2w").unwrap().next(at(2024, 12, 25, 8)), at(2025, 1, 8, 8));
+        assert_eq!(Recurrence::parse("yearly").unwrap().next(at(2024, 2, 29, 0)), at(2025, 2, 28, 0));
+        assert_eq!(days_in_month(2024, 2), 29);
+        assert_eq!(days_in_month(2100, 2), 28);
+        assert_eq!(days_in_month(2024, 12), 31);
+    }
+
+    #[test]
+    fn due_dates() {
+        let mut task = Task::new();
+        assert!(task.set_due(Some("2024-02-30")).is_err());
+        task.set_due(Some("2024-01-31")).unwrap();
+        assert_eq!(task.get_next_due(0), None);
+
+        //date-only tasks are late once their day is over, timed ones right at their time
+        assert!(!task.is_overdue(at(2024, 1, 31, 23)));
+        assert!(task.is_overdue(at(2024, 2, 1, 0)));
+        task.set_due(Some("2024-01-31 10:00")).unwrap();
+        assert!(task.is_overdue(at(2024, 1, 31, 10)));
+        task.status = TaskStatus::Done;
+        assert!(!task.is_overdue(at(2025, 1, 1, 0)));
+
+        task.recurrence = Some(Recurrence::parse("monthly").unwrap());
+        assert_eq!(task.get_next_due(0).as_deref(), Some("2024-02-29 10:00"));
+        //no due date: one period after today
+        task.set_due(None).unwrap();
+        assert_eq!(task.get_next_due(at(2024, 3, 15, 13)).as_deref(), Some("2024-04-15"));
+    }
+
+    #[test]
+    fn names_parse_back() {
+        for status in TaskStatus::ALL {
+            assert_eq!(TaskStatus::parse(status.get_name()), Ok(status));
+        }
+        for priority in TaskPriority::ALL {
+            assert_eq!(TaskPriority::parse(priority.get_name()), Ok(priority));
+        }
+        assert_eq!(serde_json::to_string(&TaskStatus::InProgress).unwrap(), "\"in-progress\"");
+    }
+}
@@ -0,0 +1,182 @@
+use crate::formats::{find_node, register_node};
+use crate::hierarchy;
+use crate::structs::task::{Task, TaskStatus};
+use crate::utils::now;
+
+//status changes of todo nodes (see structs/task.rs). Done tasks remember when they were done, and a recurring
+//task that gets done comes back as a new node: same text, tags and properties, due one period later, right
+//after the done one among its siblings. The rule moves on to the new node, so redoing the old one doesn't
+//make another.
+
+//Ok(Some(id)) is the next occurrence, when there is one
+pub fn set_status(id: i64, status: TaskStatus) -> Result<Option<i64>, String> {
+    let node = find_node(id).ok_or(format!("there is no node {}", id))?;
+    let mut task = node.read().unwrap().get_task().cloned().ok_or(format!("node {} is not a task", id))?;
+    if task.status == status {
+        return Ok(None);
+    }
+    task.status = status;
+    task.completed = match status {
+        TaskStatus::Done => Some(now()),
+        _ => None,
+    };
+
+    //the next one, made before this one loses its rule
+    let next = match (status, task.get_next_due(now())) {
+        (TaskStatus::Done, Some(due)) => {
+            let mut next_task = task.clone();
+            next_task.status = TaskStatus::Open;
+            next_task.completed = None;
+            next_task.set_due(Some(&due))?;
+            task.recurrence = None;
+
+            let mut next = node.read().unwrap().duplicate();
+            next.set_task(Some(next_task));
+            let next_id = next.get_id();
+            register_node(next);
+            if let Some(parent) = hierarchy::get_parent(id) {
+                let index = hierarchy::get_children(parent).iter().position(|child| *child == id).map(|index| index + 1);
+                hierarchy::set_parent(next_id, Some(parent), index)?;
+            }
+            Some(next_id)
+        }
+        _ => None,
+    };
+
+    node.write().unwrap().set_task(Some(task));
+    Ok(next)
+}
+
+//what clicking a checkbox does: open and in progress tasks get done, done and cancelled ones open again
+pub fn toggle(id: i64) -> Result<Option<i64>, String> {
+    let status = find_node(id)
+        .and_then(|node| Some(node.read().unwrap().get_task()?.status))
+        .ok_or(format!("node {} is not a task", id))?;
+    set_status(id, if status.is_closed() { TaskStatus::Open } else { TaskStatus::Done })
+}
+
+//plain nodes become open tasks, unless they're all tasks already: then they're back to plain notes.
+//Ok is which of the two it was, true for tasks
+pub fn toggle_all(ids: &[i64]) -> Result<bool, String> {
+    let nodes = ids.iter().map(|id| find_node(*id).ok_or(format!("there is no node {}", id))).collect::<Result<Vec<_>, _>>()?;
+    if nodes.is_empty() {
+        return Err("there are no nodes to make tasks of".to_string());
+    }
+    let all_tasks = nodes.iter().all(|node| node.read().unwrap().get_task().is_some());
+    for node in &nodes {
+        let mut node = node.write().unwrap();
+        if all_tasks {
+            node.set_task(None);
+        } else if node.get_task().is_none() {
+            node.set_task(Some(Task::new()));
+        }
+    }
+    Ok(!all_tasks)
+}
+
+//each task among ids takes the status after the one it has: open, in progress, done, cancelled, open...
+//plain nodes stay as they are. Ok is (task, new status, next occurrence) for every task
+pub fn cycle_statuses(ids: &[i64]) -> Result<Vec<(i64, TaskStatus, Option<i64>)>, String> {
+    let tasks: Vec<(i64, TaskStatus)> = ids.iter()
+        .filter_map(|id| Some((*id, find_node(*id)?.read().unwrap().get_task()?.status)))
+        .collect();
+    if tasks.is_empty() {
+        return Err("there are no tasks among the selected nodes".to_string());
+    }
+    tasks.into_iter().map(|(id, status)| {
+        let index = TaskStatus::ALL.iter().position(|known| *known == status).unwrap();
+        let status = TaskStatus::ALL[(index + 1) % TaskStatus::ALL.len()];
+        Ok((id, status, set_status(id, status)?))
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::test_support::make_nodes;
+    use crate::structs::task::Recurrence;
+
+    fn get_task(id: i64) -> Option<Task> {
+        find_node(id)?.read().unwrap().get_task().cloned()
+    }
+
+    #[test]
+    fn checkboxes_toggle() {
+        let [note, task] = make_nodes();
+        find_node(task).unwrap().write().unwrap().set_task(Some(Task::new()));
+        assert!(toggle(note).is_err());
+
+        assert_eq!(toggle(task), Ok(None));
+        let done = get_task(task).unwrap();
+        assert_eq!(done.status, TaskStatus::Done);
+        assert!(done.completed.is_some());
+
+        assert_eq!(toggle(task), Ok(None));
+        let open = get_task(task).unwrap();
+        assert_eq!(open.status, TaskStatus::Open);
+        assert!(open.completed.is_none());
+    }
+
+    #[test]
+    fn recurring_tasks_come_back() {
+        let [parent, first, second] = make_nodes();
+        for child in [first, second] {
+            hierarchy::set_parent(child, Some(parent), None).unwrap();
+        }
+        let mut rule = Task::new();
+        rule.recurrence = Some(Recurrence::parse("every week").unwrap());
+        rule.set_due(Some("2030-01-07")).unwrap();
+        find_node(first).unwrap().write().unwrap().set_task(Some(rule));
+
+        let next = set_status(first, TaskStatus::Done).unwrap().unwrap();
+        let next_task = get_task(next).unwrap();
+        assert_eq!(next_task.status, TaskStatus::Open);
+        assert_eq!(next_task.get_due(), Some(&"2030-01-14".to_string()));
+        assert!(next_task.recurrence.is_some());
+        //right after the done one, which keeps no rule to come back again
+        assert_eq!(hierarchy::get_children(parent), vec![first, next, second]);
+        assert!(get_task(first).unwrap().recurrence.is_none());
+        assert_eq!(toggle(first), Ok(None));
+        assert_eq!(toggle(first), Ok(None));
+    }
+
+    fn get_status(id: i64) -> Option<TaskStatus> {
+        Some(find_node(id)?.read().unwrap().get_task()?.status)
+    }
+
+    #[test]
+    fn toggling_many() {
+        let [a, b] = make_nodes();
+        assert_eq!(toggle_all(&[a]), Ok(true));
+        //one of them isn't a task yet: both are
+        assert_eq!(toggle_all(&[a, b]), Ok(true));
+        assert_eq!((get_status(a), get_status(b)), (Some(TaskStatus::Open), Some(TaskStatus::Open)));
+        assert_eq!(toggle_all(&[a, b]), Ok(false));
+        assert_eq!((get_status(a), get_status(b)), (None, None));
+        assert!(toggle_all(&[]).is_err());
+        assert!(toggle_all(&[-1]).is_err());
+    }
+
+    #[test]
+    fn cycling_statuses() {
+        let [note, task, recurring] = make_nodes();
+        toggle_all(&[task, recurring]).unwrap();
+        assert!(cycle_statuses(&[note]).is_err());
+
+        //plain notes are left out
+        let changed = cycle_statuses(&[note, task]).unwrap();
+        assert_eq!(changed, vec![(task, TaskStatus::InProgress, None)]);
+        assert_eq!(get_status(note), None);
+
+        //in progress to done brings a recurring task back
+        let node = find_node(recurring).unwrap();
+        let mut rule = node.read().unwrap().get_task().cloned().unwrap();
+        rule.recurrence = Some(Recurrence::parse("every week").unwrap());
+        node.write().unwrap().set_task(Some(rule));
+        cycle_statuses(&[recurring]).unwrap();
+        match cycle_statuses(&[recurring]).unwrap().as_slice() {
+            [(id, TaskStatus::Done, Some(next))] if *id == recurring => assert_eq!(get_status(*next), Some(TaskStatus::Open)),
+            other => panic!("expected the next occurrence, got {:?}", other),
+        }
+    }
+}
@@ -1,4 +1,7 @@
+use crate::structs::module::Module;
 use std::sync::Arc;
 use std::sync::RwLock;
 
 pub type DoublePointerSafe<T> = Arc<RwLock<Arc<RwLock<T>>>>;
+
+pub type ModuleRef = Arc<RwLock<Box<dyn Module + Send + Sync>>>;
@@ -16,4 +16,113 @@ pub fn viewport_to_window(point: Vec2, window: Vec2, viewport: Rect) -> Vec2 {
     let x = x*window.x + window.x/2.0;
     let y = y*window.y + window.y/2.0;
     Vec2::new(x, y)
-}
\ No newline at end of file
+}
+
+//smallest rectangle containing both
+pub fn union_rect(a: Rect, b: Rect) -> Rect {
+    Rect::from_tuples(
+        (f32::min(a.left(), b.left()), f32::min(a.top(), b.top())),
+        (f32::max(a.right(), b.right()), f32::max(a.bottom(), b.bottom())),
+    )
+}
+
+//seconds since 1970-01-01 UTC
+pub fn now() -> i64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs() as i64
+}
+
+//unix time -> ((year, month, day), (hour, minute, second)) in UTC, with Howard Hinnant's civil_from_days
+pub fn timestamp_to_civil(timestamp: i64) -> ((i32, u32, u32), (u32, u32, u32)) {
+    let (days, seconds) = (timestamp.div_euclid(86400), timestamp.rem_euclid(86400));
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    let time = (seconds / 3600, seconds % 3600 / 60, seconds % 60);
+    ((year as i32, month as u32, day as u32), (time.0 as u32, time.1 as u32, time.2 as u32))
+}
+
+//the other way around (days_from_civil), midnight UTC of that day
+pub fn civil_to_timestamp(year: i32, month: u32, day: u32) -> i64 {
+    let year = year as i64 - if month <= 2 { 1 } else { 0 };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    (era * 146097 + day_of_era - 719468) * 86400
+}
+
+//"2024-05-01 10:00" UTC
+pub fn format_timestamp(timestamp: i64) -> String {
+    let ((year, month, day), (hour, minute, _)) = timestamp_to_civil(timestamp);
+    format!("{:04}-{:02}-{:02} {:02}:{:02}", year, month, day, hour, minute)
+}
+
+//#tags outside of code, like Obsidian: letters, numbers, "_", "-" and "/" but not only numbers
+pub fn find_hashtags(body: &str) -> Vec<String> {
+    let mut tags = Vec::new();
+    let mut in_code_block = false;
+    for line in body.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        if in_code_block {
+            continue;
+        }
+
+        let characters: Vec<char> = line.chars().collect();
+        let mut in_code = false;
+        for index in 0..characters.len() {
+            if characters[index] == '`' {
+                in_code = !in_code;
+            }
+            if in_code || characters[index] != '#' || (index > 0 && !characters[index - 1].is_whitespace()) {
+                continue;
+            }
+            let tag: String = characters[index + 1..]
+                .iter()
+                .take_while(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '/'))
+                .collect();
+            if !tag.is_empty() && !tag.chars().all(|c| c.is_ascii_digit()) {
+                tags.push(tag);
+            }
+        }
+    }
+    tags
+}
+
+//the first length characters, with "..." when there was more
+pub fn shorten(text: &str, length: usize) -> String {
+    if text.chars().count() > length {
+        format!("{}...", text.chars().take(length).collect::<String>())
+    } else {
+        text.to_string()
+    }
+}
+
+//"%20" -> " ", for paths in urls
+pub fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::new();
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'%' && index + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[index + 1..index + 3]).unwrap_or_default();
+            if let Ok(byte) = u8::from_str_radix(hex, 16) {
+                decoded.push(byte);
+                index += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[index]);
+        index += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
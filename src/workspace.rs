@@ -0,0 +1,288 @@
+use crate::hierarchy;
+use crate::structs::link::{Link, LinkType, LINK_COUNTER};
+use crate::structs::node::{Node, NODE_COUNTER};
+use crate::types::ModuleRef;
+use crate::{LINKS, MODULES, MODULE_NAMES, NODES};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, RwLock};
+
+//the workspace is the "data" folder in the current directory. Everything that is not window related
+//(loading/saving nodes and links, running modules headless) goes through here so that the cli can reuse it.
+
+//per workspace settings, in data/settings.data
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Settings {
+    #[serde(default)]
+    pub author: String, //goes into every node created in this workspace
+    #[serde(default = "LinkType::get_defaults")]
+    pub link_types: Vec<LinkType>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings { author: String::new(), link_types: LinkType::get_defaults() }
+    }
+}
+
+lazy_static! {
+    static ref SETTINGS: RwLock<Settings> = RwLock::new(Settings::default());
+    //normalized tag -> ids of the nodes that have it, explicitly or as a #hashtag
+    static ref TAG_INDEX: RwLock<BTreeMap<String, BTreeSet<i64>>> = RwLock::new(BTreeMap::new());
+}
+
+pub fn get_settings() -> Settings {
+    SETTINGS.read().unwrap().clone()
+}
+
+pub fn set_settings(settings: Settings) {
+    *SETTINGS.write().unwrap() = settings;
+}
+
+//the type a link's relation names, if this workspace defines it
+pub fn get_link_type(relation: &str) -> Option<LinkType> {
+    SETTINGS.read().unwrap().link_types.iter().find(|link_type| link_type.name == relation).cloned()
+}
+
+//tags are the same whatever their case, with or without "#"
+pub fn normalize_tag(tag: &str) -> String {
+    tag.trim().trim_start_matches('#').to_lowercase()
+}
+
+//(re)files a node under its current tags. Nodes tell the index themselves whenever they change
+pub fn index_node(node: &Node) {
+    if node.get_id() < 0 {
+        return;
+    }
+    let tags: BTreeSet<String> = node.get_all_tags().iter().map(|tag| normalize_tag(tag)).collect();
+    let mut index = TAG_INDEX.write().unwrap();
+    for (tag, ids) in index.iter_mut() {
+        if !tags.contains(tag) {
+            ids.remove(&node.get_id());
+        }
+    }
+    for tag in tags {
+        index.entry(tag).or_default().insert(node.get_id());
+    }
+    index.retain(|_, ids| !ids.is_empty());
+}
+
+pub fn unindex_node(id: i64) {
+    let mut index = TAG_INDEX.write().unwrap();
+    index.values_mut().for_each(|ids| {
+        ids.remove(&id);
+    });
+    index.retain(|_, ids| !ids.is_empty());
+}
+
+pub fn rebuild_tag_index() {
+    TAG_INDEX.write().unwrap().clear();
+    for node in NODES.read().unwrap().iter() {
+        index_node(&node.read().unwrap());
+    }
+}
+
+//every tag with the number of nodes that have it, by name
+pub fn get_tag_counts() -> Vec<(String, usize)> {
+    TAG_INDEX.read().unwrap().iter().map(|(tag, ids)| (tag.clone(), ids.len())).collect()
+}
+
+//ids of the nodes with that tag
+pub fn find_tagged(tag: &str) -> BTreeSet<i64> {
+    TAG_INDEX.read().unwrap().get(&normalize_tag(tag)).cloned().unwrap_or_default()
+}
+
+fn read_data_file(path: &str) -> String {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open(path)
+        .unwrap();
+    let mut data = String::new();
+    file.read_to_string(&mut data).unwrap();
+    data
+}
+
+//returns the problems found in the data (and fixed on the way), for the user to know about
+pub fn load() -> Vec<String> {
+    //create data folder if it doesn't exist
+    std::fs::create_dir_all("data").unwrap();
+
+    let data = read_data_file("data/settings.data");
+    if !data.is_empty() {
+        set_settings(serde_json::from_str(&data).unwrap());
+    }
+
+    //load nodes from nodes.data
+    let data = read_data_file("data/nodes.data");
+
+    let nodes_owned: Vec<Node> = if !data.is_empty() {
+        serde_json::from_str(&data).unwrap()
+    } else {
+        Vec::new()
+    };
+
+    //get id as the maximum id of the nodes
+    nodes_owned.iter().for_each(|node| {
+        if node.get_id() > NODE_COUNTER.load(std::sync::atomic::Ordering::Relaxed) {
+            NODE_COUNTER.store(node.get_id(), std::sync::atomic::Ordering::Relaxed);
+        }
+    });
+    NODE_COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+
+    //refcellize the nodes vector
+    nodes_owned.iter().for_each(|node| {
+        NODES
+            .write()
+            .unwrap()
+            .push(Arc::new(RwLock::new(node.clone())))
+    });
+
+    //same thing for links
+    let data = read_data_file("data/links.data");
+
+    let links_owned: Vec<Link> = if !data.is_empty() {
+        serde_json::from_str(&data).unwrap()
+    } else {
+        Vec::new()
+    };
+
+    links_owned.iter().for_each(|link| {
+        if link.get_id() >= LINK_COUNTER.load(std::sync::atomic::Ordering::Relaxed) {
+            LINK_COUNTER.store(link.get_id() + 1, std::sync::atomic::Ordering::Relaxed);
+        }
+    });
+
+    links_owned.into_iter().for_each(|link| {
+        LINKS.write().unwrap().push(Arc::new(RwLock::new(link)))
+    });
+
+    rebuild_tag_index();
+    hierarchy::repair()
+}
+
+pub fn save() {
+    for module in MODULES.read().unwrap().iter() {
+        println!("unloading module \"{}\"", module.read().unwrap().get_name());
+        module.write().unwrap().unload();
+    }
+
+    //save nodes to data/nodes.data using serde
+
+    let mut file = File::create("data/nodes.data").unwrap();
+
+    let mut nodes = Vec::new();
+    for node in NODES.read().unwrap().iter() {
+        nodes.push(node.read().unwrap().to_owned());
+    }
+
+    let serialized = serde_json::to_string(&nodes).unwrap();
+    file.write_all(serialized.as_bytes()).unwrap();
+
+    let mut file = File::create("data/links.data").unwrap();
+
+    let links: Vec<Link> = LINKS.read().unwrap().iter().map(|link| link.read().unwrap().to_owned()).collect();
+
+    let serialized = serde_json::to_string(&links).unwrap();
+    file.write_all(serialized.as_bytes()).unwrap();
+
+    save_settings();
+}
+
+pub fn save_settings() {
+    let mut file = File::create("data/settings.data").unwrap();
+    let serialized = serde_json::to_string_pretty(&get_settings()).unwrap();
+    file.write_all(serialized.as_bytes()).unwrap();
+}
+
+//finds a module by name, or the first one (the default active module) if no name is given
+pub fn find_module(name: Option<&str>) -> Option<ModuleRef> {
+    let modules = MODULES.read().unwrap();
+    match name {
+        Some(name) => MODULE_NAMES.read().unwrap().iter()
+            .position(|(module, _)| module.eq_ignore_ascii_case(name))
+            .map(|index| modules[index].clone()),
+        None => modules.first().cloned(),
+    }
+}
+
+//names of the modules, in the side panel's order
+pub fn get_module_names() -> Vec<String> {
+    //MODULES fills the names in when it's first used
+    let _modules = MODULES.read().unwrap();
+    MODULE_NAMES.read().unwrap().iter().map(|(name, _)| name.clone()).collect()
+}
+
+//whether the module of that name shows nodes, see Module::shows_nodes. False if there is no such module
+pub fn shows_nodes(name: &str) -> bool {
+    let _modules = MODULES.read().unwrap();
+    MODULE_NAMES.read().unwrap().iter().any(|(module, shows)| *shows && module.eq_ignore_ascii_case(name))
+}
+
+//what find_nodes keeps. Nodes without timestamps never match a time bound
+#[derive(Clone, Debug, Default)]
+pub struct NodeFilter {
+    pub author: Option<String>,
+    pub tags: Vec<String>, //nodes need all of them
+    pub created_since: Option<i64>,
+    pub modified_since: Option<i64>,
+    pub modified_before: Option<i64>,
+}
+
+impl NodeFilter {
+    pub fn matches(&self, node: &Node) -> bool {
+        let after = |time: Option<i64>, bound: Option<i64>| bound.map_or(true, |bound| time.map_or(false, |time| time >= bound));
+        let before = |time: Option<i64>, bound: Option<i64>| bound.map_or(true, |bound| time.map_or(false, |time| time < bound));
+
+        self.author.as_ref().map_or(true, |author| node.get_author().eq_ignore_ascii_case(author))
+            && after(node.get_created(), self.created_since)
+            && after(node.get_modified(), self.modified_since)
+            && before(node.get_modified(), self.modified_before)
+            && self.tags.iter().all(|tag| node.has_tag(tag))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NodeOrder {
+    Created,  //newest first
+    Modified, //most recently changed first
+    Author,
+    Content,
+}
+
+impl NodeOrder {
+    pub fn parse(name: &str) -> Result<NodeOrder, String> {
+        match name {
+            "created" => Ok(NodeOrder::Created),
+            "modified" => Ok(NodeOrder::Modified),
+            "author" => Ok(NodeOrder::Author),
+            "content" => Ok(NodeOrder::Content),
+            name => Err(format!("unknown order \"{}\" (created, modified, author, content)", name)),
+        }
+    }
+}
+
+//nodes matching the filter, in that order (ties and no order: by id)
+pub fn find_nodes(filter: &NodeFilter, order: Option<NodeOrder>) -> Vec<Arc<RwLock<Node>>> {
+    let mut nodes: Vec<Arc<RwLock<Node>>> = NODES.read().unwrap().iter()
+        .filter(|node| filter.matches(&node.read().unwrap()))
+        .cloned()
+        .collect();
+
+    nodes.sort_by(|a, b| {
+        let (a, b) = (a.read().unwrap(), b.read().unwrap());
+        let ordering = match order {
+            Some(NodeOrder::Created) => b.get_created().cmp(&a.get_created()),
+            Some(NodeOrder::Modified) => b.get_modified().cmp(&a.get_modified()),
+            Some(NodeOrder::Author) => a.get_author().to_lowercase().cmp(&b.get_author().to_lowercase()),
+            Some(NodeOrder::Content) => a.get_content().to_lowercase().cmp(&b.get_content().to_lowercase()),
+            None => std::cmp::Ordering::Equal,
+        };
+        ordering.then(a.get_id().cmp(&b.get_id()))
+    });
+    nodes
+}